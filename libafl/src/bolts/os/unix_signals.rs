@@ -91,7 +91,7 @@ pub use libc::ucontext_t;
 use libc::{
     c_int, malloc, sigaction, sigaddset, sigaltstack, sigemptyset, stack_t, SA_NODEFER, SA_ONSTACK,
     SA_SIGINFO, SIGABRT, SIGALRM, SIGBUS, SIGFPE, SIGHUP, SIGILL, SIGINT, SIGKILL, SIGPIPE,
-    SIGQUIT, SIGSEGV, SIGTERM, SIGTRAP, SIGUSR2,
+    SIGQUIT, SIGSEGV, SIGTERM, SIGTRAP, SIGUSR1, SIGUSR2,
 };
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
@@ -119,6 +119,8 @@ pub enum Signal {
     SigPipe = SIGPIPE,
     /// `SIGSEGV` signal id
     SigSegmentationFault = SIGSEGV,
+    /// `SIGUSR1` signal id
+    SigUser1 = SIGUSR1,
     /// `SIGUSR2` signal id
     SigUser2 = SIGUSR2,
     /// `SIGALARM` signal id
@@ -166,6 +168,7 @@ impl Display for Signal {
             Signal::SigIllegalInstruction => write!(f, "SIGILL")?,
             Signal::SigPipe => write!(f, "SIGPIPE")?,
             Signal::SigSegmentationFault => write!(f, "SIGSEGV")?,
+            Signal::SigUser1 => write!(f, "SIGUSR1")?,
             Signal::SigUser2 => write!(f, "SIGUSR2")?,
             Signal::SigAlarm => write!(f, "SIGALRM")?,
             Signal::SigHangUp => write!(f, "SIGHUP")?,
@@ -274,6 +277,14 @@ pub unsafe fn setup_signal_handler<T: 'static + Handler>(handler: &mut T) -> Res
     Ok(())
 }
 
+/// Raise the given [`Signal`] in the current thread, its handler runs before this returns.
+/// Used by instrumentation to report a timeout or an out-of-memory to the in-process executors.
+pub fn raise(signal: Signal) {
+    unsafe {
+        libc::raise(signal as i32);
+    }
+}
+
 /// Function to get the current [`ucontext_t`] for this process.
 /// This calls the libc `getcontext` function under the hood.
 /// It can be useful, for example for `dump_regs`.
@@ -257,6 +257,24 @@ macro_rules! create_serde_registry_for_trait {
                     self.map.insert(unpack_type_id(TypeId::of::<T>()), t);
                 }
 
+                /// Retain only the elements whose [`TypeId`] matches the predicate.
+                #[inline]
+                pub fn retain<F>(&mut self, mut f: F)
+                where
+                    F: FnMut(&TypeId) -> bool,
+                {
+                    self.map.retain(|id, _| f(&pack_type_id(*id)));
+                }
+
+                /// Move all elements of `other` into this map.
+                /// Elements of types already present in this map are kept, the ones in `other` are dropped.
+                #[inline]
+                pub fn merge(&mut self, other: Self) {
+                    for (id, val) in other.map {
+                        self.map.entry(id).or_insert(val);
+                    }
+                }
+
                 /// Returns the count of elements in this map.
                 #[must_use]
                 #[inline]
@@ -3,7 +3,7 @@
 use alloc::collections::vec_deque::VecDeque;
 use core::cell::RefCell;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{
     corpus::{
@@ -132,6 +132,24 @@ where
             cache_max_len,
         })
     }
+
+    /// Creates the [`CachedOnDiskCorpus`] writing an `AFL++`-style queue to `out_dir/queue`.
+    /// See [`OnDiskCorpus::afl_queue`].
+    pub fn afl_queue<P>(out_dir: P, cache_max_len: usize) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        if cache_max_len == 0 {
+            return Err(Error::IllegalArgument(
+                "The max cache len in CachedOnDiskCorpus cannot be 0".into(),
+            ));
+        }
+        Ok(Self {
+            inner: OnDiskCorpus::afl_queue(out_dir)?,
+            cached_indexes: RefCell::new(VecDeque::new()),
+            cache_max_len,
+        })
+    }
 }
 
 /// ``CachedOnDiskCorpus`` Python bindings
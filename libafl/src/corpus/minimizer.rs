@@ -0,0 +1,218 @@
+//! Corpus minimization (`cmin`) reduces a corpus to a subset with the same coverage.
+//! The entries are picked greedily, preferring testcases that add the most coverage per weight.
+
+use alloc::{
+    collections::BTreeSet,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+
+use crate::{
+    bolts::current_time,
+    corpus::Corpus,
+    executors::{Executor, HasObservers},
+    fuzzer::{ExecutesInput, HasScheduler},
+    inputs::Input,
+    observers::{MapObserver, ObserversTuple},
+    schedulers::{FavFactor, LenTimeMulFavFactor, Scheduler, UniformFavFactor},
+    state::HasCorpus,
+    Error,
+};
+
+/// Minimizes a [`Corpus`], removing all testcases that are not needed to keep its coverage.
+pub trait CorpusMinimizer<I, S>
+where
+    I: Input,
+    S: HasCorpus<I>,
+{
+    /// Minimize the corpus of the given state, executing each entry once.
+    fn minimize<CS, E, EM, OT, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut S,
+    ) -> Result<(), Error>
+    where
+        CS: Scheduler<I, S>,
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+        OT: ObserversTuple<I, S>,
+        Z: ExecutesInput<I, OT, S, Z> + HasScheduler<CS, I, S>;
+}
+
+/// Minimizes a corpus using the entries of a [`MapObserver`] as coverage.
+/// Each pair of map index and (non-initial) value counts as one element to cover,
+/// so bucketed hitcounts are preserved, too.
+/// The weight of each testcase is computed by the [`FavFactor`] `TS`, lower is better.
+#[derive(Debug)]
+pub struct MapCorpusMinimizer<I, O, S, TS>
+where
+    I: Input,
+    O: MapObserver,
+    TS: FavFactor<I>,
+{
+    obs_name: String,
+    phantom: PhantomData<(I, O, S, TS)>,
+}
+
+/// A [`MapCorpusMinimizer`] that prefers small and fast testcases
+pub type StdCorpusMinimizer<I, O, S> = MapCorpusMinimizer<I, O, S, LenTimeMulFavFactor<I>>;
+
+/// A [`MapCorpusMinimizer`] that only minimizes the number of testcases
+pub type UnweightedCorpusMinimizer<I, O, S> = MapCorpusMinimizer<I, O, S, UniformFavFactor<I>>;
+
+impl<I, O, S, TS> MapCorpusMinimizer<I, O, S, TS>
+where
+    I: Input,
+    O: MapObserver,
+    TS: FavFactor<I>,
+{
+    /// Create a new [`MapCorpusMinimizer`] for the given observer
+    #[must_use]
+    pub fn new(obs: &O) -> Self {
+        Self {
+            obs_name: obs.name().to_string(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Greedy weighted set cover: returns the indexes of the selected sets, in the order they were picked.
+/// Each set is given as its elements and its weight.
+/// In each round, the set with the most uncovered elements per weight is picked.
+#[must_use]
+pub fn greedy_set_cover<T>(sets: &[(BTreeSet<T>, u64)]) -> Vec<usize>
+where
+    T: Ord + Clone,
+{
+    let mut uncovered: BTreeSet<T> = sets
+        .iter()
+        .flat_map(|(set, _)| set.iter().cloned())
+        .collect();
+    let mut candidates: Vec<usize> = (0..sets.len()).collect();
+    let mut selected = Vec::new();
+
+    while !uncovered.is_empty() {
+        let mut best: Option<(usize, u128, u128)> = None;
+        candidates.retain(|&idx| {
+            let (set, weight) = &sets[idx];
+            let gain = set.iter().filter(|elem| uncovered.contains(elem)).count() as u128;
+            if gain == 0 {
+                return false;
+            }
+            let weight = u128::from(*weight).max(1);
+            // gain / weight > best_gain / best_weight, without divisions
+            match best {
+                Some((_, best_gain, best_weight)) if gain * best_weight <= best_gain * weight => {}
+                _ => best = Some((idx, gain, weight)),
+            }
+            true
+        });
+
+        let (idx, _, _) = best.expect("Uncovered elements without any set covering them");
+        for elem in &sets[idx].0 {
+            uncovered.remove(elem);
+        }
+        candidates.retain(|&c| c != idx);
+        selected.push(idx);
+    }
+
+    selected
+}
+
+impl<I, O, S, TS> CorpusMinimizer<I, S> for MapCorpusMinimizer<I, O, S, TS>
+where
+    I: Input,
+    O: MapObserver,
+    S: HasCorpus<I>,
+    TS: FavFactor<I>,
+{
+    fn minimize<CS, E, EM, OT, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut S,
+    ) -> Result<(), Error>
+    where
+        CS: Scheduler<I, S>,
+        E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+        OT: ObserversTuple<I, S>,
+        Z: ExecutesInput<I, OT, S, Z> + HasScheduler<CS, I, S>,
+    {
+        let count = state.corpus().count();
+        let mut sets = Vec::with_capacity(count);
+
+        for idx in 0..count {
+            let input = state.corpus().get(idx)?.borrow_mut().load_input()?.clone();
+
+            let start = current_time();
+            fuzzer.execute_input(state, executor, manager, &input)?;
+            let exec_time = current_time() - start;
+
+            let observer = executor
+                .observers()
+                .match_name::<O>(&self.obs_name)
+                .ok_or_else(|| {
+                    Error::KeyNotFound(format!("Observer {} not found", self.obs_name))
+                })?;
+            let initial = observer.initial();
+            let covered: BTreeSet<(usize, O::Entry)> = (0..observer.usable_count())
+                .map(|i| (i, *observer.get(i)))
+                .filter(|(_, val)| *val != initial)
+                .collect();
+
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            if testcase.exec_time().is_none() {
+                testcase.set_exec_time(exec_time);
+            }
+            let weight = TS::compute(&mut testcase)?;
+            drop(testcase);
+
+            sets.push((covered, weight));
+        }
+
+        let mut keep = vec![false; count];
+        for idx in greedy_set_cover(&sets) {
+            keep[idx] = true;
+        }
+
+        // Remove from the back, so that the indexes of the remaining entries stay valid
+        for idx in (0..count).rev() {
+            if !keep[idx] {
+                let removed = state.corpus_mut().remove(idx)?;
+                fuzzer.scheduler().on_remove(state, idx, &removed)?;
+            }
+        }
+        *state.corpus_mut().current_mut() = None;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeSet, vec::Vec};
+
+    use crate::corpus::minimizer::greedy_set_cover;
+
+    #[test]
+    fn test_greedy_set_cover() {
+        let sets: Vec<(BTreeSet<u32>, u64)> = vec![
+            ([1, 2].into_iter().collect(), 1),
+            ([3].into_iter().collect(), 1),
+            ([1, 2, 3].into_iter().collect(), 1),
+            ([2].into_iter().collect(), 1),
+        ];
+        assert_eq!(greedy_set_cover(&sets), vec![2]);
+
+        // A heavy entry loses against two light ones covering the same
+        let sets: Vec<(BTreeSet<u32>, u64)> = vec![
+            ([1, 2].into_iter().collect(), 1),
+            ([3].into_iter().collect(), 1),
+            ([1, 2, 3].into_iter().collect(), 10),
+        ];
+        assert_eq!(greedy_set_cover(&sets), vec![0, 1]);
+    }
+}
@@ -1,7 +1,9 @@
 //! Corpuses contain the testcases, either in memory, on disk, or somewhere else.
 
 pub mod testcase;
-pub use testcase::{PowerScheduleTestcaseMetaData, Testcase};
+pub use testcase::{
+    PowerScheduleTestcaseMetaData, StageOpMetadata, Testcase, TestcaseOriginMetadata,
+};
 
 pub mod inmemory;
 pub use inmemory::InMemoryCorpus;
@@ -9,13 +11,18 @@ pub use inmemory::InMemoryCorpus;
 #[cfg(feature = "std")]
 pub mod ondisk;
 #[cfg(feature = "std")]
-pub use ondisk::OnDiskCorpus;
+pub use ondisk::{OnDiskCorpus, OnDiskLayout};
 
 #[cfg(feature = "std")]
 pub mod cached;
 #[cfg(feature = "std")]
 pub use cached::CachedOnDiskCorpus;
 
+pub mod minimizer;
+pub use minimizer::{
+    CorpusMinimizer, MapCorpusMinimizer, StdCorpusMinimizer, UnweightedCorpusMinimizer,
+};
+
 use core::cell::RefCell;
 
 use crate::{inputs::Input, Error};
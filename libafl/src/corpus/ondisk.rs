@@ -1,10 +1,12 @@
 //! The ondisk corpus stores unused testcases to disk.
 
-use alloc::vec::Vec;
-use core::{cell::RefCell, time::Duration};
+use alloc::{string::String, vec::Vec};
+use core::{cell::RefCell, fmt::Write as _, time::Duration};
+use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 use std::{
     fs::OpenOptions,
+    io::ErrorKind,
     path::{Path, PathBuf},
 };
 
@@ -12,8 +14,14 @@ use std::{
 use std::{fs, fs::File, io::Write};
 
 use crate::{
-    bolts::serdeany::SerdeAnyMap, corpus::Corpus, corpus::Testcase, inputs::Input,
-    state::HasMetadata, Error,
+    bolts::{current_time, serdeany::SerdeAnyMap},
+    corpus::Corpus,
+    corpus::{Testcase, TestcaseOriginMetadata},
+    events::input_hash,
+    executors::{CrashSignalMetadata, ExitKind},
+    inputs::Input,
+    state::HasMetadata,
+    Error,
 };
 
 /// Options for the the format of the on-disk metadata
@@ -28,6 +36,24 @@ pub enum OnDiskMetadataFormat {
     JsonPretty,
 }
 
+/// The layout of the testcases an [`OnDiskCorpus`] writes to disk
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OnDiskLayout {
+    /// Files are named by [`Input::generate_name`], directly in the corpus directory
+    Plain,
+    /// `AFL++`-style queue entries, named `id:000123,src:000045,time:...,execs:...,op:havoc`, in `queue/`
+    AflQueue,
+    /// `AFL++`-style solutions, in `hangs/` for timeouts and in `crashes/` otherwise, named `id:000012,sig:11,src:...`
+    AflSolutions,
+}
+
+impl Default for OnDiskLayout {
+    fn default() -> Self {
+        Self::Plain
+    }
+}
+
 /// A corpus able to store testcases to disk, and load them from disk, when they are being used.
 #[cfg(feature = "std")]
 #[derive(Debug, Serialize)]
@@ -49,6 +75,20 @@ where
     current: Option<usize>,
     dir_path: PathBuf,
     meta_format: Option<OnDiskMetadataFormat>,
+    #[serde(default)]
+    layout: OnDiskLayout,
+    /// The next `AFL++`-style id, for the queue or for crashes
+    #[serde(default)]
+    next_id: usize,
+    /// The next `AFL++`-style id for hangs
+    #[serde(default)]
+    next_hang_id: usize,
+    /// The time this corpus was created, for `AFL++`-style names
+    #[serde(default)]
+    start_time: Duration,
+    /// The files of a resumed `AFL++`-style queue, by [`input_hash`], reused when their input is added again
+    #[serde(default)]
+    resumed: HashMap<u64, PathBuf>,
 }
 
 impl<I> Corpus<I> for OnDiskCorpus<I>
@@ -65,30 +105,13 @@ where
     #[inline]
     fn add(&mut self, mut testcase: Testcase<I>) -> Result<usize, Error> {
         if testcase.filename().is_none() {
-            // TODO walk entry metadata to ask for pieces of filename (e.g. :havoc in AFL)
-            let file_orig = testcase
-                .input()
-                .as_ref()
-                .unwrap()
-                .generate_name(self.entries.len());
-            let mut file = file_orig.clone();
-
-            let mut ctr = 2;
-            let filename = loop {
-                let lockfile = format!(".{}.lafl_lock", file);
-                // try to create lockfile.
-
-                if OpenOptions::new()
-                    .write(true)
-                    .create_new(true)
-                    .open(self.dir_path.join(lockfile))
-                    .is_ok()
-                {
-                    break self.dir_path.join(file);
-                }
-
-                file = format!("{}-{}", &file_orig, ctr);
-                ctr += 1;
+            let filename = match self.layout {
+                OnDiskLayout::Plain => self.plain_filename(&testcase),
+                OnDiskLayout::AflQueue => match self.resumed_filename(&testcase) {
+                    Some(filename) => filename,
+                    None => self.afl_filename(&testcase),
+                },
+                OnDiskLayout::AflSolutions => self.afl_filename(&testcase),
             };
 
             let filename_str = filename.to_str().expect("Invalid Path");
@@ -179,17 +202,171 @@ where
         P: AsRef<Path>,
     {
         fn new<I: Input>(dir_path: PathBuf) -> Result<OnDiskCorpus<I>, Error> {
-            fs::create_dir_all(&dir_path)?;
-            Ok(OnDiskCorpus {
-                entries: vec![],
-                current: None,
-                dir_path,
-                meta_format: None,
-            })
+            OnDiskCorpus::new_save_meta(dir_path, None)
         }
         new(dir_path.as_ref().to_path_buf())
     }
 
+    /// Creates an [`OnDiskCorpus`] writing an `AFL++`-style queue to `out_dir/queue`.
+    /// Ids continue after the highest id already in the queue, and entries already in the queue
+    /// keep their file when their input is added again, see [`OnDiskCorpus::afl_resume_dir`].
+    /// Use an [`crate::feedbacks::OriginFeedback`] for the `src:` and `op:` parts of the names.
+    pub fn afl_queue<P>(out_dir: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut corpus = Self::new(out_dir.as_ref().join("queue"))?;
+        corpus.next_id = next_afl_id(&corpus.dir_path)?;
+        corpus.resumed = afl_entries::<I>(&corpus.dir_path)?;
+        corpus.layout = OnDiskLayout::AflQueue;
+        Ok(corpus)
+    }
+
+    /// Creates an [`OnDiskCorpus`] for solutions, writing `AFL++`-style `out_dir/crashes` and `out_dir/hangs`.
+    /// The [`ExitKind`] of each solution is taken from its metadata, added by the fuzzer,
+    /// and the `sig:` part of crashes from its [`CrashSignalMetadata`], added by the in-process executors.
+    /// Ids continue after the highest ids already in these directories.
+    pub fn afl_solutions<P>(out_dir: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let out_dir = out_dir.as_ref();
+        fs::create_dir_all(out_dir.join("crashes"))?;
+        fs::create_dir_all(out_dir.join("hangs"))?;
+        let mut corpus = Self::new(out_dir)?;
+        corpus.next_id = next_afl_id(&out_dir.join("crashes"))?;
+        corpus.next_hang_id = next_afl_id(&out_dir.join("hangs"))?;
+        corpus.layout = OnDiskLayout::AflSolutions;
+        Ok(corpus)
+    }
+
+    /// The `out_dir/queue` of an `AFL++`-style output directory to resume from, if there is one.
+    /// Its entries can be loaded again, for example with [`crate::state::StdState::load_initial_inputs`],
+    /// after creating the [`OnDiskCorpus::afl_queue`]: the ones added to it keep their file and id.
+    #[must_use]
+    pub fn afl_resume_dir<P>(out_dir: P) -> Option<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        let queue = out_dir.as_ref().join("queue");
+        if queue.is_dir() {
+            Some(queue)
+        } else {
+            None
+        }
+    }
+
+    /// The layout of the files written by this corpus
+    #[must_use]
+    pub fn layout(&self) -> OnDiskLayout {
+        self.layout
+    }
+
+    /// A unique filename generated by the [`Input`], guarded by a lockfile
+    fn plain_filename(&self, testcase: &Testcase<I>) -> PathBuf {
+        let file_orig = testcase
+            .input()
+            .as_ref()
+            .unwrap()
+            .generate_name(self.entries.len());
+        let mut file = file_orig.clone();
+
+        let mut ctr = 2;
+        loop {
+            let lockfile = format!(".{}.lafl_lock", file);
+            // try to create lockfile.
+
+            if OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.dir_path.join(lockfile))
+                .is_ok()
+            {
+                break self.dir_path.join(file);
+            }
+
+            file = format!("{}-{}", &file_orig, ctr);
+            ctr += 1;
+        }
+    }
+
+    /// The file of an entry of the resumed queue with the same input, if any
+    fn resumed_filename(&mut self, testcase: &Testcase<I>) -> Option<PathBuf> {
+        if self.resumed.is_empty() {
+            return None;
+        }
+        let hash = input_hash(testcase.input().as_ref()?).ok()?;
+        self.resumed.remove(&hash)
+    }
+
+    /// An `AFL++`-style filename, such as `id:000123,src:000045,time:1337,execs:4242,op:havoc`.
+    /// The id is guarded by a lockfile, so that clients sharing the directory don't take the same one.
+    fn afl_filename(&mut self, testcase: &Testcase<I>) -> PathBuf {
+        let is_hang = testcase
+            .metadata()
+            .get::<ExitKind>()
+            .map_or(false, |exit_kind| *exit_kind == ExitKind::Timeout);
+        let (parent, op) = match testcase.metadata().get::<TestcaseOriginMetadata>() {
+            Some(origin) => (origin.parent_filename.clone(), origin.op.clone()),
+            // Without an `OriginFeedback`, new queue entries come from the entry currently fuzzed
+            None if self.layout == OnDiskLayout::AflQueue => (
+                self.current.and_then(|idx| {
+                    self.entries[idx]
+                        .try_borrow()
+                        .ok()
+                        .and_then(|parent| parent.filename().clone())
+                }),
+                None,
+            ),
+            None => (None, None),
+        };
+        let (dir, next_id) = match self.layout {
+            OnDiskLayout::AflSolutions if is_hang => {
+                (self.dir_path.join("hangs"), &mut self.next_hang_id)
+            }
+            OnDiskLayout::AflSolutions => (self.dir_path.join("crashes"), &mut self.next_id),
+            _ => (self.dir_path.clone(), &mut self.next_id),
+        };
+
+        let id = lock_afl_id(&dir, next_id);
+
+        let mut name = format!("id:{:06}", id);
+        if self.layout == OnDiskLayout::AflSolutions && !is_hang {
+            if let Some(sig) = testcase.metadata().get::<CrashSignalMetadata>() {
+                write!(name, ",sig:{:02}", sig.signal).unwrap();
+            }
+        }
+        // The source is the `AFL++` id of the parent, not its index in the corpus
+        let src = parent.as_ref().and_then(|parent| {
+            Path::new(parent)
+                .file_name()?
+                .to_str()
+                .and_then(parse_afl_id)
+        });
+        if let Some(src) = src {
+            write!(name, ",src:{:06}", src).unwrap();
+        }
+        write!(
+            name,
+            ",time:{},execs:{}",
+            current_time().saturating_sub(self.start_time).as_millis(),
+            testcase.executions()
+        )
+        .unwrap();
+        if parent.is_some() {
+            // Without a stage operation, the default mutational stage found it
+            write!(name, ",op:{}", op.as_deref().unwrap_or("havoc")).unwrap();
+        } else if self.layout == OnDiskLayout::AflQueue {
+            let orig = testcase
+                .input()
+                .as_ref()
+                .unwrap()
+                .generate_name(self.entries.len());
+            write!(name, ",orig:{}", orig).unwrap();
+        }
+        dir.join(name)
+    }
+
     /// Creates the [`OnDiskCorpus`] specifying the type of `Metadata` to be saved to disk.
     /// Will error, if [`std::fs::create_dir_all()`] failed for `dir_path`.
     pub fn new_save_meta(
@@ -202,9 +379,78 @@ where
             current: None,
             dir_path,
             meta_format,
+            layout: OnDiskLayout::Plain,
+            next_id: 0,
+            next_hang_id: 0,
+            start_time: current_time(),
+            resumed: HashMap::new(),
         })
     }
 }
+
+/// Parses the id of an `AFL++`-style filename, such as `id:000123,src:000045`
+#[must_use]
+pub fn parse_afl_id(filename: &str) -> Option<usize> {
+    let id: String = filename
+        .strip_prefix("id:")?
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    id.parse().ok()
+}
+
+/// Takes the first free `AFL++`-style id in `dir` from `next_id` on, guarded by an `.id:NNNNNN.lafl_lock` lockfile,
+/// so that clients sharing the directory, such as a [`OnDiskCorpus::afl_queue`] and an
+/// [`crate::stages::AflSyncStage`], don't take the same one.
+pub(crate) fn lock_afl_id(dir: &Path, next_id: &mut usize) -> usize {
+    loop {
+        let id = *next_id;
+        *next_id += 1;
+        let lockfile = dir.join(format!(".id:{:06}.lafl_lock", id));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(lockfile)
+        {
+            // Another client took this id
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+            _ => break id,
+        }
+    }
+}
+
+/// The id following the highest `AFL++`-style id of the files in `dir`
+fn next_afl_id(dir: &Path) -> Result<usize, Error> {
+    let mut next = 0;
+    for entry in fs::read_dir(dir)? {
+        if let Some(id) = entry?.file_name().to_str().and_then(parse_afl_id) {
+            next = next.max(id + 1);
+        }
+    }
+    Ok(next)
+}
+
+/// The `AFL++`-style entries in `dir`, by [`input_hash`] of their input
+fn afl_entries<I>(dir: &Path) -> Result<HashMap<u64, PathBuf>, Error>
+where
+    I: Input,
+{
+    let mut entries = HashMap::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_entry = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_afl_id)
+            .is_some();
+        if is_entry {
+            if let Ok(input) = I::from_file(&path) {
+                entries.insert(input_hash(&input)?, path);
+            }
+        }
+    }
+    Ok(entries)
+}
 #[cfg(feature = "python")]
 /// `OnDiskCorpus` Python bindings
 pub mod pybind {
@@ -238,3 +484,125 @@ pub mod pybind {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use std::path::Path;
+
+    use crate::{
+        corpus::{ondisk::parse_afl_id, Corpus, OnDiskCorpus, Testcase, TestcaseOriginMetadata},
+        executors::{CrashSignalMetadata, ExitKind},
+        inputs::BytesInput,
+        state::HasMetadata,
+    };
+
+    fn filename(corpus: &OnDiskCorpus<BytesInput>, idx: usize) -> String {
+        let filename = corpus
+            .get(idx)
+            .unwrap()
+            .borrow()
+            .filename()
+            .clone()
+            .unwrap();
+        let name = Path::new(&filename).file_name().unwrap().to_str().unwrap();
+        name.to_string()
+    }
+
+    /// The `AFL++` entries in `dir`, without lockfiles and metadata
+    fn count_entries(dir: &Path) -> usize {
+        fs::read_dir(dir)
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                parse_afl_id(name.to_str().unwrap()).is_some()
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_afl_layout() {
+        assert_eq!(parse_afl_id("id:000123,src:000045,op:havoc"), Some(123));
+        assert_eq!(parse_afl_id("README.txt"), None);
+
+        let out_dir = env::temp_dir().join(format!("libafl_afl_layout_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out_dir);
+
+        let mut queue = OnDiskCorpus::<BytesInput>::afl_queue(&out_dir).unwrap();
+        queue
+            .add(Testcase::new(BytesInput::new(vec![0; 4])))
+            .unwrap();
+        assert!(filename(&queue, 0).contains(",orig:"));
+        *queue.current_mut() = Some(0);
+        let idx = queue
+            .add(Testcase::new(BytesInput::new(vec![1; 4])))
+            .unwrap();
+        let name = filename(&queue, idx);
+        assert!(name.starts_with("id:000001,src:000000,time:"));
+        assert!(name.ends_with(",op:havoc"));
+
+        // A second client sharing the queue does not take the ids of the first one
+        let mut other = OnDiskCorpus::<BytesInput>::afl_queue(&out_dir).unwrap();
+        queue
+            .add(Testcase::new(BytesInput::new(vec![2; 4])))
+            .unwrap();
+        other
+            .add(Testcase::new(BytesInput::new(vec![3; 4])))
+            .unwrap();
+        assert!(filename(&other, 0).starts_with("id:000003,"));
+
+        // The source is the id of the parent, not its corpus index
+        *other.current_mut() = Some(0);
+        let idx = other
+            .add(Testcase::new(BytesInput::new(vec![4; 4])))
+            .unwrap();
+        assert!(filename(&other, idx).starts_with("id:000004,src:000003,"));
+
+        // Entries of the resumed queue keep their file
+        let idx = other
+            .add(Testcase::new(BytesInput::new(vec![1; 4])))
+            .unwrap();
+        assert_eq!(filename(&other, idx), filename(&queue, 1));
+        assert_eq!(count_entries(&out_dir.join("queue")), 5);
+        assert_eq!(
+            OnDiskCorpus::<BytesInput>::afl_resume_dir(&out_dir),
+            Some(out_dir.join("queue"))
+        );
+
+        let mut solutions = OnDiskCorpus::<BytesInput>::afl_solutions(&out_dir).unwrap();
+        let mut hang = Testcase::new(BytesInput::new(vec![5; 4]));
+        hang.add_metadata(ExitKind::Timeout);
+        solutions.add(hang).unwrap();
+        let mut crash = Testcase::new(BytesInput::new(vec![6; 4]));
+        crash.add_metadata(ExitKind::Crash);
+        crash.add_metadata(CrashSignalMetadata { signal: 11 });
+        crash.add_metadata(TestcaseOriginMetadata {
+            parent_filename: queue.get(1).unwrap().borrow().filename().clone(),
+            op: Some("flip1".into()),
+        });
+        let idx = solutions.add(crash).unwrap();
+        let name = filename(&solutions, idx);
+        assert!(name.starts_with("id:000000,sig:11,src:000001,time:"));
+        assert!(name.ends_with(",op:flip1"));
+        assert_eq!(count_entries(&out_dir.join("hangs")), 1);
+        assert_eq!(count_entries(&out_dir.join("crashes")), 1);
+
+        // Resuming continues the ids
+        let queue = OnDiskCorpus::<BytesInput>::afl_queue(&out_dir).unwrap();
+        assert_eq!(queue.next_id, 5);
+
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_deserialize_plain_corpus() {
+        // A corpus serialized before the `AFL++` layouts existed
+        let corpus: OnDiskCorpus<BytesInput> = serde_json::from_str(
+            r#"{"entries":[],"current":null,"dir_path":"corpus","meta_format":null}"#,
+        )
+        .unwrap();
+        assert_eq!(corpus.count(), 0);
+        assert_eq!(corpus.next_id, 0);
+    }
+}
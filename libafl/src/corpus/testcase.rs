@@ -629,3 +629,24 @@ impl PowerScheduleTestcaseMetaData {
 }
 
 crate::impl_serdeany!(PowerScheduleTestcaseMetaData);
+
+/// The operation a stage is currently performing, such as `havoc` or `flip1`, set as state metadata while it runs.
+/// An [`crate::feedbacks::OriginFeedback`] adds it to the testcases found meanwhile.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StageOpMetadata {
+    /// The name of the operation
+    pub op: String,
+}
+
+crate::impl_serdeany!(StageOpMetadata);
+
+/// Where a testcase comes from, added by an [`crate::feedbacks::OriginFeedback`]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TestcaseOriginMetadata {
+    /// The filename of the corpus entry that was fuzzed when the testcase was found, if any
+    pub parent_filename: Option<String>,
+    /// The [`StageOpMetadata`] of the stage that found the testcase, if any
+    pub op: Option<String>,
+}
+
+crate::impl_serdeany!(TestcaseOriginMetadata);
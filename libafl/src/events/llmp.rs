@@ -14,23 +14,28 @@ use crate::bolts::{llmp::LlmpConnection, shmem::StdShMemProvider, staterestore::
 use crate::{
     bolts::{
         llmp::{self, Flags, LlmpClient, LlmpClientDescription, Tag},
+        serdeany::SerdeAny,
         shmem::ShMemProvider,
     },
+    corpus::Corpus,
     events::{
-        BrokerEventResult, Event, EventConfig, EventFirer, EventManager, EventManagerId,
+        input_hash, BrokerEventResult, CustomBrokerEventHandler, CustomClientEventHandler,
+        CustomEventHandlers, Event, EventConfig, EventFirer, EventManager, EventManagerId,
         EventProcessor, EventRestarter, HasEventManagerId, ProgressReporter,
+        ReceivedTestcasesMetadata,
     },
     executors::{Executor, HasObservers},
     fuzzer::{EvaluatorObservers, ExecutionProcessor},
     inputs::Input,
     monitors::Monitor,
     observers::ObserversTuple,
+    state::{HasCorpus, HasMetadata},
     Error,
 };
-use alloc::string::ToString;
+use alloc::{boxed::Box, string::ToString, vec::Vec};
 #[cfg(feature = "std")]
 use core::sync::atomic::{compiler_fence, Ordering};
-use core::{marker::PhantomData, time::Duration};
+use core::{any::TypeId, marker::PhantomData, time::Duration};
 #[cfg(feature = "std")]
 use core_affinity::CoreId;
 use serde::de::DeserializeOwned;
@@ -68,6 +73,7 @@ where
     llmp: llmp::LlmpBroker<SP>,
     #[cfg(feature = "llmp_compression")]
     compressor: GzipCompressor,
+    custom_handlers: CustomEventHandlers<CustomBrokerEventHandler>,
     phantom: PhantomData<I>,
 }
 
@@ -84,6 +90,7 @@ where
             llmp,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
+            custom_handlers: CustomEventHandlers::new(),
             phantom: PhantomData,
         })
     }
@@ -97,6 +104,7 @@ where
             llmp: llmp::LlmpBroker::create_attach_to_tcp(shmem_provider, port)?,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
+            custom_handlers: CustomEventHandlers::new(),
             phantom: PhantomData,
         })
     }
@@ -110,9 +118,24 @@ where
         self.llmp.connect_b2b(addr)
     }
 
+    /// Register a handler for [`Event::Custom`] events with a payload of type `T`.
+    /// The handler decides if the event should be forwarded to the clients.
+    /// Custom events without a registered handler are always forwarded.
+    pub fn add_custom_event_handler<T, F>(&mut self, mut handler: F)
+    where
+        T: SerdeAny,
+        F: FnMut(u32, &T) -> Result<BrokerEventResult, Error> + 'static,
+    {
+        self.custom_handlers
+            .insert::<T>(Box::new(move |client_id, payload| {
+                handler(client_id, payload.get::<T>().unwrap())
+            }));
+    }
+
     /// Run forever in the broker
     pub fn broker_loop(&mut self) -> Result<(), Error> {
         let monitor = &mut self.monitor;
+        let custom_handlers = &mut self.custom_handlers;
         #[cfg(feature = "llmp_compression")]
         let compressor = &self.compressor;
         self.llmp.loop_forever(
@@ -130,7 +153,7 @@ where
                         msg
                     };
                     let event: Event<I> = postcard::from_bytes(event_bytes)?;
-                    match Self::handle_in_broker(monitor, client_id, &event)? {
+                    match Self::handle_in_broker(monitor, custom_handlers, client_id, &event)? {
                         BrokerEventResult::Forward => Ok(llmp::LlmpMsgHookResult::ForwardToClients),
                         BrokerEventResult::Handled => Ok(llmp::LlmpMsgHookResult::Handled),
                     }
@@ -148,6 +171,7 @@ where
     #[allow(clippy::unnecessary_wraps)]
     fn handle_in_broker(
         monitor: &mut MT,
+        custom_handlers: &mut CustomEventHandlers<CustomBrokerEventHandler>,
         client_id: u32,
         event: &Event<I>,
     ) -> Result<BrokerEventResult, Error> {
@@ -158,6 +182,7 @@ where
                 exit_kind: _,
                 corpus_size,
                 observers_buf: _,
+                metadata: _,
                 time,
                 executions,
             } => {
@@ -228,7 +253,21 @@ where
                 #[cfg(feature = "std")]
                 println!("[LOG {}]: {}", severity_level, message);
                 Ok(BrokerEventResult::Handled)
-            } //_ => Ok(BrokerEventResult::Forward),
+            }
+            Event::TestcaseMetadata {
+                input_hash: _,
+                metadata: _,
+                client_config: _,
+                phantom: _,
+            } => Ok(BrokerEventResult::Forward),
+            Event::Custom {
+                name: _,
+                payload,
+                phantom: _,
+            } => match custom_handlers.get_mut(payload) {
+                Some(handler) => handler(client_id, payload),
+                None => Ok(BrokerEventResult::Forward),
+            },
         }
     }
 }
@@ -247,6 +286,8 @@ where
     #[cfg(feature = "llmp_compression")]
     compressor: GzipCompressor,
     configuration: EventConfig,
+    shared_metadata: Vec<TypeId>,
+    custom_handlers: CustomEventHandlers<CustomClientEventHandler<S>>,
     phantom: PhantomData<(I, OT, S)>,
 }
 
@@ -275,6 +316,8 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            shared_metadata: vec![],
+            custom_handlers: CustomEventHandlers::new(),
             phantom: PhantomData,
         })
     }
@@ -293,6 +336,8 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            shared_metadata: vec![],
+            custom_handlers: CustomEventHandlers::new(),
             phantom: PhantomData,
         })
     }
@@ -309,6 +354,8 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            shared_metadata: vec![],
+            custom_handlers: CustomEventHandlers::new(),
             phantom: PhantomData,
         })
     }
@@ -329,6 +376,8 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            shared_metadata: vec![],
+            custom_handlers: CustomEventHandlers::new(),
             phantom: PhantomData,
         })
     }
@@ -339,6 +388,34 @@ where
         self.llmp.to_env(env_name).unwrap();
     }
 
+    /// Send the testcase metadata of type `T` along with each [`Event::NewTestcase`],
+    /// so that receivers with the same configuration don't have to recompute it.
+    /// Metadata the stages add later, such as the calibration results, follows with an
+    /// [`Event::TestcaseMetadata`] once they ran on the testcase.
+    pub fn share_metadata<T>(&mut self)
+    where
+        T: SerdeAny,
+    {
+        let type_id = TypeId::of::<T>();
+        if !self.shared_metadata.contains(&type_id) {
+            self.shared_metadata.push(type_id);
+        }
+    }
+
+    /// Register a handler for [`Event::Custom`] events with a payload of type `T`,
+    /// sent by other clients and forwarded by the broker.
+    /// Custom events without a registered handler are ignored.
+    pub fn add_custom_event_handler<T, F>(&mut self, mut handler: F)
+    where
+        T: SerdeAny,
+        F: FnMut(&mut S, u32, &T) -> Result<(), Error> + 'static,
+    {
+        self.custom_handlers
+            .insert::<T>(Box::new(move |state, client_id, payload| {
+                handler(state, client_id, payload.get::<T>().unwrap())
+            }));
+    }
+
     // Handle arriving events in the client
     #[allow(clippy::unused_self)]
     fn handle_in_client<E, Z>(
@@ -346,12 +423,13 @@ where
         fuzzer: &mut Z,
         executor: &mut E,
         state: &mut S,
-        _client_id: u32,
+        client_id: u32,
         event: Event<I>,
     ) -> Result<(), Error>
     where
         OT: ObserversTuple<I, S> + DeserializeOwned,
         E: Executor<Self, I, S, Z> + HasObservers<I, OT, S>,
+        S: HasCorpus<I> + HasMetadata,
         Z: ExecutionProcessor<I, OT, S> + EvaluatorObservers<I, OT, S>,
     {
         match event {
@@ -361,29 +439,88 @@ where
                 exit_kind,
                 corpus_size: _,
                 observers_buf,
+                metadata,
                 time: _,
                 executions: _,
             } => {
                 #[cfg(feature = "std")]
                 println!(
                     "Received new Testcase from {} ({:?})",
-                    _client_id, client_config
+                    client_id, client_config
                 );
 
-                let _res = if client_config.match_with(&self.configuration)
-                    && observers_buf.is_some()
-                {
+                let config_matches = client_config.match_with(&self.configuration);
+                // The sender may send more metadata for this testcase once its stages ran
+                let hash = if config_matches && metadata.is_some() {
+                    Some(input_hash(&input)?)
+                } else {
+                    None
+                };
+                let res = if config_matches && observers_buf.is_some() {
                     let observers: OT = postcard::from_bytes(observers_buf.as_ref().unwrap())?;
                     fuzzer.process_execution(state, self, input, &observers, &exit_kind, false)?
                 } else {
                     fuzzer.evaluate_input_with_observers(state, executor, self, input, false)?
                 };
-                #[cfg(feature = "std")]
-                if let Some(item) = _res.1 {
+                if let Some(item) = res.1 {
+                    // The metadata may depend on the build, so we only trust matching configs
+                    if config_matches {
+                        if let Some(metadata) = metadata {
+                            state
+                                .corpus()
+                                .get(item)?
+                                .borrow_mut()
+                                .metadata_mut()
+                                .merge(metadata);
+                        }
+                        if let Some(hash) = hash {
+                            if !state.has_metadata::<ReceivedTestcasesMetadata>() {
+                                state.add_metadata(ReceivedTestcasesMetadata::default());
+                            }
+                            state
+                                .metadata_mut()
+                                .get_mut::<ReceivedTestcasesMetadata>()
+                                .unwrap()
+                                .insert(hash, item);
+                        }
+                    }
+                    #[cfg(feature = "std")]
                     println!("Added received Testcase as item #{}", item);
                 }
                 Ok(())
             }
+            Event::TestcaseMetadata {
+                input_hash,
+                metadata,
+                client_config,
+                phantom: _,
+            } => {
+                if client_config.match_with(&self.configuration) {
+                    let item = state
+                        .metadata_mut()
+                        .get_mut::<ReceivedTestcasesMetadata>()
+                        .and_then(|received| received.take(input_hash));
+                    if let Some(item) = item {
+                        state
+                            .corpus()
+                            .get(item)?
+                            .borrow_mut()
+                            .metadata_mut()
+                            .merge(metadata);
+                    }
+                }
+                Ok(())
+            }
+            Event::Custom {
+                name: _,
+                payload,
+                phantom: _,
+            } => {
+                if let Some(handler) = self.custom_handlers.get_mut(&payload) {
+                    handler(state, client_id, &payload)?;
+                }
+                Ok(())
+            }
             _ => Err(Error::Unknown(format!(
                 "Received illegal message that message should not have arrived: {:?}.",
                 event.name()
@@ -429,6 +566,10 @@ where
     fn configuration(&self) -> EventConfig {
         self.configuration
     }
+
+    fn shared_metadata(&self) -> &[TypeId] {
+        &self.shared_metadata
+    }
 }
 
 impl<I, OT, S, SP> EventRestarter<S> for LlmpEventManager<I, OT, S, SP>
@@ -452,6 +593,7 @@ where
     E: Executor<Self, I, S, Z> + HasObservers<I, OT, S>,
     I: Input,
     OT: ObserversTuple<I, S> + DeserializeOwned,
+    S: HasCorpus<I> + HasMetadata,
     Z: ExecutionProcessor<I, OT, S> + EvaluatorObservers<I, OT, S>, //CE: CustomEvent<I>,
 {
     fn process(&mut self, fuzzer: &mut Z, state: &mut S, executor: &mut E) -> Result<usize, Error> {
@@ -491,6 +633,7 @@ where
     E: Executor<Self, I, S, Z> + HasObservers<I, OT, S>,
     I: Input,
     OT: ObserversTuple<I, S> + DeserializeOwned,
+    S: HasCorpus<I> + HasMetadata,
     SP: ShMemProvider,
     Z: ExecutionProcessor<I, OT, S> + EvaluatorObservers<I, OT, S>, //CE: CustomEvent<I>,
 {
@@ -560,6 +703,10 @@ where
     fn configuration(&self) -> EventConfig {
         self.llmp_mgr.configuration()
     }
+
+    fn shared_metadata(&self) -> &[TypeId] {
+        self.llmp_mgr.shared_metadata()
+    }
 }
 
 #[cfg(feature = "std")]
@@ -592,6 +739,7 @@ impl<E, I, OT, S, SP, Z> EventProcessor<E, I, S, Z> for LlmpRestartingEventManag
 where
     E: Executor<LlmpEventManager<I, OT, S, SP>, I, S, Z> + HasObservers<I, OT, S>,
     I: Input,
+    S: HasCorpus<I> + HasMetadata,
     Z: ExecutionProcessor<I, OT, S> + EvaluatorObservers<I, OT, S>,
     OT: ObserversTuple<I, S> + DeserializeOwned,
     SP: ShMemProvider + 'static,
@@ -607,7 +755,7 @@ impl<E, I, OT, S, SP, Z> EventManager<E, I, S, Z> for LlmpRestartingEventManager
 where
     E: Executor<LlmpEventManager<I, OT, S, SP>, I, S, Z> + HasObservers<I, OT, S>,
     I: Input,
-    S: Serialize,
+    S: Serialize + HasCorpus<I> + HasMetadata,
     Z: ExecutionProcessor<I, OT, S> + EvaluatorObservers<I, OT, S>,
     OT: ObserversTuple<I, S> + DeserializeOwned,
     SP: ShMemProvider + 'static,
@@ -650,6 +798,25 @@ where
         }
     }
 
+    /// Send the testcase metadata of type `T` along with each [`Event::NewTestcase`].
+    /// See [`LlmpEventManager::share_metadata`].
+    pub fn share_metadata<T>(&mut self)
+    where
+        T: SerdeAny,
+    {
+        self.llmp_mgr.share_metadata::<T>();
+    }
+
+    /// Register a handler for [`Event::Custom`] events with a payload of type `T`.
+    /// See [`LlmpEventManager::add_custom_event_handler`].
+    pub fn add_custom_event_handler<T, F>(&mut self, handler: F)
+    where
+        T: SerdeAny,
+        F: FnMut(&mut S, u32, &T) -> Result<(), Error> + 'static,
+    {
+        self.llmp_mgr.add_custom_event_handler::<T, F>(handler);
+    }
+
     /// Get the staterestorer
     pub fn staterestorer(&self) -> &StateRestorer<SP> {
         &self.staterestorer
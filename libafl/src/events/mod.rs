@@ -7,17 +7,30 @@ pub use llmp::*;
 
 use ahash::AHasher;
 use alloc::{
+    boxed::Box,
+    collections::VecDeque,
     string::{String, ToString},
     vec::Vec,
 };
-use core::{fmt, hash::Hasher, marker::PhantomData, time::Duration};
+use core::{
+    any::{type_name, TypeId},
+    fmt,
+    hash::Hasher,
+    marker::PhantomData,
+    time::Duration,
+};
+use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "std")]
 use uuid::Uuid;
 
 use crate::{
-    bolts::current_time,
+    bolts::{
+        anymap::unpack_type_id,
+        current_time,
+        serdeany::{SerdeAny, SerdeAnyMap},
+    },
     executors::ExitKind,
     inputs::Input,
     monitors::UserStats,
@@ -36,8 +49,6 @@ pub struct EventManagerId {
 
 #[cfg(feature = "introspection")]
 use crate::monitors::ClientPerfMonitor;
-#[cfg(feature = "introspection")]
-use alloc::boxed::Box;
 
 /// The log event severity
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -145,20 +156,207 @@ impl From<String> for EventConfig {
     }
 }
 
-/*
-/// A custom event, for own messages, with own handler.
-pub trait CustomEvent<I>: SerdeAny
+/// The payload of an [`Event::Custom`].
+/// Any type implementing [`SerdeAny`] can be sent, as long as it is registered
+/// in the [`crate::bolts::serdeany::RegistryBuilder`], for example using [`crate::impl_serdeany`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomEventPayload {
+    payload: Box<dyn SerdeAny>,
+}
+
+// Cloning by serializing and deserializing, just like the `SerdeAnyMap`.
+// We unwrap postcard, it should not have a reason to fail.
+impl Clone for CustomEventPayload {
+    fn clone(&self) -> Self {
+        let serialized = postcard::to_allocvec(&self).unwrap();
+        postcard::from_bytes(&serialized).unwrap()
+    }
+}
+
+impl CustomEventPayload {
+    /// Create a new [`CustomEventPayload`], wrapping the given [`SerdeAny`]
+    #[must_use]
+    pub fn new<T>(payload: T) -> Self
+    where
+        T: SerdeAny,
+    {
+        Self {
+            payload: Box::new(payload),
+        }
+    }
+
+    /// Get the payload, if it is of type `T`
+    #[must_use]
+    pub fn get<T>(&self) -> Option<&T>
+    where
+        T: SerdeAny,
+    {
+        self.payload.as_any().downcast_ref::<T>()
+    }
+
+    /// Returns `true` if the payload is of type `T`
+    #[must_use]
+    pub fn is<T>(&self) -> bool
+    where
+        T: SerdeAny,
+    {
+        self.payload.as_any().is::<T>()
+    }
+
+    /// The type id of the wrapped payload, used to look up its handlers
+    fn packed_type_id(&self) -> u64 {
+        unpack_type_id(self.payload.as_any().type_id())
+    }
+}
+
+/// A handler for [`Event::Custom`] events, called in the broker.
+/// Gets the id of the sending client and the payload.
+/// Returns if the event should be forwarded to the clients.
+pub type CustomBrokerEventHandler =
+    dyn FnMut(u32, &CustomEventPayload) -> Result<BrokerEventResult, Error>;
+
+/// A handler for [`Event::Custom`] events, called in each receiving client.
+/// Gets the current state, the id of the sending client and the payload.
+pub type CustomClientEventHandler<S> =
+    dyn FnMut(&mut S, u32, &CustomEventPayload) -> Result<(), Error>;
+
+/// A set of handlers for [`Event::Custom`] events, with one handler per payload type.
+pub struct CustomEventHandlers<H>
+where
+    H: ?Sized,
+{
+    handlers: HashMap<u64, Box<H>>,
+}
+
+impl<H> fmt::Debug for CustomEventHandlers<H>
+where
+    H: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CustomEventHandlers with {} handlers",
+            self.handlers.len()
+        )
+    }
+}
+
+impl<H> Default for CustomEventHandlers<H>
+where
+    H: ?Sized,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H> CustomEventHandlers<H>
+where
+    H: ?Sized,
+{
+    /// Create a new, empty set of handlers
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::default(),
+        }
+    }
+
+    /// Set the handler for payloads of type `T`, replacing any previous handler for this type
+    pub fn insert<T>(&mut self, handler: Box<H>)
+    where
+        T: SerdeAny,
+    {
+        self.handlers
+            .insert(unpack_type_id(TypeId::of::<T>()), handler);
+    }
+
+    /// Get the handler for the given payload, if one was registered for its type
+    pub fn get_mut(&mut self, payload: &CustomEventPayload) -> Option<&mut H> {
+        self.handlers
+            .get_mut(&payload.packed_type_id())
+            .map(AsMut::as_mut)
+    }
+
+    /// The amount of registered handlers
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.handlers.len()
+    }
+
+    /// Returns `true` if no handlers are registered
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
+
+/// Marks a testcase sent with [`Event::NewTestcase`] before the stages ran on it.
+/// Once they did, its shared metadata is sent again with [`Event::TestcaseMetadata`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct PendingSharedMetadata {}
+
+crate::impl_serdeany!(PendingSharedMetadata);
+
+/// The maximum number of received testcases [`ReceivedTestcasesMetadata`] waits for an [`Event::TestcaseMetadata`] for
+pub const RECEIVED_TESTCASES_MAX: usize = 1024;
+
+/// The corpus indexes of the testcases received with shared metadata, by [`input_hash`],
+/// to add the metadata of a later [`Event::TestcaseMetadata`] to.
+/// An index is dropped once its metadata arrived, or once [`RECEIVED_TESTCASES_MAX`] newer testcases were received.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ReceivedTestcasesMetadata {
+    /// The corpus index of each received testcase
+    indexes: HashMap<u64, usize>,
+    /// The received testcases, oldest first
+    order: VecDeque<(u64, usize)>,
+}
+
+crate::impl_serdeany!(ReceivedTestcasesMetadata);
+
+impl ReceivedTestcasesMetadata {
+    /// Wait for the metadata of the testcase with the given hash, at corpus index `idx`
+    pub fn insert(&mut self, hash: u64, idx: usize) {
+        self.indexes.insert(hash, idx);
+        self.order.push_back((hash, idx));
+        while self.order.len() > RECEIVED_TESTCASES_MAX {
+            let (old_hash, old_idx) = self.order.pop_front().unwrap();
+            // The same input may have been received again since
+            if self.indexes.get(&old_hash) == Some(&old_idx) {
+                self.indexes.remove(&old_hash);
+            }
+        }
+    }
+
+    /// Take the corpus index of the testcase with the given hash, if still waiting for its metadata
+    pub fn take(&mut self, hash: u64) -> Option<usize> {
+        let idx = self.indexes.remove(&hash)?;
+        self.order.retain(|&(h, _)| h != hash);
+        Some(idx)
+    }
+
+    /// The number of testcases waiting for their metadata
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    /// If no testcase is waiting for its metadata
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+}
+
+/// Hash an input, to identify a testcase across clients
+pub fn input_hash<I>(input: &I) -> Result<u64, Error>
 where
     I: Input,
 {
-    /// Returns the name of this event
-    fn name(&self) -> &str;
-    /// This method will be called in the broker
-    fn handle_in_broker(&self) -> Result<BrokerEventResult, Error>;
-    /// This method will be called in the clients after handle_in_broker (unless BrokerEventResult::Handled) was returned in handle_in_broker
-    fn handle_in_client(&self) -> Result<(), Error>;
+    let mut hasher = AHasher::new_with_keys(0, 0);
+    hasher.write(&postcard::to_allocvec(input)?);
+    Ok(hasher.finish())
 }
-*/
 
 /// Events sent around in the library
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -167,14 +365,14 @@ pub enum Event<I>
 where
     I: Input,
 {
-    // TODO use an ID to keep track of the original index in the sender Corpus
-    // The sender can then use it to send Testcase metadata with CustomEvent
     /// A fuzzer found a new testcase. Rejoice!
     NewTestcase {
         /// The input for the new testcase
         input: I,
         /// The state of the observers when this testcase was found
         observers_buf: Option<Vec<u8>>,
+        /// The testcase metadata selected by [`EventFirer::shared_metadata`], if any
+        metadata: Option<SerdeAnyMap>,
         /// The exit kind
         exit_kind: ExitKind,
         /// The new corpus size of this client
@@ -231,11 +429,28 @@ where
         /// `PhantomData`
         phantom: PhantomData<I>,
     },
-    /*/// A custom type
+    /// The shared metadata of a testcase sent earlier with [`Event::NewTestcase`],
+    /// once the stages ran on it and added their metadata, such as the calibration results.
+    TestcaseMetadata {
+        /// The [`input_hash`] of the testcase
+        input_hash: u64,
+        /// The testcase metadata selected by [`EventFirer::shared_metadata`]
+        metadata: SerdeAnyMap,
+        /// The client config of the testcase
+        client_config: EventConfig,
+        /// `PhantomData`
+        phantom: PhantomData<I>,
+    },
+    /// A custom, user-defined event.
+    /// The broker and the clients dispatch it to the handlers registered for the payload type.
     Custom {
-        // TODO: Allow custom events
-        // custom_event: Box<dyn CustomEvent<I, OT>>,
-    },*/
+        /// The name of this event
+        name: String,
+        /// The user-defined payload
+        payload: CustomEventPayload,
+        /// `PhantomData`
+        phantom: PhantomData<I>,
+    },
 }
 
 impl<I> Event<I>
@@ -250,6 +465,7 @@ where
                 corpus_size: _,
                 exit_kind: _,
                 observers_buf: _,
+                metadata: _,
                 time: _,
                 executions: _,
             } => "Testcase",
@@ -276,9 +492,17 @@ where
                 message: _,
                 phantom: _,
             } => "Log",
-            /*Event::Custom {
-                sender_id: _, /*custom_event} => custom_event.name()*/
-            } => "todo",*/
+            Event::TestcaseMetadata {
+                input_hash: _,
+                metadata: _,
+                client_config: _,
+                phantom: _,
+            } => "TestcaseMetadata",
+            Event::Custom {
+                name,
+                payload: _,
+                phantom: _,
+            } => name,
         }
     }
 }
@@ -316,6 +540,23 @@ where
         )
     }
 
+    /// Send off an [`Event::Custom`] event, carrying the given payload, to the broker.
+    /// This is a shortcut for [`EventFirer::fire`] with [`Event::Custom`] as argument.
+    /// The event is named after the payload's type.
+    fn fire_custom<S, T>(&mut self, state: &mut S, payload: T) -> Result<(), Error>
+    where
+        T: SerdeAny,
+    {
+        self.fire(
+            state,
+            Event::Custom {
+                name: type_name::<T>().to_string(),
+                payload: CustomEventPayload::new(payload),
+                phantom: PhantomData,
+            },
+        )
+    }
+
     /// Serialize all observers for this type and manager
     fn serialize_observers<OT, S>(&mut self, observers: &OT) -> Result<Vec<u8>, Error>
     where
@@ -328,6 +569,14 @@ where
     fn configuration(&self) -> EventConfig {
         EventConfig::AlwaysUnique
     }
+
+    /// The types of testcase metadata to send along with each [`Event::NewTestcase`],
+    /// and again with [`Event::TestcaseMetadata`] once the stages ran on the testcase.
+    /// Receivers with a matching [`EventConfig`] add them to their own copy of the testcase.
+    /// By default, no metadata is sent.
+    fn shared_metadata(&self) -> &[TypeId] {
+        &[]
+    }
 }
 
 /// [`ProgressReporter`] report progress to the broker.
@@ -490,15 +739,22 @@ impl HasEventManagerId for NopEventManager {
 #[cfg(test)]
 mod tests {
 
+    use alloc::boxed::Box;
+    use core::{any::TypeId, marker::PhantomData};
     use tuple_list::tuple_list_type;
 
     use crate::{
         bolts::{
             current_time,
+            serdeany::SerdeAnyMap,
             tuples::{tuple_list, Named},
         },
-        events::{Event, EventConfig},
+        events::{
+            BrokerEventResult, CustomBrokerEventHandler, CustomEventHandlers, CustomEventPayload,
+            Event, EventConfig, ReceivedTestcasesMetadata, RECEIVED_TESTCASES_MAX,
+        },
         executors::ExitKind,
+        feedbacks::{MapIndexesMetadata, MapNoveltiesMetadata},
         inputs::bytes::BytesInput,
         observers::StdMapObserver,
     };
@@ -511,10 +767,17 @@ mod tests {
         let map = tuple_list!(obv);
         let observers_buf = postcard::to_allocvec(&map).unwrap();
 
+        let mut metadata = SerdeAnyMap::new();
+        metadata.insert(MapIndexesMetadata::new(vec![1, 2, 3]));
+        metadata.insert(MapNoveltiesMetadata::new(vec![4]));
+        let shared = [TypeId::of::<MapIndexesMetadata>()];
+        metadata.retain(|type_id| shared.contains(type_id));
+
         let i = BytesInput::new(vec![0]);
         let e = Event::NewTestcase {
             input: i,
             observers_buf: Some(observers_buf),
+            metadata: Some(metadata),
             exit_kind: ExitKind::Ok,
             corpus_size: 123,
             client_config: EventConfig::AlwaysUnique,
@@ -529,6 +792,7 @@ mod tests {
             Event::NewTestcase {
                 input: _,
                 observers_buf,
+                metadata,
                 corpus_size: _,
                 exit_kind: _,
                 client_config: _,
@@ -538,10 +802,80 @@ mod tests {
                 let o: tuple_list_type!(StdMapObserver::<u32>) =
                     postcard::from_bytes(observers_buf.as_ref().unwrap()).unwrap();
                 assert_eq!("test", o.0.name());
+
+                let metadata = metadata.unwrap();
+                assert_eq!(metadata.len(), 1);
+                let mut local = SerdeAnyMap::new();
+                local.insert(MapIndexesMetadata::new(vec![5]));
+                local.merge(metadata);
+                assert_eq!(local.get::<MapIndexesMetadata>().unwrap().list, [5]);
             }
             _ => panic!("mistmatch"),
         };
     }
+
+    #[test]
+    fn test_custom_event_serde() {
+        let e = Event::<BytesInput>::Custom {
+            name: "indexes".into(),
+            payload: CustomEventPayload::new(MapIndexesMetadata::new(vec![1, 2, 3])),
+            phantom: PhantomData,
+        };
+
+        let serialized = postcard::to_allocvec(&e).unwrap();
+
+        let d = postcard::from_bytes::<Event<BytesInput>>(&serialized).unwrap();
+        assert_eq!(d.name(), "indexes");
+        match d {
+            Event::Custom {
+                name: _,
+                payload,
+                phantom: _,
+            } => {
+                assert!(!payload.is::<MapNoveltiesMetadata>());
+                assert_eq!(payload.get::<MapIndexesMetadata>().unwrap().list, [1, 2, 3]);
+            }
+            _ => panic!("mistmatch"),
+        };
+    }
+
+    #[test]
+    fn test_custom_event_handlers() {
+        let mut handlers = CustomEventHandlers::<CustomBrokerEventHandler>::new();
+        handlers.insert::<MapIndexesMetadata>(Box::new(|client_id, payload| {
+            assert_eq!(client_id, 1);
+            assert!(payload.is::<MapIndexesMetadata>());
+            Ok(BrokerEventResult::Handled)
+        }));
+        assert_eq!(handlers.len(), 1);
+
+        let payload = CustomEventPayload::new(MapIndexesMetadata::new(vec![]));
+        let handler = handlers.get_mut(&payload).unwrap();
+        assert!(matches!(
+            handler(1, &payload).unwrap(),
+            BrokerEventResult::Handled
+        ));
+
+        let payload = CustomEventPayload::new(MapNoveltiesMetadata::new(vec![]));
+        assert!(handlers.get_mut(&payload).is_none());
+    }
+
+    #[test]
+    fn test_received_testcases_limit() {
+        let mut received = ReceivedTestcasesMetadata::default();
+        received.insert(1, 0);
+        received.insert(2, 1);
+        assert_eq!(received.take(1), Some(0));
+        assert_eq!(received.take(1), None);
+
+        // The oldest testcases stop waiting for their metadata
+        for i in 0..RECEIVED_TESTCASES_MAX as u64 {
+            received.insert(100 + i, 2);
+        }
+        assert_eq!(received.len(), RECEIVED_TESTCASES_MAX);
+        assert_eq!(received.take(2), None);
+        assert_eq!(received.take(100), Some(2));
+    }
 }
 /// `EventManager` Python bindings
 #[cfg(feature = "python")]
@@ -135,6 +135,7 @@ where
                 exit_kind: _,
                 corpus_size,
                 observers_buf: _,
+                metadata: _,
                 time,
                 executions,
             } => {
@@ -201,7 +202,19 @@ where
                 #[cfg(feature = "std")]
                 println!("[LOG {}]: {}", severity_level, message);
                 Ok(BrokerEventResult::Handled)
-            } //_ => Ok(BrokerEventResult::Forward),
+            }
+            // There are no other clients to receive testcase metadata or handle custom events.
+            Event::TestcaseMetadata {
+                input_hash: _,
+                metadata: _,
+                client_config: _,
+                phantom: _,
+            }
+            | Event::Custom {
+                name: _,
+                payload: _,
+                phantom: _,
+            } => Ok(BrokerEventResult::Handled),
         }
     }
 
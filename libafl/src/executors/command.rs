@@ -22,7 +22,10 @@ use crate::{
         AsSlice,
     },
     inputs::HasTargetBytes,
-    observers::{ASANBacktraceObserver, ObserversTuple, StdErrObserver, StdOutObserver},
+    observers::{
+        ASANBacktraceObserver, ObserversTuple, SanitizerReportObserver, StdErrObserver,
+        StdOutObserver,
+    },
 };
 #[cfg(feature = "std")]
 use crate::{inputs::Input, Error};
@@ -142,6 +145,7 @@ impl CommandConfigurator for StdCommandConfigurator {
 /// A `CommandExecutor` is a wrapper around [`std::process::Command`] to execute a target as a child process.
 /// Construct a `CommandExecutor` by implementing [`CommandConfigurator`] for a type of your choice and calling [`CommandConfigurator::into_executor`] on it.
 /// Instead, you can use [`CommandExecutor::builder()`] to construct a [`CommandExecutor`] backed by a [`StdCommandConfigurator`].
+#[allow(clippy::struct_excessive_bools)]
 pub struct CommandExecutor<EM, I, OT, S, T, Z>
 where
     T: Debug,
@@ -152,6 +156,8 @@ where
     observers: OT,
     /// cache if the AsanBacktraceObserver is present
     has_asan_observer: bool,
+    /// cache if the [`SanitizerReportObserver`] is present
+    has_sanitizer_observer: bool,
     /// If set, we found a [`StdErrObserver`] in the observer list.
     /// Pipe the child's `stderr` instead of closing it.
     has_stdout_observer: bool,
@@ -239,13 +245,17 @@ where
         let has_asan_observer = observers
             .match_name::<ASANBacktraceObserver>("ASANBacktraceObserver")
             .is_some();
-        if has_stderr_observer || has_asan_observer {
+        let has_sanitizer_observer = observers
+            .match_name::<SanitizerReportObserver>("SanitizerReportObserver")
+            .is_some();
+        if has_stderr_observer || has_asan_observer || has_sanitizer_observer {
             command.stderr(Stdio::piped());
         }
 
         Ok(Self {
             observers,
             has_asan_observer,
+            has_sanitizer_observer,
             configurer: StdCommandConfigurator {
                 input_location: InputLocation::File {
                     out_file: OutFile::create(path)?,
@@ -341,7 +351,7 @@ where
             }
         };
 
-        if self.has_asan_observer || self.has_stderr_observer {
+        if self.has_asan_observer || self.has_sanitizer_observer || self.has_stderr_observer {
             let mut stderr = String::new();
             child.stderr.as_mut().ok_or_else(|| {
                 Error::IllegalState(
@@ -354,6 +364,12 @@ where
                     .unwrap()
                     .parse_asan_output(&stderr);
             }
+            if self.has_sanitizer_observer {
+                self.observers
+                    .match_name_mut::<SanitizerReportObserver>("SanitizerReportObserver")
+                    .unwrap()
+                    .parse_output(&stderr);
+            }
             if self.has_stderr_observer {
                 self.observers
                     .match_name_mut::<StdErrObserver>("StdErrObserver")
@@ -569,6 +585,9 @@ impl CommandExecutorBuilder {
         if observers
             .match_name::<ASANBacktraceObserver>("ASANBacktraceObserver")
             .is_some()
+            || observers
+                .match_name::<SanitizerReportObserver>("SanitizerReportObserver")
+                .is_some()
             || observers
                 .match_name::<StdErrObserver>("StdErrObserver")
                 .is_some()
@@ -638,6 +657,10 @@ pub trait CommandConfigurator: Sized + Debug {
             .match_name::<ASANBacktraceObserver>("ASANBacktraceObserver")
             .is_some();
 
+        let has_sanitizer_observer = observers
+            .match_name::<SanitizerReportObserver>("SanitizerReportObserver")
+            .is_some();
+
         let has_stdout_observer = observers
             .match_name::<StdOutObserver>("StdOutObserver")
             .is_some();
@@ -649,6 +672,7 @@ pub trait CommandConfigurator: Sized + Debug {
         CommandExecutor {
             observers,
             has_asan_observer,
+            has_sanitizer_observer,
             has_stdout_observer,
             has_stderr_observer,
             configurer: self,
@@ -7,6 +7,7 @@ use core::{
 };
 use std::{
     ffi::{OsStr, OsString},
+    fs,
     io::{self, prelude::*, ErrorKind},
     os::unix::{io::RawFd, process::CommandExt},
     path::Path,
@@ -20,10 +21,13 @@ use crate::{
         shmem::{ShMem, ShMemProvider, StdShMemProvider},
         AsMutSlice, AsSlice,
     },
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{oom::is_oom_report, Executor, ExitKind, HasObservers},
     inputs::{HasTargetBytes, Input},
     mutators::Tokens,
-    observers::{get_asan_runtime_flags_with_log_path, ASANBacktraceObserver, ObserversTuple},
+    observers::{
+        get_asan_runtime_flags_with_log_path, get_sanitizers_runtime_flags_with_log_path,
+        ASANBacktraceObserver, ObserversTuple, SanitizerReportObserver, ASAN_LOG_PATH,
+    },
     Error,
 };
 
@@ -32,6 +36,7 @@ use nix::{
         select::{pselect, FdSet},
         signal::{kill, SigSet, Signal},
         time::{TimeSpec, TimeValLike},
+        wait::{waitpid, WaitStatus},
     },
     unistd::Pid,
 };
@@ -43,8 +48,110 @@ const FS_OPT_ENABLED: i32 = 0x80000001_u32 as i32;
 const FS_OPT_SHDMEM_FUZZ: i32 = 0x01000000_u32 as i32;
 #[allow(clippy::cast_possible_wrap)]
 const FS_OPT_AUTODICT: i32 = 0x10000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_MAPSIZE: i32 = 0x40000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_ERROR: i32 = 0xf800008f_u32 as i32;
+/// Old `AFL++` binaries set these bits by mistake
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_OLD_AFLPP_WORKAROUND: i32 = 0x0f000000_u32 as i32;
+const FS_ERROR_MAP_SIZE: i32 = 1;
+const FS_ERROR_MAP_ADDR: i32 = 2;
+const FS_ERROR_SHM_OPEN: i32 = 4;
+const FS_ERROR_SHMAT: i32 = 8;
+const FS_ERROR_MMAP: i32 = 16;
+const FS_ERROR_OLD_CMPLOG: i32 = 32;
+const FS_ERROR_OLD_CMPLOG_QEMU: i32 = 64;
+/// The default coverage map size of `AFL++`
+const DEFAULT_MAP_SIZE: usize = 65536;
 const SHMEM_FUZZ_HDR_SIZE: usize = 4;
 const MAX_FILE: usize = 1024 * 1024;
+/// The signature `afl-cc` places in binaries using `__AFL_LOOP`
+const PERSIST_SIG: &[u8] = b"##SIG_AFL_PERSISTENT##";
+/// The signature `afl-cc` places in binaries using `__AFL_INIT`
+const DEFER_SIG: &[u8] = b"##SIG_AFL_DEFER_FORKSRV##";
+/// A symbol of binaries built with `ASan`
+const ASAN_SIG: &[u8] = b"__asan_init";
+
+/// The reason a forkserver target failed to initialize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkserverError {
+    /// The coverage map is smaller than the map of the target, of the given size if it reported it
+    MapSize(Option<usize>),
+    /// The target could not map the coverage map at its fixed address
+    MapAddr,
+    /// The target could not open the coverage shared memory
+    ShmOpen,
+    /// The target could not attach the coverage shared memory
+    Shmat,
+    /// The target could not `mmap` the coverage shared memory
+    Mmap,
+    /// The target was built with the old `CmpLog` instrumentation of `AFL++`
+    OldCmplog,
+    /// The target is run in an old `CmpLog` `QEMU`
+    OldCmplogQemu,
+    /// The target reported an unknown error code
+    Unknown(i32),
+    /// The target was killed by the given signal before the handshake, for example by an `abort` in its init
+    InitCrash(i32),
+    /// The target was built with `ASan` and died before the handshake,
+    /// because the shadow memory of `ASan` does not fit into the memory limit
+    AsanMismatch,
+}
+
+impl fmt::Display for ForkserverError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MapSize(Some(size)) => write!(
+                f,
+                "the coverage map is smaller than the map of the target ({} bytes)",
+                size
+            ),
+            Self::MapSize(None) => {
+                write!(f, "the coverage map is smaller than the map of the target")
+            }
+            Self::MapAddr => write!(
+                f,
+                "the target could not map the coverage map at its fixed address"
+            ),
+            Self::ShmOpen => write!(f, "the target could not open the coverage shared memory"),
+            Self::Shmat => write!(f, "the target could not attach the coverage shared memory"),
+            Self::Mmap => write!(f, "the target could not mmap the coverage shared memory"),
+            Self::OldCmplog => write!(
+                f,
+                "the target was built with the old AFL++ cmplog instrumentation"
+            ),
+            Self::OldCmplogQemu => write!(f, "the target is run in an old cmplog qemu"),
+            Self::Unknown(code) => write!(f, "the target reported the unknown error {}", code),
+            Self::InitCrash(signal) => write!(
+                f,
+                "the target was killed by signal {} during its init",
+                signal
+            ),
+            Self::AsanMismatch => write!(
+                f,
+                "the target was built with ASan, which needs more memory than the memory limit"
+            ),
+        }
+    }
+}
+
+impl ForkserverError {
+    /// Decode the error of a `FS_OPT_ERROR` status
+    #[must_use]
+    pub fn from_status(status: i32) -> Self {
+        match (status & 0x00ffff00) >> 8 {
+            FS_ERROR_MAP_SIZE => Self::MapSize(None),
+            FS_ERROR_MAP_ADDR => Self::MapAddr,
+            FS_ERROR_SHM_OPEN => Self::ShmOpen,
+            FS_ERROR_SHMAT => Self::Shmat,
+            FS_ERROR_MMAP => Self::Mmap,
+            FS_ERROR_OLD_CMPLOG => Self::OldCmplog,
+            FS_ERROR_OLD_CMPLOG_QEMU => Self::OldCmplogQemu,
+            code => Self::Unknown(code),
+        }
+    }
+}
 
 /// Configure the target, `limit`, `setsid`, `pipe_stdin`, the code was borrowed from the [`Angora`](https://github.com/AngoraFuzzer/Angora) fuzzer
 pub trait ConfigTarget {
@@ -166,10 +273,28 @@ pub struct Forkserver {
     child_pid: Pid,
     status: i32,
     last_run_timed_out: i32,
+    is_persistent: bool,
+    fsrv_pid: Pid,
+    memlimit: u64,
+}
+
+impl Drop for Forkserver {
+    fn drop(&mut self) {
+        if self.child_pid.as_raw() > 0 {
+            let _ = kill(self.child_pid, Signal::SIGKILL);
+        }
+        if self.fsrv_pid.as_raw() > 0 {
+            let _ = kill(self.fsrv_pid, Signal::SIGKILL);
+            let _ = waitpid(self.fsrv_pid, None);
+        }
+    }
 }
 
 impl Forkserver {
-    /// Create a new [`Forkserver`]
+    /// Create a new [`Forkserver`].
+    /// If `is_persistent` is set, the target runs its `__AFL_LOOP` instead of forking for each input.
+    /// If `is_deferred_frksrv` is set, the forkserver only starts at the `__AFL_INIT` of the target.
+    #[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
     pub fn new(
         target: OsString,
         args: Vec<OsString>,
@@ -177,6 +302,8 @@ impl Forkserver {
         out_filefd: RawFd,
         use_stdin: bool,
         memlimit: u64,
+        is_persistent: bool,
+        is_deferred_frksrv: bool,
         debug_output: bool,
     ) -> Result<Self, Error> {
         let mut st_pipe = Pipe::new().unwrap();
@@ -188,13 +315,27 @@ impl Forkserver {
             (Stdio::null(), Stdio::null())
         };
 
-        match Command::new(target)
+        let mut command = Command::new(target);
+        if is_persistent {
+            command.env("__AFL_PERSISTENT", "1");
+        }
+        if is_deferred_frksrv {
+            command.env("__AFL_DEFER_FORKSRV", "1");
+        }
+
+        let fsrv_pid = match command
             .args(args)
             .stdin(Stdio::null())
             .stdout(stdout)
             .stderr(stderr)
             .env("LD_BIND_LAZY", "1")
             .env("ASAN_OPTIONS", get_asan_runtime_flags_with_log_path())
+            .env(
+                "UBSAN_OPTIONS",
+                get_sanitizers_runtime_flags_with_log_path(),
+            )
+            .env("MSAN_OPTIONS", get_sanitizers_runtime_flags_with_log_path())
+            .env("TSAN_OPTIONS", get_sanitizers_runtime_flags_with_log_path())
             .envs(envs)
             .setlimit(memlimit)
             .setsid()
@@ -207,7 +348,8 @@ impl Forkserver {
             )
             .spawn()
         {
-            Ok(_) => (),
+            #[allow(clippy::cast_possible_wrap)]
+            Ok(child) => Pid::from_raw(child.id() as i32),
             Err(err) => {
                 return Err(Error::Forkserver(format!(
                     "Could not spawn the forkserver: {:#?}",
@@ -226,9 +368,43 @@ impl Forkserver {
             child_pid: Pid::from_raw(0),
             status: 0,
             last_run_timed_out: 0,
+            is_persistent,
+            fsrv_pid,
+            memlimit,
         })
     }
 
+    /// The pid of the forkserver process
+    #[must_use]
+    pub fn fsrv_pid(&self) -> Pid {
+        self.fsrv_pid
+    }
+
+    /// Find out why the forkserver died before the handshake.
+    /// Set `uses_asan` if the target was built with `ASan`.
+    pub fn init_failure(&mut self, uses_asan: bool) -> Error {
+        let status = waitpid(self.fsrv_pid, None);
+        self.fsrv_pid = Pid::from_raw(0);
+        match status {
+            // `ASan` exits with an error instead of crashing if it can't map its shadow memory
+            Ok(WaitStatus::Signaled(_, _, _) | WaitStatus::Exited(_, 1..=255))
+                if uses_asan && self.memlimit > 0 =>
+            {
+                ForkserverError::AsanMismatch.into()
+            }
+            Ok(WaitStatus::Signaled(_, signal, _)) => {
+                ForkserverError::InitCrash(signal as i32).into()
+            }
+            _ => Error::Forkserver("Failed to start a forkserver".to_string()),
+        }
+    }
+
+    /// If the target runs in persistent mode, reusing its child process between inputs
+    #[must_use]
+    pub fn is_persistent(&self) -> bool {
+        self.is_persistent
+    }
+
     /// If the last run timed out
     #[must_use]
     pub fn last_run_timed_out(&self) -> i32 {
@@ -324,6 +500,19 @@ impl Forkserver {
     }
 }
 
+/// Write the testcase to the `__AFL_SHM_FUZZ_ID` shared memory, truncated to [`MAX_FILE`] bytes.
+fn write_shmem_testcase<SHM>(shmem: &mut SHM, target_bytes: &[u8])
+where
+    SHM: ShMem,
+{
+    let size = target_bytes.len().min(MAX_FILE);
+    // The first four bytes tell the size of the testcase.
+    #[allow(clippy::cast_possible_truncation)] // `MAX_FILE` fits into an `u32`
+    shmem.as_mut_slice()[..SHMEM_FUZZ_HDR_SIZE].copy_from_slice(&(size as u32).to_ne_bytes());
+    shmem.as_mut_slice()[SHMEM_FUZZ_HDR_SIZE..(SHMEM_FUZZ_HDR_SIZE + size)]
+        .copy_from_slice(&target_bytes[..size]);
+}
+
 /// A struct that has a forkserver
 pub trait HasForkserver {
     /// The [`ShMemProvider`] used for this forkserver's map
@@ -394,13 +583,7 @@ where
 
         match &mut self.executor.shmem_mut() {
             Some(shmem) => {
-                let target_bytes = input.target_bytes();
-                let size = target_bytes.as_slice().len();
-                let size_in_bytes = size.to_ne_bytes();
-                // The first four bytes tells the size of the shmem.
-                shmem.as_mut_slice()[..4].copy_from_slice(&size_in_bytes[..4]);
-                shmem.as_mut_slice()[SHMEM_FUZZ_HDR_SIZE..(SHMEM_FUZZ_HDR_SIZE + size)]
-                    .copy_from_slice(target_bytes.as_slice());
+                write_shmem_testcase(shmem, input.target_bytes().as_slice());
             }
             None => {
                 self.executor
@@ -446,7 +629,7 @@ where
         {
             self.executor.forkserver_mut().set_status(status);
             if libc::WIFSIGNALED(self.executor.forkserver().status()) {
-                exit_kind = ExitKind::Crash;
+                exit_kind = signaled_exit_kind(pid);
             }
         } else {
             self.executor.forkserver_mut().set_last_run_timed_out(1);
@@ -471,7 +654,8 @@ where
 }
 
 /// This [`Executor`] can run binaries compiled for AFL/AFL++ that make use of a forkserver.
-/// Shared memory feature is also available, but you have to set things up in your code.
+/// Persistent mode (`__AFL_LOOP`) and shared memory testcases (`__AFL_FUZZ_TESTCASE_BUF`) are supported,
+/// see [`ForkserverExecutorBuilder::is_persistent`] and [`ForkserverExecutorBuilder::shmem_provider`].
 /// Please refer to AFL++'s docs. <https://github.com/AFLplusplus/AFLplusplus/blob/stable/instrumentation/README.persistent_mode.md>
 pub struct ForkserverExecutor<I, OT, S, SP>
 where
@@ -483,10 +667,14 @@ where
     out_file: OutFile,
     forkserver: Forkserver,
     observers: OT,
+    /// The coverage map of [`ForkserverExecutorBuilder::build_dynamic_map`], dropped after the observers.
+    coverage_map: Option<SP::ShMem>,
     map: Option<SP::ShMem>,
     phantom: PhantomData<(I, S)>,
     /// Cache that indicates if we have a asan observer registered.
     has_asan_observer: Option<bool>,
+    /// Cache that indicates if we have a [`SanitizerReportObserver`] registered.
+    has_sanitizer_observer: Option<bool>,
 }
 
 impl<I, OT, S, SP> Debug for ForkserverExecutor<I, OT, S, SP>
@@ -501,6 +689,7 @@ where
             .field("out_file", &self.out_file)
             .field("forkserver", &self.forkserver)
             .field("observers", &self.observers)
+            .field("coverage_map", &self.coverage_map)
             .field("map", &self.map)
             .finish()
     }
@@ -543,20 +732,30 @@ where
 
 /// The builder for `ForkserverExecutor`
 #[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ForkserverExecutorBuilder<'a, SP> {
     program: Option<OsString>,
     arguments: Vec<OsString>,
     envs: Vec<(OsString, OsString)>,
     debug_child: bool,
     use_stdin: bool,
+    is_persistent: bool,
+    is_deferred_frksrv: bool,
+    coverage_map_size: Option<usize>,
+    memlimit: u64,
     autotokens: Option<&'a mut Tokens>,
     out_filename: Option<OsString>,
     shmem_provider: Option<&'a mut SP>,
 }
 
 impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
+    /// The [`ShMemProvider`] given to [`ForkserverExecutorBuilder::shmem_provider`], if any
+    pub fn shmem_provider_mut(&mut self) -> Option<&mut SP> {
+        self.shmem_provider.as_deref_mut()
+    }
+
     /// Builds `ForkserverExecutor`.
-    #[allow(clippy::pedantic)]
+    /// If a [`ForkserverExecutorBuilder::coverage_map_size`] is set, the map size reported by the target is checked against it.
     pub fn build<I, OT, S>(
         &mut self,
         observers: OT,
@@ -565,6 +764,124 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
         I: Input + HasTargetBytes,
         OT: ObserversTuple<I, S>,
         SP: ShMemProvider,
+    {
+        let (target, forkserver, out_file, map, target_map_size) =
+            self.build_helper(self.coverage_map_size)?;
+
+        if let (Some(map_size), Some(target_map_size)) = (self.coverage_map_size, target_map_size) {
+            if target_map_size > map_size {
+                return Err(ForkserverError::MapSize(Some(target_map_size)).into());
+            }
+        }
+
+        Ok(ForkserverExecutor {
+            target,
+            args: self.arguments.clone(),
+            out_file,
+            forkserver,
+            observers,
+            coverage_map: None,
+            map,
+            phantom: PhantomData,
+            has_asan_observer: None,      // initialized on first use
+            has_sanitizer_observer: None, // initialized on first use
+        })
+    }
+
+    /// Builds a `ForkserverExecutor` owning its coverage map, a `__AFL_SHM_ID` shared memory of the [`ShMemProvider`].
+    /// The map is sized to the map size the target reports, restarting the target once if the map was too small.
+    /// Then, `observers` creates the observers for the map.
+    ///
+    /// # Safety
+    /// The map passed to `observers` lives as long as the executor, and must not be used after the executor is dropped.
+    pub unsafe fn build_dynamic_map<I, OT, S, F>(
+        &mut self,
+        observers: F,
+    ) -> Result<ForkserverExecutor<I, OT, S, SP>, Error>
+    where
+        I: Input + HasTargetBytes,
+        OT: ObserversTuple<I, S>,
+        SP: ShMemProvider,
+        F: FnOnce(&'static mut [u8]) -> OT,
+    {
+        let mut map_size = self.coverage_map_size.unwrap_or(DEFAULT_MAP_SIZE);
+        let mut resized = false;
+        loop {
+            let mut coverage_map = match &mut self.shmem_provider {
+                Some(provider) => provider.new_shmem(map_size)?,
+                None => {
+                    return Err(Error::IllegalArgument(
+                        "ForkserverExecutorBuilder::build_dynamic_map: a shmem_provider is needed for the coverage map"
+                            .to_string(),
+                    ))
+                }
+            };
+            coverage_map.write_to_env("__AFL_SHM_ID")?;
+
+            let (target, forkserver, out_file, map, target_map_size) =
+                match self.build_helper(Some(map_size)) {
+                    Err(Error::ForkserverInit(ForkserverError::MapSize(None))) if !resized => {
+                        // The target did not report the size of its map, ask it
+                        map_size = self.dump_map_size()?;
+                        resized = true;
+                        continue;
+                    }
+                    res => res?,
+                };
+
+            if let Some(target_map_size) = target_map_size {
+                if target_map_size > map_size {
+                    if resized {
+                        return Err(ForkserverError::MapSize(Some(target_map_size)).into());
+                    }
+                    println!(
+                        "Restarting the target with a coverage map of {} bytes",
+                        target_map_size
+                    );
+                    drop(forkserver);
+                    map_size = target_map_size;
+                    resized = true;
+                    continue;
+                }
+            }
+
+            let len = target_map_size.unwrap_or(map_size);
+            let map_slice =
+                core::slice::from_raw_parts_mut(coverage_map.as_mut_slice().as_mut_ptr(), len);
+
+            return Ok(ForkserverExecutor {
+                target,
+                args: self.arguments.clone(),
+                out_file,
+                forkserver,
+                observers: observers(map_slice),
+                coverage_map: Some(coverage_map),
+                map,
+                phantom: PhantomData,
+                has_asan_observer: None,      // initialized on first use
+                has_sanitizer_observer: None, // initialized on first use
+            });
+        }
+    }
+
+    /// Spawns the forkserver and negotiates the options of the handshake.
+    /// Returns the map size of the target, if it reported it.
+    #[allow(clippy::type_complexity, clippy::too_many_lines)]
+    fn build_helper(
+        &mut self,
+        map_size: Option<usize>,
+    ) -> Result<
+        (
+            OsString,
+            Forkserver,
+            OutFile,
+            Option<SP::ShMem>,
+            Option<usize>,
+        ),
+        Error,
+    >
+    where
+        SP: ShMemProvider,
     {
         let out_filename = match &self.out_filename {
             Some(name) => name.clone(),
@@ -573,7 +890,7 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
 
         let out_file = OutFile::create(&out_filename)?;
 
-        let map = match &mut self.shmem_provider {
+        let mut map = match &mut self.shmem_provider {
             None => None,
             Some(provider) => {
                 // setup shared memory
@@ -586,19 +903,42 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
             }
         };
 
-        let (target, mut forkserver) = match &self.program {
+        let mut envs = self.envs.clone();
+        if let Some(map_size) = map_size {
+            envs.push(("AFL_MAP_SIZE".into(), map_size.to_string().into()));
+        }
+
+        let (target, mut forkserver, uses_asan) = match &self.program {
             Some(t) => {
+                // Like `afl-fuzz`, detect the persistent and deferred modes by the signatures in the binary
+                let (is_persistent, is_deferred_frksrv, uses_asan) = match fs::read(t) {
+                    Ok(binary) => (
+                        self.is_persistent || contains_signature(&binary, PERSIST_SIG),
+                        self.is_deferred_frksrv || contains_signature(&binary, DEFER_SIG),
+                        contains_signature(&binary, ASAN_SIG),
+                    ),
+                    Err(_) => (self.is_persistent, self.is_deferred_frksrv, false),
+                };
+                if is_persistent {
+                    println!("Persistent mode binary detected.");
+                }
+                if is_deferred_frksrv {
+                    println!("Deferred forkserver binary detected.");
+                }
+
                 let forkserver = Forkserver::new(
                     t.clone(),
                     self.arguments.clone(),
-                    self.envs.clone(),
+                    envs,
                     out_file.as_raw_fd(),
                     self.use_stdin,
-                    0,
+                    self.memlimit,
+                    is_persistent,
+                    is_deferred_frksrv,
                     self.debug_child,
                 )?;
 
-                (t.clone(), forkserver)
+                (t.clone(), forkserver, uses_asan)
             }
             None => {
                 return Err(Error::IllegalArgument(
@@ -607,21 +947,42 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
             }
         };
 
-        let (rlen, status) = forkserver.read_st()?; // Initial handshake, read 4-bytes hello message from the forkserver.
+        let (rlen, mut status) = forkserver.read_st()?; // Initial handshake, read 4-bytes hello message from the forkserver.
 
         if rlen != 4 {
-            return Err(Error::Forkserver(
-                "Failed to start a forkserver".to_string(),
-            ));
+            return Err(forkserver.init_failure(uses_asan));
         }
         println!("All right - fork server is up.");
+
+        if status & FS_OPT_OLD_AFLPP_WORKAROUND == FS_OPT_OLD_AFLPP_WORKAROUND {
+            status &= !FS_OPT_OLD_AFLPP_WORKAROUND;
+        }
+
+        if status & FS_OPT_ERROR == FS_OPT_ERROR {
+            return Err(ForkserverError::from_status(status).into());
+        }
+
+        let mut target_map_size = None;
+
         // If forkserver is responding, we then check if there's any option enabled.
         if status & FS_OPT_ENABLED == FS_OPT_ENABLED {
+            if status & FS_OPT_MAPSIZE == FS_OPT_MAPSIZE {
+                #[allow(clippy::cast_sign_loss)] // masked to 24 bits
+                let size = (((status & 0x00fffffe) >> 1) + 1) as usize;
+                // Round up to 64 bytes, like `afl-fuzz`
+                let size = (size + 63) & !63;
+                println!("Target map size: {}", size);
+                target_map_size = Some(size);
+            }
+
             let mut send_status = FS_OPT_ENABLED;
 
             if (status & FS_OPT_SHDMEM_FUZZ == FS_OPT_SHDMEM_FUZZ) && map.is_some() {
                 println!("Using SHARED MEMORY FUZZING feature.");
                 send_status |= FS_OPT_SHDMEM_FUZZ;
+            } else if map.is_some() {
+                println!("The target does not support shared memory testcases, using a file.");
+                map = None;
             }
 
             if (status & FS_OPT_AUTODICT == FS_OPT_AUTODICT) && self.autotokens.is_some() {
@@ -629,11 +990,16 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
                 send_status |= FS_OPT_AUTODICT;
             }
 
-            let send_len = forkserver.write_ctl(send_status)?;
-            if send_len != 4 {
-                return Err(Error::Forkserver(
-                    "Writing to forkserver failed.".to_string(),
-                ));
+            // The target only waits for an answer if it offered shared memory testcases or a dictionary
+            if status & FS_OPT_SHDMEM_FUZZ == FS_OPT_SHDMEM_FUZZ
+                || status & FS_OPT_AUTODICT == FS_OPT_AUTODICT
+            {
+                let send_len = forkserver.write_ctl(send_status)?;
+                if send_len != 4 {
+                    return Err(Error::Forkserver(
+                        "Writing to forkserver failed.".to_string(),
+                    ));
+                }
             }
 
             if (send_status & FS_OPT_AUTODICT) == FS_OPT_AUTODICT {
@@ -652,20 +1018,27 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
 
                 println!("Autodict size {:x}", dict_size);
 
-                let (rlen, buf) = forkserver.read_st_size(dict_size as usize)?;
+                #[allow(clippy::cast_sign_loss)] // checked above
+                let dict_size = dict_size as usize;
+                let (rlen, buf) = forkserver.read_st_size(dict_size)?;
 
-                if rlen != dict_size as usize {
+                if rlen != dict_size {
                     return Err(Error::Forkserver(
                         "Failed to load autodictionary".to_string(),
                     ));
                 }
 
                 if let Some(t) = &mut self.autotokens {
-                    t.parse_autodict(&buf, dict_size as usize);
+                    t.parse_autodict(&buf, dict_size);
                 }
             }
         } else {
+            // Legacy protocol of `AFL` and old `AFL++` binaries
             println!("Forkserver Options are not available.");
+            if map.is_some() {
+                println!("The target does not support shared memory testcases, using a file.");
+                map = None;
+            }
         }
 
         println!(
@@ -675,37 +1048,25 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
             self.use_stdin
         );
 
-        Ok(ForkserverExecutor {
-            target,
-            args: self.arguments.clone(),
-            out_file,
-            forkserver,
-            observers,
-            map,
-            phantom: PhantomData,
-            has_asan_observer: None, // initialized on first use
-        })
+        Ok((target, forkserver, out_file, map, target_map_size))
     }
-}
 
-impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
-    /// Creates a new `AFL`-style [`ForkserverExecutor`] with the given target, arguments and observers.
-    /// This is the builder for `ForkserverExecutor`
-    /// This Forkserver will attempt to provide inputs over shared mem when `shmem_provider` is given.
-    /// Else this forkserver will try to write the input to `.cur_input` file.
-    /// If `debug_child` is set, the child will print to `stdout`/`stderr`.
-    #[must_use]
-    pub fn new() -> ForkserverExecutorBuilder<'a, StdShMemProvider> {
-        ForkserverExecutorBuilder {
-            program: None,
-            arguments: vec![],
-            envs: vec![],
-            debug_child: false,
-            use_stdin: true,
-            autotokens: None,
-            out_filename: None,
-            shmem_provider: None,
-        }
+    /// Ask the target for the size of its coverage map, with `AFL_DUMP_MAP_SIZE`
+    fn dump_map_size(&self) -> Result<usize, Error> {
+        let program = match &self.program {
+            Some(program) => program,
+            None => return Err(ForkserverError::MapSize(None).into()),
+        };
+        let output = Command::new(program)
+            .envs(self.envs.iter().cloned())
+            .env("AFL_DUMP_MAP_SIZE", "1")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .map_err(|_| ForkserverError::MapSize(None).into())
     }
 
     /// The harness
@@ -823,14 +1184,73 @@ impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
         self
     }
 
+    /// Run the target in persistent mode, looping in `__AFL_LOOP` instead of forking for each input.
+    /// This is detected from the binary if it was built by `afl-cc`.
+    #[must_use]
+    pub fn is_persistent(mut self, is_persistent: bool) -> Self {
+        self.is_persistent = is_persistent;
+        self
+    }
+
+    /// Start the forkserver at `__AFL_INIT` of the target, instead of before `main`.
+    /// This is detected from the binary if it was built by `afl-cc`.
+    #[must_use]
+    pub fn is_deferred_frksrv(mut self, is_deferred_frksrv: bool) -> Self {
+        self.is_deferred_frksrv = is_deferred_frksrv;
+        self
+    }
+
+    /// The size of the coverage map, the target fails the handshake if its map is larger.
+    /// For [`ForkserverExecutorBuilder::build_dynamic_map`], this is only the initial size.
+    #[must_use]
+    pub fn coverage_map_size(mut self, coverage_map_size: usize) -> Self {
+        self.coverage_map_size = Some(coverage_map_size);
+        self
+    }
+
+    /// Limit the memory of the target to `memlimit` MB with `RLIMIT_AS`, as the `-m` option of `afl-fuzz`, `0` for no limit.
+    /// Allocations beyond the limit fail. A target built with a sanitizer reports it and aborts,
+    /// which is reported as [`ExitKind::Oom`]. Other targets mostly crash then, reported as [`ExitKind::Crash`].
+    #[must_use]
+    pub fn memlimit(mut self, memlimit: u64) -> Self {
+        self.memlimit = memlimit;
+        self
+    }
+
     /// Use autodict?
     #[must_use]
     pub fn autotokens(mut self, tokens: &'a mut Tokens) -> Self {
         self.autotokens = Some(tokens);
         self
     }
+}
+
+impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
+    /// Creates a new `AFL`-style [`ForkserverExecutor`] with the given target, arguments and observers.
+    /// This is the builder for `ForkserverExecutor`
+    /// This Forkserver will attempt to provide inputs over shared mem when `shmem_provider` is given.
+    /// Else this forkserver will try to write the input to `.cur_input` file.
+    /// If `debug_child` is set, the child will print to `stdout`/`stderr`.
+    #[must_use]
+    pub fn new() -> ForkserverExecutorBuilder<'a, StdShMemProvider> {
+        ForkserverExecutorBuilder {
+            program: None,
+            arguments: vec![],
+            envs: vec![],
+            debug_child: false,
+            use_stdin: true,
+            is_persistent: false,
+            is_deferred_frksrv: false,
+            coverage_map_size: None,
+            memlimit: 0,
+            autotokens: None,
+            out_filename: None,
+            shmem_provider: None,
+        }
+    }
 
     /// Shmem provider for forkserver's shared memory testcase feature.
+    /// The testcases are delivered in `__AFL_FUZZ_TESTCASE_BUF` if the target supports it, else in the file.
     pub fn shmem_provider<SP: ShMemProvider>(
         self,
         shmem_provider: &'a mut SP,
@@ -841,6 +1261,10 @@ impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
             envs: self.envs,
             debug_child: self.debug_child,
             use_stdin: self.use_stdin,
+            is_persistent: self.is_persistent,
+            is_deferred_frksrv: self.is_deferred_frksrv,
+            coverage_map_size: self.coverage_map_size,
+            memlimit: self.memlimit,
             autotokens: self.autotokens,
             out_filename: self.out_filename,
             shmem_provider: Some(shmem_provider),
@@ -848,6 +1272,21 @@ impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
     }
 }
 
+/// The [`ExitKind`] of the child `pid` killed by a signal, [`ExitKind::Oom`] if its sanitizer log reports running out of memory
+fn signaled_exit_kind(pid: i32) -> ExitKind {
+    match fs::read_to_string(format!("{}.{}", ASAN_LOG_PATH, pid)) {
+        Ok(report) if is_oom_report(&report) => ExitKind::Oom,
+        _ => ExitKind::Crash,
+    }
+}
+
+/// Check if the `binary` contains the `afl-cc` `signature`
+fn contains_signature(binary: &[u8], signature: &[u8]) -> bool {
+    binary
+        .windows(signature.len())
+        .any(|window| window == signature)
+}
+
 impl<'a> Default for ForkserverExecutorBuilder<'a, StdShMemProvider> {
     fn default() -> Self {
         Self::new()
@@ -873,13 +1312,7 @@ where
         // Write to testcase
         match &mut self.map {
             Some(map) => {
-                let target_bytes = input.target_bytes();
-                let size = target_bytes.as_slice().len();
-                let size_in_bytes = size.to_ne_bytes();
-                // The first four bytes tells the size of the shmem.
-                map.as_mut_slice()[..4].copy_from_slice(&size_in_bytes[..4]);
-                map.as_mut_slice()[SHMEM_FUZZ_HDR_SIZE..(SHMEM_FUZZ_HDR_SIZE + size)]
-                    .copy_from_slice(target_bytes.as_slice());
+                write_shmem_testcase(map, input.target_bytes().as_slice());
             }
             None => {
                 self.out_file.write_buf(input.target_bytes().as_slice())?;
@@ -920,7 +1353,7 @@ where
         self.forkserver.set_status(status);
 
         if libc::WIFSIGNALED(self.forkserver.status()) {
-            exit_kind = ExitKind::Crash;
+            exit_kind = signaled_exit_kind(pid);
             if self.has_asan_observer.is_none() {
                 self.has_asan_observer = Some(
                     self.observers()
@@ -928,6 +1361,21 @@ where
                         .is_some(),
                 );
             }
+            if self.has_sanitizer_observer.is_none() {
+                self.has_sanitizer_observer = Some(
+                    self.observers()
+                        .match_name::<SanitizerReportObserver>("SanitizerReportObserver")
+                        .is_some(),
+                );
+            }
+            if self.has_sanitizer_observer.unwrap() {
+                // The log file is removed by the `ASANBacktraceObserver`, if any
+                let remove = !self.has_asan_observer.unwrap();
+                self.observers_mut()
+                    .match_name_mut::<SanitizerReportObserver>("SanitizerReportObserver")
+                    .unwrap()
+                    .parse_log_file(ASAN_LOG_PATH, pid, remove)?;
+            }
             if self.has_asan_observer.unwrap() {
                 self.observers_mut()
                     .match_name_mut::<ASANBacktraceObserver>("ASANBacktraceObserver")
@@ -1022,7 +1470,10 @@ mod tests {
             tuples::tuple_list,
             AsMutSlice,
         },
-        executors::forkserver::ForkserverExecutorBuilder,
+        executors::forkserver::{
+            contains_signature, write_shmem_testcase, Forkserver, ForkserverError,
+            ForkserverExecutorBuilder, FS_OPT_ERROR, PERSIST_SIG, SHMEM_FUZZ_HDR_SIZE,
+        },
         inputs::NopInput,
         observers::{ConstMapObserver, HitcountsMapObserver},
         Error,
@@ -1064,4 +1515,68 @@ mod tests {
         };
         assert!(result);
     }
+
+    #[test]
+    #[serial]
+    fn test_shmem_testcase() {
+        let mut shmem_provider = StdShMemProvider::new().unwrap();
+        let mut shmem = shmem_provider.new_shmem(SHMEM_FUZZ_HDR_SIZE + 16).unwrap();
+        write_shmem_testcase(&mut shmem, b"hello");
+        assert_eq!(shmem.as_mut_slice()[..4], 5_u32.to_ne_bytes());
+        assert_eq!(&shmem.as_mut_slice()[4..9], b"hello");
+
+        assert!(contains_signature(
+            b"\0\0##SIG_AFL_PERSISTENT##\0",
+            PERSIST_SIG
+        ));
+        assert!(!contains_signature(
+            b"##SIG_AFL_DEFER_FORKSRV##",
+            PERSIST_SIG
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_forkserver_init_error() {
+        assert_eq!(
+            ForkserverError::from_status(FS_OPT_ERROR | (1 << 8)),
+            ForkserverError::MapSize(None)
+        );
+        assert_eq!(
+            ForkserverError::from_status(FS_OPT_ERROR | (32 << 8)),
+            ForkserverError::OldCmplog
+        );
+
+        let executor = ForkserverExecutorBuilder::new()
+            .program("sh")
+            .args(&["-c", "kill -SEGV $$"])
+            .build::<NopInput, _, ()>(tuple_list!());
+        assert!(matches!(
+            executor,
+            Err(Error::ForkserverInit(ForkserverError::InitCrash(11)))
+        ));
+
+        // `ASan` exits with status 1 if its shadow memory does not fit into the memory limit
+        let init_failure = |uses_asan, memlimit| {
+            Forkserver::new(
+                OsString::from("sh"),
+                vec![OsString::from("-c"), OsString::from("exit 1")],
+                vec![],
+                -1,
+                false,
+                memlimit,
+                false,
+                false,
+                false,
+            )
+            .unwrap()
+            .init_failure(uses_asan)
+        };
+        assert!(matches!(
+            init_failure(true, 1024),
+            Error::ForkserverInit(ForkserverError::AsanMismatch)
+        ));
+        assert!(matches!(init_failure(false, 1024), Error::Forkserver(_)));
+        assert!(matches!(init_failure(true, 0), Error::Forkserver(_)));
+    }
 }
@@ -26,10 +26,7 @@ use std::intrinsics::transmute;
 use libc::{siginfo_t, ucontext_t};
 
 #[cfg(all(feature = "std", unix))]
-use nix::{
-    sys::wait::{waitpid, WaitStatus},
-    unistd::{fork, ForkResult},
-};
+use nix::unistd::{fork, ForkResult};
 
 #[cfg(unix)]
 use crate::bolts::os::unix_signals::setup_signal_handler;
@@ -37,6 +34,8 @@ use crate::bolts::os::unix_signals::setup_signal_handler;
 use crate::bolts::os::windows_exceptions::setup_exception_handler;
 #[cfg(all(feature = "std", unix))]
 use crate::bolts::shmem::ShMemProvider;
+#[cfg(all(feature = "std", unix))]
+use crate::executors::oom::{max_rss_mb, ChildRssWatcher};
 
 #[cfg(windows)]
 use windows::Win32::System::Threading::SetThreadStackGuarantee;
@@ -238,7 +237,7 @@ where
 pub struct InProcessHandlers {
     /// On crash C function pointer
     pub crash_handler: *const c_void,
-    /// On timeout C function pointer
+    /// On timeout C function pointer, also called on out-of-memory (`SIGUSR1` on unix)
     pub timeout_handler: *const c_void,
 }
 
@@ -518,7 +517,7 @@ mod unix_signal_handler {
         events::{Event, EventFirer, EventRestarter},
         executors::{
             inprocess::{InProcessExecutorHandlerData, GLOBAL_STATE},
-            Executor, ExitKind, HasObservers,
+            CrashSignalMetadata, Executor, ExitKind, HasObservers,
         },
         feedbacks::Feedback,
         fuzzer::HasObjective,
@@ -545,7 +544,7 @@ mod unix_signal_handler {
             unsafe {
                 let data = &mut GLOBAL_STATE;
                 match signal {
-                    Signal::SigUser2 | Signal::SigAlarm => {
+                    Signal::SigUser1 | Signal::SigUser2 | Signal::SigAlarm => {
                         if !data.timeout_handler.is_null() {
                             let func: HandlerFuncPtr = transmute(data.timeout_handler);
                             (func)(signal, info, context, data);
@@ -564,6 +563,7 @@ mod unix_signal_handler {
         fn signals(&self) -> Vec<Signal> {
             vec![
                 Signal::SigAlarm,
+                Signal::SigUser1,
                 Signal::SigUser2,
                 Signal::SigAbort,
                 Signal::SigBus,
@@ -648,9 +648,11 @@ mod unix_signal_handler {
         }));
     }
 
+    /// Timeout-Handler for in-process fuzzing, also reporting out-of-memory on `SIGUSR1`.
+    /// It will store the current State to shmem, then exit.
     #[cfg(unix)]
     pub unsafe fn inproc_timeout_handler<E, EM, I, OF, OT, S, Z>(
-        _signal: Signal,
+        signal: Signal,
         _info: siginfo_t,
         _context: &mut ucontext_t,
         data: &mut InProcessExecutorHandlerData,
@@ -677,23 +679,34 @@ mod unix_signal_handler {
 
         let input = data.take_current_input::<I>();
 
+        // `SIGUSR1` is raised by the memory limits, see `crate::executors::oom`
+        let exit_kind = if signal == Signal::SigUser1 {
+            ExitKind::Oom
+        } else {
+            ExitKind::Timeout
+        };
+
         #[cfg(feature = "std")]
-        println!("Timeout in fuzz run.");
+        if exit_kind == ExitKind::Oom {
+            println!("Out of memory in fuzz run.");
+        } else {
+            println!("Timeout in fuzz run.");
+        }
         #[cfg(feature = "std")]
         let _res = stdout().flush();
 
         observers
-            .post_exec_all(state, input, &ExitKind::Timeout)
+            .post_exec_all(state, input, &exit_kind)
             .expect("Observers post_exec_all failed");
 
         let interesting = fuzzer
             .objective_mut()
-            .is_interesting(state, event_mgr, input, observers, &ExitKind::Timeout)
+            .is_interesting(state, event_mgr, input, observers, &exit_kind)
             .expect("In timeout handler objective failure.");
 
         if interesting {
             let mut new_testcase = Testcase::new(input.clone());
-            new_testcase.add_metadata(ExitKind::Timeout);
+            new_testcase.add_metadata(exit_kind);
             fuzzer
                 .objective_mut()
                 .append_metadata(state, &mut new_testcase)
@@ -785,6 +798,9 @@ mod unix_signal_handler {
                 let new_input = input.clone();
                 let mut new_testcase = Testcase::new(new_input);
                 new_testcase.add_metadata(ExitKind::Crash);
+                new_testcase.add_metadata(CrashSignalMetadata {
+                    signal: signal.into(),
+                });
                 fuzzer
                     .objective_mut()
                     .append_metadata(state, &mut new_testcase)
@@ -1375,6 +1391,7 @@ impl Handler for InProcessForkExecutorGlobalData {
     fn signals(&self) -> Vec<Signal> {
         vec![
             Signal::SigAlarm,
+            Signal::SigUser1,
             Signal::SigUser2,
             Signal::SigAbort,
             Signal::SigBus,
@@ -1400,6 +1417,8 @@ where
     shmem_provider: SP,
     observers: OT,
     handlers: InChildProcessHandlers,
+    rss_limit_mb: usize,
+    rss_watcher: Option<ChildRssWatcher>,
     phantom: PhantomData<(I, S)>,
 }
 
@@ -1444,6 +1463,10 @@ where
                     // Child
                     self.shmem_provider.post_fork(true)?;
 
+                    if self.handlers.crash_handler.is_null() {
+                        // Without handlers, the `ChildRssWatcher` must stop the child, even if the parent handles `SIGUSR1`
+                        libc::signal(libc::SIGUSR1, libc::SIG_DFL);
+                    }
                     self.handlers.pre_run_target(self, state, input);
 
                     self.observers
@@ -1465,11 +1488,36 @@ where
                     // println!("from parent {} child is {}", std::process::id(), child);
                     self.shmem_provider.post_fork(false)?;
 
-                    let res = waitpid(child, None)?;
+                    if let Some(rss_watcher) = &self.rss_watcher {
+                        rss_watcher.watch(child.as_raw());
+                        // Wait without reaping, the pid of the exited child can't be reused until the watcher let it go
+                        let mut info: libc::siginfo_t = core::mem::zeroed();
+                        #[allow(clippy::cast_sign_loss)]
+                        libc::waitid(
+                            libc::P_PID,
+                            child.as_raw() as libc::id_t,
+                            &mut info,
+                            libc::WEXITED | libc::WNOWAIT,
+                        );
+                        rss_watcher.watch(0);
+                    }
+                    let mut status = 0;
+                    let mut usage: libc::rusage = core::mem::zeroed();
+                    let res = libc::wait4(child.as_raw(), &mut status, 0, &mut usage);
+                    if res < 0 {
+                        return Err(Error::from(std::io::Error::last_os_error()));
+                    }
 
-                    match res {
-                        WaitStatus::Signaled(_, _, _) => Ok(ExitKind::Crash),
-                        _ => Ok(ExitKind::Ok),
+                    if libc::WIFSIGNALED(status) {
+                        match libc::WTERMSIG(status) {
+                            // Raised by the memory limits, in the child or by the `ChildRssWatcher`
+                            libc::SIGUSR1 => Ok(ExitKind::Oom),
+                            _ => Ok(ExitKind::Crash),
+                        }
+                    } else if self.rss_limit_mb != 0 && max_rss_mb(&usage) > self.rss_limit_mb {
+                        Ok(ExitKind::Oom)
+                    } else {
+                        Ok(ExitKind::Ok)
                     }
                 }
                 Err(e) => Err(Error::from(e)),
@@ -1507,10 +1555,27 @@ where
             shmem_provider,
             observers,
             handlers,
+            rss_limit_mb: 0,
+            rss_watcher: None,
             phantom: PhantomData,
         })
     }
 
+    /// Limit the resident set size of the children, in MB, `0` for no limit.
+    /// A [`ChildRssWatcher`] stops a running child exceeding it, on `Linux` and `Android`.
+    /// Elsewhere, and for children exceeding it between two checks of the watcher,
+    /// the peak RSS is checked after the child exited. In both cases, it is reported as [`ExitKind::Oom`].
+    pub fn set_rss_limit_mb(&mut self, rss_limit_mb: usize) {
+        self.rss_limit_mb = rss_limit_mb;
+        match &self.rss_watcher {
+            Some(rss_watcher) => rss_watcher.set_rss_limit_mb(rss_limit_mb),
+            None if rss_limit_mb != 0 => {
+                self.rss_watcher = Some(ChildRssWatcher::new(rss_limit_mb));
+            }
+            None => (),
+        }
+    }
+
     /// Retrieve the harness function.
     #[inline]
     pub fn harness(&self) -> &H {
@@ -1585,14 +1650,15 @@ pub mod child_signal_handlers {
         }));
     }
 
-    /// invokes the `post_exec` hook on all observer in case the child process crashes
+    /// invokes the `post_exec` hook on all observer in case the child process crashes,
+    /// or runs out of memory (`SIGUSR1`), then dies by `SIGUSR1` for the parent to report it.
     ///
     /// # Safety
     /// The function should only be called from a child crash handler.
     /// It will dereference the `data` pointer and assume it's valid.
     #[cfg(unix)]
     pub unsafe fn child_crash_handler<E, I, OT, S>(
-        _signal: Signal,
+        signal: Signal,
         _info: siginfo_t,
         _context: &mut ucontext_t,
         data: &mut InProcessForkExecutorGlobalData,
@@ -1606,11 +1672,21 @@ pub mod child_signal_handlers {
             let observers = executor.observers_mut();
             let state = data.state_mut::<S>();
             let input = data.take_current_input::<I>();
+            let exit_kind = if signal == Signal::SigUser1 {
+                ExitKind::Oom
+            } else {
+                ExitKind::Crash
+            };
             observers
-                .post_exec_child_all(state, input, &ExitKind::Crash)
+                .post_exec_child_all(state, input, &exit_kind)
                 .expect("Failed to run post_exec on observers");
         }
 
+        if signal == Signal::SigUser1 {
+            libc::signal(libc::SIGUSR1, libc::SIG_DFL);
+            libc::raise(libc::SIGUSR1);
+        }
+
         //libc::_exit(128 + (_signal as i32));
     }
 }
@@ -1659,6 +1735,8 @@ mod tests {
             shmem_provider: provider,
             observers: tuple_list!(),
             handlers: InChildProcessHandlers::nop(),
+            rss_limit_mb: 0,
+            rss_watcher: None,
             phantom: PhantomData,
         };
         let input = NopInput {};
@@ -1666,6 +1744,52 @@ mod tests {
             .run_target(&mut (), &mut (), &mut (), &input)
             .is_ok());
     }
+
+    #[test]
+    #[cfg(all(feature = "std", feature = "fork", unix))]
+    fn test_inprocessfork_oom() {
+        use core::{hint::black_box, time::Duration};
+        use std::thread;
+
+        use crate::executors::inprocess::InChildProcessHandlers;
+
+        let provider = StdShMemProvider::new().unwrap();
+
+        let mut harness = |_buf: &NopInput| {
+            let buf = black_box(vec![1_u8; 256 << 20]);
+            thread::sleep(Duration::from_secs(1));
+            drop(buf);
+            ExitKind::Ok
+        };
+        let mut in_process_fork_executor = InProcessForkExecutor::<_, NopInput, (), (), _> {
+            harness_fn: &mut harness,
+            shmem_provider: provider,
+            observers: tuple_list!(),
+            handlers: InChildProcessHandlers::nop(),
+            rss_limit_mb: 0,
+            rss_watcher: None,
+            phantom: PhantomData,
+        };
+        let input = NopInput {};
+        assert_eq!(
+            in_process_fork_executor
+                .run_target(&mut (), &mut (), &mut (), &input)
+                .unwrap(),
+            ExitKind::Ok
+        );
+        in_process_fork_executor.set_rss_limit_mb(128);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let start = std::time::Instant::now();
+        assert_eq!(
+            in_process_fork_executor
+                .run_target(&mut (), &mut (), &mut (), &input)
+                .unwrap(),
+            ExitKind::Oom
+        );
+        // The child is stopped while it runs
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
 }
 
 #[cfg(feature = "python")]
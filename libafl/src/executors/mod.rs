@@ -29,6 +29,16 @@ pub use shadow::ShadowExecutor;
 pub mod with_observers;
 pub use with_observers::WithObservers;
 
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod snapshot;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use snapshot::{MemorySnapshot, SnapshotExecutor};
+
+#[cfg(all(feature = "std", unix))]
+pub mod oom;
+#[cfg(all(feature = "std", unix))]
+pub use oom::OomExecutor;
+
 #[cfg(all(feature = "std", unix))]
 pub mod command;
 #[cfg(all(feature = "std", unix))]
@@ -45,7 +55,7 @@ use core::fmt::Debug;
 use serde::{Deserialize, Serialize};
 
 /// How an execution finished.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ExitKind {
     /// The run exited normally.
     Ok,
@@ -67,7 +77,7 @@ pub enum ExitKind {
 }
 
 /// How one of the diffing executions finished.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum DiffExitKind {
     /// The run exited normally.
     Ok,
@@ -99,6 +109,15 @@ impl From<ExitKind> for DiffExitKind {
 
 crate::impl_serdeany!(DiffExitKind);
 
+/// The signal that crashed the target, added to solutions by the executors that know it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CrashSignalMetadata {
+    /// The number of the signal
+    pub signal: i32,
+}
+
+crate::impl_serdeany!(CrashSignalMetadata);
+
 /// Holds a tuple of Observers
 pub trait HasObservers<I, OT, S>: Debug
 where
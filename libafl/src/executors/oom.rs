@@ -0,0 +1,317 @@
+//! Out-of-memory detection, similar to the `-rss_limit_mb` and `-malloc_limit_mb` options of `libFuzzer`.
+//! The memory limits raise `SIGUSR1` in the fuzzed process, handled by the [`InProcessExecutor`]
+//! and the [`InProcessForkExecutor`] as out-of-memory, reporting [`ExitKind::Oom`].
+//!
+//! The [`OomExecutor`] checks the resident set size (RSS) of the process during the runs of an in-process executor.
+//! The [`InProcessForkExecutor`] limits the RSS of each child with a [`ChildRssWatcher`], see [`InProcessForkExecutor::set_rss_limit_mb`].
+//! For targets built with a sanitizer, `libafl_targets` also limits the size of single allocations.
+//! Keep the inputs running out of memory with an [`crate::feedbacks::OomFeedback`].
+//!
+//! [`InProcessExecutor`]: crate::executors::InProcessExecutor
+//! [`InProcessForkExecutor`]: crate::executors::InProcessForkExecutor
+//! [`InProcessForkExecutor::set_rss_limit_mb`]: crate::executors::InProcessForkExecutor::set_rss_limit_mb
+
+use alloc::sync::Arc;
+use core::{
+    fmt::{self, Debug, Formatter},
+    mem,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::fs;
+use std::{sync::Mutex, thread};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::Input,
+    observers::ObserversTuple,
+    Error,
+};
+
+/// The interval between two checks of the RSS by the [`OomExecutor`]
+const RSS_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The maximum resident set size of a [`libc::rusage`], in MB
+#[must_use]
+pub fn max_rss_mb(usage: &libc::rusage) -> usize {
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    let max_rss = usage.ru_maxrss as usize;
+    // In bytes on `MacOS`, in KB elsewhere
+    if cfg!(target_vendor = "apple") {
+        max_rss >> 20
+    } else {
+        max_rss >> 10
+    }
+}
+
+/// The peak resident set size of this process, in MB
+#[must_use]
+pub fn peak_rss_mb() -> usize {
+    let mut usage: libc::rusage = unsafe { mem::zeroed() };
+    unsafe {
+        libc::getrusage(libc::RUSAGE_SELF, &mut usage);
+    }
+    max_rss_mb(&usage)
+}
+
+/// The resident set size in a `statm` file of `procfs`, in MB
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn statm_rss_mb(path: &str) -> Option<usize> {
+    let pages = fs::read_to_string(path)
+        .ok()?
+        .split_whitespace()
+        .nth(1)?
+        .parse::<usize>()
+        .ok()?;
+    #[allow(clippy::cast_sign_loss)]
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    Some((pages * page_size) >> 20)
+}
+
+/// The current resident set size of this process, in MB, or the peak one if not available
+#[must_use]
+pub fn current_rss_mb() -> usize {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(rss) = statm_rss_mb("/proc/self/statm") {
+        return rss;
+    }
+    peak_rss_mb()
+}
+
+/// The current resident set size of the process `pid`, in MB
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[must_use]
+pub fn process_rss_mb(pid: libc::pid_t) -> Option<usize> {
+    statm_rss_mb(&format!("/proc/{}/statm", pid))
+}
+
+/// The current resident set size of the process `pid`, in MB, not available on this platform
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+#[must_use]
+pub fn process_rss_mb(_pid: libc::pid_t) -> Option<usize> {
+    None
+}
+
+/// If the sanitizer `report` is about running out of memory, i.e. an allocation failing under a memory limit
+#[must_use]
+pub fn is_oom_report(report: &str) -> bool {
+    [
+        "out of memory",
+        "out-of-memory",
+        "allocation-size-too-big",
+        "rss limit exhausted",
+    ]
+    .iter()
+    .any(|oom| report.contains(oom))
+}
+
+/// The oom executor is a wrapper that limits the resident set size (RSS) of the process during each run,
+/// like the `-rss_limit_mb` of `libFuzzer`.
+/// A thread checks the RSS periodically and raises `SIGUSR1` on the thread running the target
+/// if the limit is exceeded, reported as [`ExitKind::Oom`] by the wrapped [`crate::executors::InProcessExecutor`].
+pub struct OomExecutor<E> {
+    executor: E,
+    rss_limit_mb: Arc<AtomicUsize>,
+    /// The thread running the target, `0` between runs
+    running: Arc<AtomicUsize>,
+    stop: Arc<AtomicBool>,
+}
+
+impl<E> Debug for OomExecutor<E>
+where
+    E: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OomExecutor")
+            .field("executor", &self.executor)
+            .field("rss_limit_mb", &self.rss_limit_mb.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<E> OomExecutor<E> {
+    /// Create a new [`OomExecutor`], wrapping the given `executor` and limiting the RSS to `rss_limit_mb` MB.
+    /// It starts the thread checking the RSS.
+    pub fn new(executor: E, rss_limit_mb: usize) -> Self {
+        let rss_limit_mb = Arc::new(AtomicUsize::new(rss_limit_mb));
+        let running = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let (limit, thread, stopped) = (rss_limit_mb.clone(), running.clone(), stop.clone());
+        thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                thread::sleep(RSS_CHECK_INTERVAL);
+                let limit = limit.load(Ordering::Relaxed);
+                let thread = thread.load(Ordering::SeqCst);
+                if limit == 0 || thread == 0 {
+                    continue;
+                }
+                let rss = current_rss_mb();
+                if rss > limit {
+                    println!("RSS limit exceeded: {} MB > {} MB", rss, limit);
+                    unsafe {
+                        libc::pthread_kill(thread as libc::pthread_t, libc::SIGUSR1);
+                    }
+                }
+            }
+        });
+
+        Self {
+            executor,
+            rss_limit_mb,
+            running,
+            stop,
+        }
+    }
+
+    /// Set the RSS limit for the next runs, in MB, `0` for no limit
+    pub fn set_rss_limit_mb(&mut self, rss_limit_mb: usize) {
+        self.rss_limit_mb.store(rss_limit_mb, Ordering::Relaxed);
+    }
+
+    /// Retrieve the inner `Executor` that is wrapped by this `OomExecutor`.
+    pub fn inner(&mut self) -> &mut E {
+        &mut self.executor
+    }
+}
+
+impl<E> Drop for OomExecutor<E> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A thread limiting the resident set size (RSS) of the children of the [`crate::executors::InProcessForkExecutor`] while they run,
+/// sending `SIGUSR1` to a child exceeding the limit, which then reports [`ExitKind::Oom`].
+/// The RSS of other processes is only available on `Linux` and `Android`.
+#[derive(Debug)]
+pub struct ChildRssWatcher {
+    rss_limit_mb: Arc<AtomicUsize>,
+    /// The running child, `0` between runs, locked while it is signaled
+    child: Arc<Mutex<libc::pid_t>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ChildRssWatcher {
+    /// Create a new [`ChildRssWatcher`] limiting the RSS to `rss_limit_mb` MB, it starts the thread checking the RSS.
+    #[must_use]
+    pub fn new(rss_limit_mb: usize) -> Self {
+        let rss_limit_mb = Arc::new(AtomicUsize::new(rss_limit_mb));
+        let child = Arc::new(Mutex::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let (limit, watched, stopped) = (rss_limit_mb.clone(), child.clone(), stop.clone());
+        thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                thread::sleep(RSS_CHECK_INTERVAL);
+                let limit = limit.load(Ordering::Relaxed);
+                // Hold the lock until the signal is sent, so that the child is not reaped meanwhile
+                let pid = watched.lock().unwrap();
+                if limit == 0 || *pid == 0 {
+                    continue;
+                }
+                if let Some(rss) = process_rss_mb(*pid) {
+                    if rss > limit {
+                        println!("RSS limit exceeded: {} MB > {} MB", rss, limit);
+                        unsafe {
+                            libc::kill(*pid, libc::SIGUSR1);
+                        }
+                    }
+                }
+            }
+        });
+
+        Self {
+            rss_limit_mb,
+            child,
+            stop,
+        }
+    }
+
+    /// Set the RSS limit, in MB, `0` for no limit
+    pub fn set_rss_limit_mb(&self, rss_limit_mb: usize) {
+        self.rss_limit_mb.store(rss_limit_mb, Ordering::Relaxed);
+    }
+
+    /// Watch the running child `pid`, `0` once it exited.
+    /// Call it with `0` before reaping the child, its pid could be reused otherwise.
+    /// Once it returns, the child is not signaled anymore.
+    pub fn watch(&self, pid: libc::pid_t) {
+        *self.child.lock().unwrap() = pid;
+    }
+}
+
+impl Drop for ChildRssWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<E, EM, I, S, Z> Executor<EM, I, S, Z> for OomExecutor<E>
+where
+    E: Executor<EM, I, S, Z>,
+    I: Input,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        #[allow(clippy::cast_possible_truncation)]
+        let thread = unsafe { libc::pthread_self() } as usize;
+        self.running.store(thread, Ordering::SeqCst);
+        let ret = self.executor.run_target(fuzzer, state, mgr, input);
+        self.running.store(0, Ordering::SeqCst);
+        ret
+    }
+
+    fn post_run_reset(&mut self) {
+        self.executor.post_run_reset();
+    }
+}
+
+impl<E, I, OT, S> HasObservers<I, OT, S> for OomExecutor<E>
+where
+    E: HasObservers<I, OT, S>,
+    OT: ObserversTuple<I, S>,
+{
+    #[inline]
+    fn observers(&self) -> &OT {
+        self.executor.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut OT {
+        self.executor.observers_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executors::oom::{current_rss_mb, is_oom_report, peak_rss_mb};
+
+    #[test]
+    fn test_rss() {
+        let buf = vec![1_u8; 64 << 20];
+        assert!(current_rss_mb() >= 64);
+        // Both are sampled from approximate counters, compare the peak to the allocation only
+        assert!(peak_rss_mb() >= 64);
+        drop(buf);
+    }
+
+    #[test]
+    fn test_oom_report() {
+        assert!(is_oom_report(
+            "==1==ERROR: AddressSanitizer: out of memory: allocator is trying to allocate 0x100000000 bytes"
+        ));
+        assert!(is_oom_report(
+            "SUMMARY: AddressSanitizer: allocation-size-too-big (/out/fuzz+0x4a0b2d) in malloc"
+        ));
+        assert!(!is_oom_report(
+            "==1==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011"
+        ));
+    }
+}
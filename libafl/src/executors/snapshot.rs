@@ -0,0 +1,311 @@
+//! A snapshot executor for Linux userspace, restoring the memory changed by a run, similar to `Nyx`.
+//! The [`MemorySnapshot`] copies writable memory ranges, such as the `.data` and `.bss` of the target library,
+//! after the initialization of the harness. Before each run, the [`SnapshotExecutor`] restores the pages
+//! written since, found with the soft-dirty bits of `/proc/self/pagemap`.
+//! This gives an in-process executor the isolation of a fork for the global state of stateful libraries.
+//!
+//! Only the registered ranges are restored: memory allocated on the heap or mapped during a run is not.
+//! Do not register memory of the fuzzer itself, like its state, as it would be rolled back too.
+
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Debug, Formatter},
+    ptr,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    os::unix::fs::FileExt,
+};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::Input,
+    observers::ObserversTuple,
+    Error,
+};
+
+/// The soft-dirty bit of a `/proc/self/pagemap` entry
+const PM_SOFT_DIRTY: u64 = 1 << 55;
+
+/// A memory range of a [`MemorySnapshot`], with its content at the time of the snapshot
+#[derive(Debug)]
+struct SnapshotRange {
+    start: usize,
+    len: usize,
+    data: Vec<u8>,
+}
+
+/// A snapshot of writable memory ranges, restoring only the pages written since the snapshot,
+/// if the kernel supports soft-dirty bits (`CONFIG_MEM_SOFT_DIRTY`), else all of them.
+pub struct MemorySnapshot {
+    ranges: Vec<SnapshotRange>,
+    pagemap: File,
+    page_size: usize,
+    soft_dirty: bool,
+}
+
+impl Debug for MemorySnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemorySnapshot")
+            .field("ranges", &self.ranges.len())
+            .field("page_size", &self.page_size)
+            .field("soft_dirty", &self.soft_dirty)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MemorySnapshot {
+    /// Creates a new [`MemorySnapshot`], without ranges
+    pub fn new() -> Result<Self, Error> {
+        #[allow(clippy::cast_sign_loss)]
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        Ok(Self {
+            ranges: vec![],
+            pagemap: File::open("/proc/self/pagemap")?,
+            page_size,
+            soft_dirty: false,
+        })
+    }
+
+    /// Adds the writable range of `len` bytes at `start`, both aligned to the page size
+    pub fn add_range(&mut self, start: usize, len: usize) -> Result<(), Error> {
+        if start % self.page_size != 0 || len % self.page_size != 0 {
+            return Err(Error::IllegalArgument(format!(
+                "MemorySnapshot: range {:#x} of {:#x} bytes is not page aligned",
+                start, len
+            )));
+        }
+        self.ranges.push(SnapshotRange {
+            start,
+            len,
+            data: vec![],
+        });
+        Ok(())
+    }
+
+    /// Adds the private writable mappings of the modules whose path contains `name`, including their `.bss`.
+    /// Returns the number of added mappings.
+    pub fn add_module(&mut self, name: &str) -> Result<usize, Error> {
+        let maps = fs::read_to_string("/proc/self/maps")?;
+        let mut added = 0;
+        // The end of the last mapping of the module, the `.bss` may follow as anonymous mapping
+        let mut module_end = None;
+        for line in maps.lines() {
+            let mut fields = line.split_whitespace();
+            let (range, perms) = match (fields.next(), fields.next()) {
+                (Some(range), Some(perms)) => (range, perms),
+                _ => continue,
+            };
+            let path = fields.nth(3).unwrap_or("");
+            let (start, end) = match range.split_once('-') {
+                Some((start, end)) => (
+                    usize::from_str_radix(start, 16)?,
+                    usize::from_str_radix(end, 16)?,
+                ),
+                None => continue,
+            };
+            let writable = perms.starts_with("rw") && perms.ends_with('p');
+            if path.contains(name) && !name.is_empty() {
+                if writable {
+                    self.add_range(start, end - start)?;
+                    added += 1;
+                }
+                module_end = Some(end);
+            } else {
+                if path.is_empty() && writable && module_end == Some(start) {
+                    self.add_range(start, end - start)?;
+                    added += 1;
+                }
+                module_end = None;
+            }
+        }
+        Ok(added)
+    }
+
+    /// If the pages to restore are found with soft-dirty bits
+    #[must_use]
+    pub fn soft_dirty(&self) -> bool {
+        self.soft_dirty
+    }
+
+    /// Takes the snapshot of the ranges
+    pub fn take(&mut self) -> Result<(), Error> {
+        for range in &mut self.ranges {
+            range.data =
+                unsafe { core::slice::from_raw_parts(range.start as *const u8, range.len) }
+                    .to_vec();
+        }
+
+        // Probe the soft-dirty bit, writing a page with its own content
+        self.soft_dirty = false;
+        if let Some(range) = self.ranges.first() {
+            clear_soft_dirty()?;
+            unsafe {
+                let probe = range.start as *mut u8;
+                ptr::write_volatile(probe, ptr::read_volatile(probe));
+            }
+            self.soft_dirty = self.dirty_pages(range.start, 1)?[0];
+        }
+        clear_soft_dirty()
+    }
+
+    /// Restores the pages of the ranges written since the last snapshot or restore.
+    /// Returns the number of restored pages.
+    pub fn restore(&mut self) -> Result<usize, Error> {
+        let mut restored = 0;
+        for range in &self.ranges {
+            let pages = range.len / self.page_size;
+            let dirty = if self.soft_dirty {
+                self.dirty_pages(range.start, pages)?
+            } else {
+                vec![true; pages]
+            };
+            for (page, _) in dirty.iter().enumerate().filter(|(_, dirty)| **dirty) {
+                let offset = page * self.page_size;
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        range.data.as_ptr().add(offset),
+                        (range.start + offset) as *mut u8,
+                        self.page_size,
+                    );
+                }
+                restored += 1;
+            }
+        }
+        if self.soft_dirty {
+            clear_soft_dirty()?;
+        }
+        Ok(restored)
+    }
+
+    /// Reads the soft-dirty bits of `pages` pages at `start`
+    fn dirty_pages(&self, start: usize, pages: usize) -> Result<Vec<bool>, Error> {
+        let mut entries = vec![0; pages * 8];
+        self.pagemap
+            .read_exact_at(&mut entries, (start / self.page_size * 8) as u64)?;
+        Ok(entries
+            .chunks_exact(8)
+            .map(|entry| u64::from_ne_bytes(entry.try_into().unwrap()) & PM_SOFT_DIRTY != 0)
+            .collect())
+    }
+}
+
+/// Clears the soft-dirty bits of all pages of this process
+fn clear_soft_dirty() -> Result<(), Error> {
+    OpenOptions::new()
+        .write(true)
+        .open("/proc/self/clear_refs")?
+        .write_all(b"4")?;
+    Ok(())
+}
+
+/// An [`Executor`] wrapping an in-process executor, that restores a [`MemorySnapshot`] before each run.
+/// The coverage of a run is restored only before the next one, so the observers still see it.
+pub struct SnapshotExecutor<E> {
+    executor: E,
+    snapshot: MemorySnapshot,
+}
+
+impl<E> Debug for SnapshotExecutor<E>
+where
+    E: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotExecutor")
+            .field("executor", &self.executor)
+            .field("snapshot", &self.snapshot)
+            .finish()
+    }
+}
+
+impl<E> SnapshotExecutor<E> {
+    /// Creates a new [`SnapshotExecutor`], taking the `snapshot` now.
+    /// Create it after the initialization of the harness.
+    pub fn new(executor: E, mut snapshot: MemorySnapshot) -> Result<Self, Error> {
+        snapshot.take()?;
+        Ok(Self { executor, snapshot })
+    }
+
+    /// The wrapped executor
+    pub fn executor(&self) -> &E {
+        &self.executor
+    }
+
+    /// The wrapped executor, mutable
+    pub fn executor_mut(&mut self) -> &mut E {
+        &mut self.executor
+    }
+
+    /// The [`MemorySnapshot`]
+    pub fn snapshot(&self) -> &MemorySnapshot {
+        &self.snapshot
+    }
+}
+
+impl<E, EM, I, S, Z> Executor<EM, I, S, Z> for SnapshotExecutor<E>
+where
+    E: Executor<EM, I, S, Z>,
+    I: Input,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        self.snapshot.restore()?;
+        self.executor.run_target(fuzzer, state, mgr, input)
+    }
+
+    fn post_run_reset(&mut self) {
+        self.executor.post_run_reset();
+    }
+}
+
+impl<E, I, OT, S> HasObservers<I, OT, S> for SnapshotExecutor<E>
+where
+    E: HasObservers<I, OT, S>,
+    OT: ObserversTuple<I, S>,
+{
+    #[inline]
+    fn observers(&self) -> &OT {
+        self.executor.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut OT {
+        self.executor.observers_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executors::snapshot::MemorySnapshot;
+
+    #[test]
+    fn test_memory_snapshot() {
+        let mut snapshot = MemorySnapshot::new().unwrap();
+        let page_size = snapshot.page_size;
+        let mut buf = vec![0_u8; 4 * page_size];
+        let start = (buf.as_ptr() as usize + page_size - 1) & !(page_size - 1);
+        let offset = start - buf.as_ptr() as usize;
+        buf[offset] = 1;
+        buf[offset + page_size] = 2;
+
+        snapshot.add_range(start, 2 * page_size).unwrap();
+        assert!(snapshot.add_range(start + 1, page_size).is_err());
+        snapshot.take().unwrap();
+
+        buf[offset + page_size] = 3;
+        let restored = snapshot.restore().unwrap();
+        assert_eq!(buf[offset], 1);
+        assert_eq!(buf[offset + page_size], 2);
+        if snapshot.soft_dirty() {
+            assert_eq!(restored, 1);
+        } else {
+            assert_eq!(restored, 2);
+        }
+    }
+}
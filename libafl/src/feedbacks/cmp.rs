@@ -0,0 +1,292 @@
+//! The [`CmpFeedback`] treats comparisons that got closer to being satisfied as new coverage,
+//! based on the operands logged by a [`CmpObserver`], for example the `CmpLog` observer of `libafl_targets`.
+//! Inputs that progress toward magic values are kept, even without reaching new edges.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Debug, marker::PhantomData};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::{MatchName, Named},
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::{Feedback, FeedbackState},
+    inputs::Input,
+    observers::{CmpMap, CmpObserver, CmpValues, ObserversTuple},
+    state::{HasClientPerfMonitor, HasFeedbackStates},
+    Error,
+};
+
+/// How the [`CmpFeedback`] scores the operands of a comparison, the higher the closer
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmpScore {
+    /// The number of equal bits of the operands, as the `value_profile` of `libFuzzer`
+    MatchingBits,
+    /// The absolute distance of numerical operands.
+    /// Bytes operands are still scored by the length of their common prefix.
+    Distance,
+}
+
+impl CmpScore {
+    /// Score the logged operands of a comparison, the higher the closer
+    #[must_use]
+    pub fn score(self, values: &CmpValues) -> u64 {
+        match (self, values.distance()) {
+            (CmpScore::Distance, Some(distance)) => u64::MAX - distance,
+            _ => values.matching_bits() as u64,
+        }
+    }
+}
+
+/// The state of [`CmpFeedback`], the best score reached so far for each comparison
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CmpFeedbackState {
+    /// The best score for each comparison, `0` if never executed
+    pub history: Vec<u64>,
+    /// Name identifier of this instance
+    pub name: String,
+}
+
+impl FeedbackState for CmpFeedbackState {
+    fn reset(&mut self) -> Result<(), Error> {
+        self.history.clear();
+        Ok(())
+    }
+}
+
+impl Named for CmpFeedbackState {
+    #[inline]
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl CmpFeedbackState {
+    /// Create a new [`CmpFeedbackState`].
+    /// The [`CmpFeedback`] created from it looks it up by this name.
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            history: vec![],
+            name: name.to_string(),
+        }
+    }
+
+    /// Create a new [`CmpFeedbackState`] for the given [`CmpObserver`]
+    #[must_use]
+    pub fn with_observer<O>(observer: &O) -> Self
+    where
+        O: Named,
+    {
+        Self {
+            history: vec![],
+            name: observer.name().to_string(),
+        }
+    }
+
+    /// Update the best scores with the first `count` comparisons of the [`CmpMap`].
+    /// Returns `true` if any comparison got closer than ever before.
+    pub fn update<CM>(&mut self, map: &CM, count: usize, score: CmpScore) -> bool
+    where
+        CM: CmpMap,
+    {
+        if self.history.len() < count {
+            self.history.resize(count, 0);
+        }
+        let mut interesting = false;
+        for (i, best) in self.history.iter_mut().enumerate().take(count) {
+            for j in 0..map.usable_executions_for(i) {
+                let current = score.score(&map.values_of(i, j));
+                if current > *best {
+                    *best = current;
+                    interesting = true;
+                }
+            }
+        }
+        interesting
+    }
+}
+
+/// A [`CmpFeedback`] considers an input interesting if any comparison logged by the [`CmpObserver`]
+/// got closer to being satisfied than ever before, according to the [`CmpScore`].
+#[derive(Debug)]
+pub struct CmpFeedback<CM, O> {
+    name: String,
+    observer_name: String,
+    score: CmpScore,
+    phantom: PhantomData<(CM, O)>,
+}
+
+impl<CM, I, O, S> Feedback<I, S> for CmpFeedback<CM, O>
+where
+    CM: CmpMap,
+    I: Input,
+    O: CmpObserver<CM, I, S>,
+    S: HasClientPerfMonitor + HasFeedbackStates,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let observer = observers
+            .match_name::<O>(&self.observer_name)
+            .ok_or_else(|| {
+                Error::KeyNotFound(format!("Observer {} not found", self.observer_name))
+            })?;
+        let cmp_state = state
+            .feedback_states_mut()
+            .match_name_mut::<CmpFeedbackState>(&self.name)
+            .ok_or_else(|| {
+                Error::KeyNotFound(format!("CmpFeedbackState {} not found", self.name))
+            })?;
+        Ok(cmp_state.update(observer.cmp_map(), observer.usable_count(), self.score))
+    }
+}
+
+impl<CM, O> Named for CmpFeedback<CM, O> {
+    #[inline]
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl<CM, O> CmpFeedback<CM, O>
+where
+    CM: CmpMap,
+{
+    /// Create a new [`CmpFeedback`] for the given [`CmpObserver`].
+    /// It keeps its best scores in the given [`CmpFeedbackState`], which must be in the feedback states of the fuzzer.
+    #[must_use]
+    pub fn new(feedback_state: &CmpFeedbackState, observer: &O, score: CmpScore) -> Self
+    where
+        O: Named,
+    {
+        Self {
+            name: feedback_state.name().to_string(),
+            observer_name: observer.name().to_string(),
+            score,
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::InMemoryCorpus,
+        events::SimpleEventManager,
+        executors::ExitKind,
+        feedbacks::{CmpFeedback, CmpFeedbackState, CmpScore, Feedback},
+        inputs::BytesInput,
+        monitors::NopMonitor,
+        observers::{CmpMap, CmpObserver, CmpValues, StdCmpObserver},
+        state::StdState,
+        Error,
+    };
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct TestCmpMap(Vec<Vec<CmpValues>>);
+
+    impl CmpMap for TestCmpMap {
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn executions_for(&self, idx: usize) -> usize {
+            self.0[idx].len()
+        }
+
+        fn usable_executions_for(&self, idx: usize) -> usize {
+            self.0[idx].len()
+        }
+
+        fn values_of(&self, idx: usize, execution: usize) -> CmpValues {
+            self.0[idx][execution].clone()
+        }
+
+        fn reset(&mut self) -> Result<(), Error> {
+            self.0.clear();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cmp_feedback_state() {
+        let mut state = CmpFeedbackState::new("cmp");
+        let map = TestCmpMap(vec![vec![CmpValues::U32((0, 0x4141_4141))], vec![]]);
+        assert!(state.update(&map, 2, CmpScore::MatchingBits));
+        assert!(!state.update(&map, 2, CmpScore::MatchingBits));
+
+        let closer = TestCmpMap(vec![
+            vec![CmpValues::U32((0x4100_0000, 0x4141_4141))],
+            vec![],
+        ]);
+        assert!(state.update(&closer, 2, CmpScore::MatchingBits));
+        assert_eq!(state.history[0], 32 - 6);
+
+        let mut state = CmpFeedbackState::new("cmp");
+        let map = TestCmpMap(vec![vec![CmpValues::U8((10, 20)), CmpValues::U8((19, 20))]]);
+        assert!(state.update(&map, 1, CmpScore::Distance));
+        assert_eq!(state.history[0], u64::MAX - 1);
+        let further = TestCmpMap(vec![vec![CmpValues::U8((30, 20))]]);
+        assert!(!state.update(&further, 1, CmpScore::Distance));
+    }
+
+    #[test]
+    fn test_cmp_feedback() {
+        let mut map = TestCmpMap(vec![vec![CmpValues::U32((0, 0x4141_4141))]]);
+        let observer = StdCmpObserver::new("cmp", &mut map);
+        let feedback_state = CmpFeedbackState::with_observer(&observer);
+        let mut feedback = CmpFeedback::new(&feedback_state, &observer, CmpScore::MatchingBits);
+        let mut observers = tuple_list!(observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::<BytesInput>::new(),
+            tuple_list!(feedback_state),
+        );
+        let mut manager = SimpleEventManager::new(NopMonitor::new());
+        let input = BytesInput::new(vec![]);
+
+        assert!(feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        assert!(!feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+
+        // The operands got closer, without any new comparison
+        CmpObserver::<_, BytesInput, ()>::cmp_map_mut(&mut observers.0).0[0][0] =
+            CmpValues::U32((0x4100_0000, 0x4141_4141));
+        assert!(feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+
+        // A feedback whose state is missing fails instead of panicking
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::<BytesInput>::new(),
+            tuple_list!(CmpFeedbackState::new("other")),
+        );
+        assert!(feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .is_err());
+    }
+}
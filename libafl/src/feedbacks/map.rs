@@ -5,6 +5,7 @@ use alloc::{
     vec::Vec,
 };
 use core::{fmt::Debug, marker::PhantomData};
+use hashbrown::HashMap;
 use num_traits::PrimInt;
 use serde::{Deserialize, Serialize};
 
@@ -279,6 +280,10 @@ where
 {
     /// Contains information about untouched entries
     pub history_map: Vec<T>,
+    /// The reduced values of the entries seen so far, instead of the `history_map`, for a sparse state.
+    /// The other entries are `T::min_value()`.
+    #[serde(default)]
+    pub sparse_history_map: Option<HashMap<usize, T>>,
     /// Name identifier of this instance
     pub name: String,
 }
@@ -291,6 +296,9 @@ where
         self.history_map
             .iter_mut()
             .for_each(|x| *x = T::min_value());
+        if let Some(sparse_history_map) = self.sparse_history_map.as_mut() {
+            sparse_history_map.clear();
+        }
         Ok(())
     }
 }
@@ -314,6 +322,7 @@ where
     pub fn new(name: &'static str, map_size: usize) -> Self {
         Self {
             history_map: vec![T::min_value(); map_size],
+            sparse_history_map: None,
             name: name.to_string(),
         }
     }
@@ -326,22 +335,56 @@ where
     {
         Self {
             history_map: vec![T::min_value(); map_observer.len()],
+            sparse_history_map: None,
             name: map_observer.name().to_string(),
         }
     }
 
+    /// Create new sparse `MapFeedbackState`, only storing the entries seen so far.
+    /// The [`MapFeedback`] then only evaluates the entries touched by each run,
+    /// use it for huge maps, like the [`crate::observers::SparseMapObserver`].
+    #[must_use]
+    pub fn new_sparse(name: &'static str) -> Self {
+        Self {
+            history_map: vec![],
+            sparse_history_map: Some(HashMap::new()),
+            name: name.to_string(),
+        }
+    }
+
+    /// Create new sparse `MapFeedbackState` for the observer type, see [`MapFeedbackState::new_sparse`].
+    pub fn sparse_with_observer<O>(map_observer: &O) -> Self
+    where
+        O: MapObserver<Entry = T>,
+        T: Debug,
+    {
+        Self {
+            history_map: vec![],
+            sparse_history_map: Some(HashMap::new()),
+            name: map_observer.name().to_string(),
+        }
+    }
+
+    /// If this state only stores the entries seen so far
+    #[must_use]
+    pub fn is_sparse(&self) -> bool {
+        self.sparse_history_map.is_some()
+    }
+
     /// Create new `MapFeedbackState` using a name and a map.
     /// The map can be shared.
     #[must_use]
     pub fn with_history_map(name: &'static str, history_map: Vec<T>) -> Self {
         Self {
             history_map,
+            sparse_history_map: None,
             name: name.to_string(),
         }
     }
 }
 
-/// The most common AFL-like feedback type
+/// The most common AFL-like feedback type.
+/// With a sparse [`MapFeedbackState`], only the entries touched by each run are evaluated.
 #[derive(Clone, Debug)]
 pub struct MapFeedback<I, N, O, R, S, T>
 where
@@ -398,6 +441,51 @@ where
             .match_name_mut::<MapFeedbackState<T>>(&self.name)
             .unwrap();
 
+        if let Some(history_map) = map_state.sparse_history_map.as_mut() {
+            // Only the entries touched by this run can be novel
+            let indexes = &mut self.indexes;
+            let novelties = &mut self.novelties;
+            observer.for_each_touched(|i, item| {
+                if item == initial {
+                    return;
+                }
+                if let Some(indexes) = indexes.as_mut() {
+                    indexes.push(i);
+                }
+                let history = history_map.get(&i).copied().unwrap_or_else(T::min_value);
+                let reduced = R::reduce(history, item);
+                if N::is_novel(history, reduced) {
+                    history_map.insert(i, reduced);
+                    interesting = true;
+                    if let Some(novelties) = novelties.as_mut() {
+                        novelties.push(i);
+                    }
+                }
+            });
+
+            if interesting {
+                // The size of a sparse map is only an upper bound of its indexes, so report the count alone
+                let filled = history_map.values().filter(|x| **x != initial).count();
+                if let Some(indexes) = self.indexes.as_mut() {
+                    indexes.sort_unstable();
+                }
+                if let Some(novelties) = self.novelties.as_mut() {
+                    novelties.sort_unstable();
+                }
+                manager.fire(
+                    state,
+                    Event::UpdateUserStats {
+                        name: self.name.clone(),
+                        value: UserStats::Number(filled as u64),
+                        phantom: PhantomData,
+                    },
+                )?;
+            } else if let Some(indexes) = self.indexes.as_mut() {
+                indexes.clear();
+            }
+            return Ok(interesting);
+        }
+
         assert!(size <= map_state.history_map.len(), "The size of the associated map observer cannot exceed the size of the history map of the feedback. If you are running multiple instances of slightly different fuzzers (e.g. one with ASan and another without) synchronized using LLMP please check the `configuration` field of the LLMP manager.");
 
         assert!(size <= observer.len());
@@ -658,7 +746,20 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::feedbacks::{AllIsNovel, IsNovel, NextPow2IsNovel};
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{InMemoryCorpus, Testcase},
+        events::SimpleEventManager,
+        executors::ExitKind,
+        feedbacks::{
+            AllIsNovel, Feedback, IsNovel, MapFeedbackState, MapIndexesMetadata,
+            MapNoveltiesMetadata, MaxMapFeedback, NextPow2IsNovel,
+        },
+        inputs::BytesInput,
+        monitors::NopMonitor,
+        observers::{MapObserver, SparseMapObserver, StdMapObserver},
+        state::{HasMetadata, StdState},
+    };
 
     #[test]
     fn test_map_is_novel() {
@@ -679,6 +780,126 @@ mod tests {
         assert!(NextPow2IsNovel::is_novel(254_u8, 255));
         assert!(!NextPow2IsNovel::is_novel(255_u8, 255));
     }
+
+    #[test]
+    fn test_map_feedback() {
+        let mut map = [0_u8; 4];
+        let observer = StdMapObserver::new("map", &mut map);
+        let feedback_state = MapFeedbackState::with_observer(&observer);
+        let mut feedback = MaxMapFeedback::<BytesInput, _, _, _>::new_tracking(
+            &feedback_state,
+            &observer,
+            true,
+            true,
+        );
+        let mut observers = tuple_list!(observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::<BytesInput>::new(),
+            tuple_list!(feedback_state),
+        );
+        let mut manager = SimpleEventManager::new(NopMonitor::new());
+        let input = BytesInput::new(vec![]);
+
+        *observers.0.get_mut(1) = 2;
+        assert!(feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        let mut testcase = Testcase::new(input.clone());
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        assert_eq!(
+            testcase
+                .metadata()
+                .get::<MapIndexesMetadata>()
+                .unwrap()
+                .list,
+            vec![1]
+        );
+        assert_eq!(
+            testcase
+                .metadata()
+                .get::<MapNoveltiesMetadata>()
+                .unwrap()
+                .list,
+            vec![1]
+        );
+
+        // Nothing new, and a smaller value is not novel for the maximizing reducer
+        *observers.0.get_mut(1) = 1;
+        assert!(!feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        feedback.discard_metadata(&mut state, &input).unwrap();
+    }
+
+    #[test]
+    fn test_deserialize_dense_state() {
+        // A state serialized before sparse states existed
+        let state: MapFeedbackState<u8> =
+            serde_json::from_str(r#"{"history_map":[0,1],"name":"map"}"#).unwrap();
+        assert!(!state.is_sparse());
+        assert_eq!(state.history_map, [0, 1]);
+    }
+
+    #[test]
+    fn test_sparse_map_feedback() {
+        let observer = SparseMapObserver::<u8>::new_owned("sparse", usize::MAX);
+        let feedback_state = MapFeedbackState::sparse_with_observer(&observer);
+        assert!(feedback_state.is_sparse());
+        let mut feedback = MaxMapFeedback::<BytesInput, _, _, _>::new_tracking(
+            &feedback_state,
+            &observer,
+            true,
+            true,
+        );
+        let mut observers = tuple_list!(observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::<BytesInput>::new(),
+            tuple_list!(feedback_state),
+        );
+        let mut manager = SimpleEventManager::new(NopMonitor::new());
+        let input = BytesInput::new(vec![]);
+
+        *observers.0.get_mut(1 << 40) = 1;
+        *observers.0.get_mut(7) = 1;
+        assert!(feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        let mut testcase = Testcase::new(input.clone());
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        assert_eq!(
+            testcase
+                .metadata()
+                .get::<MapNoveltiesMetadata>()
+                .unwrap()
+                .list,
+            vec![7, 1 << 40]
+        );
+
+        // Only a new entry is novel, the untouched history is kept
+        observers.0.reset_map().unwrap();
+        *observers.0.get_mut(7) = 1;
+        assert!(!feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        *observers.0.get_mut(8) = 1;
+        assert!(feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        let mut testcase = Testcase::new(input);
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        assert_eq!(
+            testcase
+                .metadata()
+                .get::<MapIndexesMetadata>()
+                .unwrap()
+                .list,
+            vec![7, 8]
+        );
+    }
 }
 
 #[cfg(feature = "python")]
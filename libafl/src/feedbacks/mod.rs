@@ -4,6 +4,9 @@
 pub mod map;
 pub use map::*;
 
+pub mod cmp;
+pub use cmp::{CmpFeedback, CmpFeedbackState, CmpScore};
+
 pub mod differential;
 pub use differential::DiffFeedback;
 #[cfg(feature = "std")]
@@ -18,6 +21,14 @@ pub use new_hash_feedback::NewHashFeedback;
 #[cfg(feature = "std")]
 pub use new_hash_feedback::NewHashFeedbackState;
 
+#[cfg(feature = "std")]
+pub mod sanitizer;
+#[cfg(feature = "std")]
+pub use sanitizer::SanitizerReportFeedback;
+
+pub mod triage;
+pub use triage::{CrashTriageFeedback, CrashTriageFeedbackState};
+
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "nautilus")]
@@ -28,12 +39,12 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     bolts::tuples::{MatchName, Named},
-    corpus::Testcase,
+    corpus::{Corpus, StageOpMetadata, Testcase, TestcaseOriginMetadata},
     events::EventFirer,
     executors::ExitKind,
     inputs::Input,
     observers::{ListObserver, ObserversTuple, TimeObserver},
-    state::HasClientPerfMonitor,
+    state::{HasClientPerfMonitor, HasCorpus, HasMetadata},
     Error,
 };
 
@@ -116,6 +127,30 @@ where
     }
 }
 
+/// Creates a [`Feedback`] from the observers of a previous run,
+/// for example to check that later runs behave like this one did.
+pub trait FeedbackFactory<F, I, S, OT>
+where
+    F: Feedback<I, S>,
+    I: Input,
+    S: HasClientPerfMonitor,
+{
+    /// Create the [`Feedback`] for the given observers
+    fn create_feedback(&self, observers: &OT) -> F;
+}
+
+/// Any [`Feedback`] that does not depend on a previous run acts as its own factory
+impl<F, I, S, OT> FeedbackFactory<F, I, S, OT> for F
+where
+    F: Feedback<I, S> + Clone,
+    I: Input,
+    S: HasClientPerfMonitor,
+{
+    fn create_feedback(&self, _observers: &OT) -> F {
+        self.clone()
+    }
+}
+
 /// [`FeedbackState`] is the data associated with a [`Feedback`] that must persist as part
 /// of the fuzzer State
 pub trait FeedbackState: Named + Serialize + serde::de::DeserializeOwned + Debug {
@@ -838,6 +873,63 @@ impl Default for TimeoutFeedback {
     }
 }
 
+/// An [`OomFeedback`] reports as interesting if the target ran out of memory.
+/// Combined with a [`CrashFeedback`], the solutions are told apart by their [`ExitKind`] metadata.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OomFeedback {}
+
+impl<I, S> Feedback<I, S> for OomFeedback
+where
+    I: Input,
+    S: HasClientPerfMonitor,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        if let ExitKind::Oom = exit_kind {
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        testcase.add_metadata(ExitKind::Oom);
+        Ok(())
+    }
+}
+
+impl Named for OomFeedback {
+    #[inline]
+    fn name(&self) -> &str {
+        "OomFeedback"
+    }
+}
+
+impl OomFeedback {
+    /// Returns a new [`OomFeedback`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for OomFeedback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Nop feedback that annotates execution time in the new testcase, if any
 /// for this Feedback, the testcase is never interesting (use with an OR).
 /// It decides, if the given [`TimeObserver`] value of a run is interesting.
@@ -914,6 +1006,78 @@ impl TimeFeedback {
     }
 }
 
+/// Nop feedback that adds a [`TestcaseOriginMetadata`] to the new testcase, naming the corpus entry
+/// being fuzzed and the [`StageOpMetadata`] of the running stage, for example for `AFL++`-style filenames.
+/// For this Feedback, the testcase is never interesting (use with an OR).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OriginFeedback {}
+
+impl<I, S> Feedback<I, S> for OriginFeedback
+where
+    I: Input,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasMetadata,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        Ok(false)
+    }
+
+    /// Append the origin to the testcase, for new corpus entries and solutions alike
+    fn append_metadata(&mut self, state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        let parent_filename = match state.corpus().current() {
+            // The entry may still be borrowed if we crashed during a stage
+            Some(idx) => state
+                .corpus()
+                .get(*idx)?
+                .try_borrow()
+                .ok()
+                .and_then(|parent| parent.filename().clone()),
+            None => None,
+        };
+        let op = state
+            .metadata()
+            .get::<StageOpMetadata>()
+            .map(|meta| meta.op.clone());
+        testcase.add_metadata(TestcaseOriginMetadata {
+            parent_filename,
+            op,
+        });
+        Ok(())
+    }
+}
+
+impl Named for OriginFeedback {
+    #[inline]
+    fn name(&self) -> &str {
+        "OriginFeedback"
+    }
+}
+
+impl OriginFeedback {
+    /// Creates a new [`OriginFeedback`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for OriginFeedback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Consider interesting a testcase if the list in `ListObserver` is not empty.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ListFeedback<T>
@@ -0,0 +1,148 @@
+//! The [`SanitizerReportFeedback`] adds the [`SanitizerReport`] of a [`SanitizerReportObserver`] to the new testcases
+
+use alloc::string::{String, ToString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::Named,
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::Feedback,
+    inputs::Input,
+    observers::{ObserversTuple, SanitizerReport, SanitizerReportObserver},
+    state::{HasClientPerfMonitor, HasMetadata},
+    Error,
+};
+
+/// Nop feedback that annotates the [`SanitizerReport`] of a run in the new testcase, if any.
+/// For this Feedback, the testcase is never interesting (use with an OR, i.e. with the `CrashFeedback` of the objective).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SanitizerReportFeedback {
+    name: String,
+    report: Option<SanitizerReport>,
+}
+
+impl<I, S> Feedback<I, S> for SanitizerReportFeedback
+where
+    I: Input,
+    S: HasClientPerfMonitor,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        // TODO Replace with match_name_type when stable
+        let observer = observers
+            .match_name::<SanitizerReportObserver>(self.name())
+            .unwrap();
+        self.report = observer.report().clone();
+        Ok(false)
+    }
+
+    /// Append the sanitizer report to the testcase
+    #[inline]
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(report) = self.report.take() {
+            testcase.add_metadata(report);
+        }
+        Ok(())
+    }
+
+    /// Discard the stored report in case that the testcase is not added
+    #[inline]
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.report = None;
+        Ok(())
+    }
+}
+
+impl Named for SanitizerReportFeedback {
+    #[inline]
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl SanitizerReportFeedback {
+    /// Creates a new [`SanitizerReportFeedback`] for the [`SanitizerReportObserver`] with the given `name`.
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: name.to_string(),
+            report: None,
+        }
+    }
+
+    /// Creates a new [`SanitizerReportFeedback`] for the given [`SanitizerReportObserver`].
+    #[must_use]
+    pub fn new_with_observer(observer: &SanitizerReportObserver) -> Self {
+        Self {
+            name: observer.name().to_string(),
+            report: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{Feedback, SanitizerReportFeedback},
+        inputs::BytesInput,
+        observers::{SanitizerReport, SanitizerReportObserver},
+        state::{HasMetadata, StdState},
+    };
+
+    #[test]
+    fn test_sanitizer_report_feedback() {
+        let mut observer = SanitizerReportObserver::new("sanitizer");
+        let mut feedback = SanitizerReportFeedback::new_with_observer(&observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            (),
+        );
+        let mut mgr = NopEventManager {};
+        let input = BytesInput::new(vec![]);
+
+        observer.parse_output(
+            "==1==ERROR: AddressSanitizer: SEGV on unknown address 0x000000000000 (pc 0x4f4c2d bp 0x7ffd sp 0x7ffd T0)\n\
+            #0 0x4f4c2c in LLVMFuzzerTestOneInput /src/fuzz.c:12:5\n",
+        );
+        let observers = tuple_list!(observer);
+        // The report alone is never interesting
+        assert!(!feedback
+            .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Crash)
+            .unwrap());
+
+        let mut testcase: Testcase<BytesInput> = Testcase::new(input.clone());
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        let report = testcase.metadata().get::<SanitizerReport>().unwrap();
+        assert_eq!(report.bug_type, "SEGV");
+        assert_eq!(report.pc, Some(0x4f_4c2d));
+        assert_eq!(report.frames.len(), 1);
+
+        // A discarded report is not added to the next testcase
+        feedback
+            .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Crash)
+            .unwrap();
+        feedback.discard_metadata(&mut state, &input).unwrap();
+        let mut testcase: Testcase<BytesInput> = Testcase::new(input);
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        assert!(testcase.metadata().get::<SanitizerReport>().is_none());
+    }
+}
@@ -0,0 +1,355 @@
+//! The ``CrashTriageFeedback`` buckets solutions by exit kind, stack hash and faulting address,
+//! and only keeps one representative per bucket, counting the duplicates.
+
+use alloc::string::{String, ToString};
+use core::{
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use ahash::AHasher;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::{MatchName, Named},
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, FeedbackState},
+    inputs::Input,
+    monitors::UserStats,
+    observers::{ObserverWithHashField, ObserversTuple},
+    state::{HasClientPerfMonitor, HasFeedbackStates, HasMetadata},
+    Error,
+};
+
+/// A bucket of crashes sharing the same [`ExitKind`], stack hash and faulting address
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CrashBucket {
+    /// The [`ExitKind`] of the crashes in this bucket
+    pub exit_kind: ExitKind,
+    /// The stack hash, if the observer collected one
+    pub stack_hash: Option<u64>,
+    /// The faulting address, if the observer found one
+    pub fault_address: Option<u64>,
+    /// How often a crash of this bucket was hit
+    pub count: u64,
+}
+
+impl CrashBucket {
+    /// Compute the id of the bucket for the given crash properties
+    #[must_use]
+    pub fn id_for(exit_kind: ExitKind, stack_hash: Option<u64>, fault_address: Option<u64>) -> u64 {
+        let mut hasher = AHasher::new_with_keys(0, 0);
+        exit_kind.hash(&mut hasher);
+        stack_hash.hash(&mut hasher);
+        fault_address.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The id of this bucket
+    #[must_use]
+    pub fn id(&self) -> u64 {
+        Self::id_for(self.exit_kind, self.stack_hash, self.fault_address)
+    }
+}
+
+/// Metadata added by the [`CrashTriageFeedback`] to the representative of each [`CrashBucket`]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashBucketMetadata {
+    /// The id of the bucket, see [`CrashBucket::id`]
+    pub bucket_id: u64,
+    /// The [`ExitKind`] of the crash
+    pub exit_kind: ExitKind,
+    /// The stack hash, if the observer collected one
+    pub stack_hash: Option<u64>,
+    /// The faulting address, if the observer found one
+    pub fault_address: Option<u64>,
+}
+
+crate::impl_serdeany!(CrashBucketMetadata);
+
+/// The state of [`CrashTriageFeedback`], holding all crash buckets seen so far
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashTriageFeedbackState {
+    /// The crash buckets, by id
+    pub buckets: HashMap<u64, CrashBucket>,
+    /// Name identifier of this instance
+    pub name: String,
+}
+
+impl FeedbackState for CrashTriageFeedbackState {
+    fn reset(&mut self) -> Result<(), Error> {
+        self.buckets.clear();
+        Ok(())
+    }
+}
+
+impl Named for CrashTriageFeedbackState {
+    #[inline]
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl CrashTriageFeedbackState {
+    /// Create a new [`CrashTriageFeedbackState`]
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            buckets: HashMap::default(),
+            name: name.to_string(),
+        }
+    }
+
+    /// Create a new [`CrashTriageFeedbackState`] for an observer that implements [`ObserverWithHashField`]
+    pub fn with_observer(observer: &(impl ObserverWithHashField + Named)) -> Self {
+        Self {
+            buckets: HashMap::default(),
+            name: observer.name().to_string(),
+        }
+    }
+
+    /// Count a crash in its bucket, creating the bucket if needed.
+    /// Returns the id of the bucket, and if the bucket is new.
+    pub fn add_crash(
+        &mut self,
+        exit_kind: ExitKind,
+        stack_hash: Option<u64>,
+        fault_address: Option<u64>,
+    ) -> (u64, bool) {
+        let id = CrashBucket::id_for(exit_kind, stack_hash, fault_address);
+        let mut is_new = false;
+        let bucket = self.buckets.entry(id).or_insert_with(|| {
+            is_new = true;
+            CrashBucket {
+                exit_kind,
+                stack_hash,
+                fault_address,
+                count: 0,
+            }
+        });
+        bucket.count += 1;
+        (id, is_new)
+    }
+
+    /// The total amount of crashes, including duplicates
+    #[must_use]
+    pub fn crashes(&self) -> u64 {
+        self.buckets.values().map(|bucket| bucket.count).sum()
+    }
+}
+
+/// A [`CrashTriageFeedback`] sorts every run that did not exit with [`ExitKind::Ok`] into a [`CrashBucket`],
+/// using the hash and faulting address reported by an [`ObserverWithHashField`], such as the
+/// [`crate::observers::BacktraceObserver`] or the [`crate::observers::ASANBacktraceObserver`].
+/// Only the first crash of each bucket is considered interesting, later hits only increase the bucket's count.
+/// The number of buckets and of total crashes is reported to the monitor as user stats.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashTriageFeedback<O> {
+    feedback_name: String,
+    observer_name: String,
+    last_bucket: Option<CrashBucketMetadata>,
+    o_type: PhantomData<O>,
+}
+
+impl<I, S, O> Feedback<I, S> for CrashTriageFeedback<O>
+where
+    I: Input,
+    S: HasClientPerfMonitor + HasFeedbackStates,
+    O: ObserverWithHashField + Named + Debug,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        self.last_bucket = None;
+        if *exit_kind == ExitKind::Ok {
+            return Ok(false);
+        }
+
+        let observer = observers
+            .match_name::<O>(&self.observer_name)
+            .expect("A CrashTriageFeedback needs an ObserverWithHashField");
+        let stack_hash = *observer.hash();
+        let fault_address = observer.fault_address();
+
+        let triage_state = state
+            .feedback_states_mut()
+            .match_name_mut::<CrashTriageFeedbackState>(&self.observer_name)
+            .unwrap();
+        let (bucket_id, is_new) = triage_state.add_crash(*exit_kind, stack_hash, fault_address);
+        let buckets = triage_state.buckets.len() as u64;
+        let crashes = triage_state.crashes();
+
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: "crash buckets".to_string(),
+                value: UserStats::Number(buckets),
+                phantom: PhantomData,
+            },
+        )?;
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: "total crashes".to_string(),
+                value: UserStats::Number(crashes),
+                phantom: PhantomData,
+            },
+        )?;
+
+        if is_new {
+            self.last_bucket = Some(CrashBucketMetadata {
+                bucket_id,
+                exit_kind: *exit_kind,
+                stack_hash,
+                fault_address,
+            });
+        }
+        Ok(is_new)
+    }
+
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(metadata) = self.last_bucket.take() {
+            testcase.add_metadata(metadata);
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last_bucket = None;
+        Ok(())
+    }
+}
+
+impl<O> Named for CrashTriageFeedback<O> {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.feedback_name
+    }
+}
+
+impl<O> CrashTriageFeedback<O>
+where
+    O: ObserverWithHashField + Named + Debug,
+{
+    /// Returns a new [`CrashTriageFeedback`]. Carefull, it's recommended to use `new_with_observer`
+    /// Setting an observer name that doesn't exist would eventually trigger a panic.
+    #[must_use]
+    pub fn new(feedback_name: &str, observer_name: &str) -> Self {
+        Self {
+            feedback_name: feedback_name.to_string(),
+            observer_name: observer_name.to_string(),
+            last_bucket: None,
+            o_type: PhantomData,
+        }
+    }
+
+    /// Returns a new [`CrashTriageFeedback`].
+    #[must_use]
+    pub fn new_with_observer(feedback_name: &str, observer: &O) -> Self {
+        Self {
+            feedback_name: feedback_name.to_string(),
+            observer_name: observer.name().to_string(),
+            last_bucket: None,
+            o_type: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{
+            triage::{CrashBucketMetadata, CrashTriageFeedback, CrashTriageFeedbackState},
+            Feedback,
+        },
+        inputs::BytesInput,
+        observers::{ASANBacktraceObserver, ObserverWithHashField},
+        state::{HasMetadata, StdState},
+    };
+
+    #[test]
+    fn test_crash_buckets() {
+        let mut state = CrashTriageFeedbackState::new("triage");
+
+        let (first, is_new) = state.add_crash(ExitKind::Crash, Some(0x1337), Some(0x4000));
+        assert!(is_new);
+        let (second, is_new) = state.add_crash(ExitKind::Crash, Some(0x1337), Some(0x4000));
+        assert!(!is_new);
+        assert_eq!(first, second);
+
+        let (_, is_new) = state.add_crash(ExitKind::Timeout, Some(0x1337), Some(0x4000));
+        assert!(is_new);
+        let (_, is_new) = state.add_crash(ExitKind::Crash, Some(0x1337), None);
+        assert!(is_new);
+
+        assert_eq!(state.buckets.len(), 3);
+        assert_eq!(state.crashes(), 4);
+        assert_eq!(state.buckets[&first].count, 2);
+        assert_eq!(state.buckets[&first].id(), first);
+    }
+
+    #[test]
+    fn test_crash_triage_feedback() {
+        let mut observer = ASANBacktraceObserver::new("ASANBacktraceObserver");
+        let mut feedback = CrashTriageFeedback::new_with_observer("triage", &observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            tuple_list!(CrashTriageFeedbackState::with_observer(&observer)),
+        );
+        let mut mgr = NopEventManager {};
+        let input = BytesInput::new(vec![]);
+
+        let heap_overflow = "==1==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011 at pc 0x4f4b1d bp 0x7ffd sp 0x7ffd\n\
+            WRITE of size 1 at 0x602000000011 thread T0\n\
+            #0 0x4f4b1c in LLVMFuzzerTestOneInput /src/fuzz.c:10:5\n";
+        let segv = "==1==ERROR: AddressSanitizer: SEGV on unknown address 0x000000000000 (pc 0x4f4c2d bp 0x7ffd sp 0x7ffd T0)\n\
+            #0 0x4f4c2c in LLVMFuzzerTestOneInput /src/fuzz.c:12:5\n";
+
+        let mut is_interesting = |observer: &ASANBacktraceObserver, exit_kind| {
+            feedback
+                .is_interesting(
+                    &mut state,
+                    &mut mgr,
+                    &input,
+                    &tuple_list!(observer.clone()),
+                    &exit_kind,
+                )
+                .unwrap()
+        };
+
+        assert!(!is_interesting(&observer, ExitKind::Ok));
+        observer.parse_asan_output(heap_overflow);
+        assert_eq!(observer.fault_address(), Some(0x4f_4b1d));
+        assert!(is_interesting(&observer, ExitKind::Crash));
+        assert!(!is_interesting(&observer, ExitKind::Crash));
+        observer.parse_asan_output(segv);
+        assert_eq!(observer.fault_address(), Some(0x4f_4c2d));
+        assert!(is_interesting(&observer, ExitKind::Crash));
+
+        let mut testcase: Testcase<BytesInput> = Testcase::new(input.clone());
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        let metadata = testcase.metadata().get::<CrashBucketMetadata>().unwrap();
+        assert_eq!(metadata.fault_address, Some(0x4f_4c2d));
+        assert_eq!(metadata.exit_kind, ExitKind::Crash);
+    }
+}
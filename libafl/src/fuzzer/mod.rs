@@ -1,9 +1,12 @@
 //! The `Fuzzer` is the main struct for a fuzz campaign.
 
 use crate::{
-    bolts::current_time,
+    bolts::{current_time, serdeany::SerdeAnyMap},
     corpus::{Corpus, Testcase},
-    events::{Event, EventConfig, EventFirer, EventManager, ProgressReporter},
+    events::{
+        input_hash, Event, EventConfig, EventFirer, EventManager, PendingSharedMetadata,
+        ProgressReporter,
+    },
     executors::{Executor, ExitKind, HasObservers},
     feedbacks::Feedback,
     inputs::Input,
@@ -12,7 +15,7 @@ use crate::{
     schedulers::Scheduler,
     stages::StagesTuple,
     start_timer,
-    state::{HasClientPerfMonitor, HasCorpus, HasExecutions, HasSolutions},
+    state::{HasClientPerfMonitor, HasCorpus, HasExecutions, HasMetadata, HasSolutions},
     Error,
 };
 
@@ -221,6 +224,63 @@ where
     }
 }
 
+/// Collect the metadata of the testcase at `idx` that the `manager` wants to send along with [`Event::NewTestcase`].
+/// The testcase is marked to send the metadata again once the stages ran on it, see [`send_pending_metadata`].
+fn shared_testcase_metadata<EM, I, S>(
+    state: &S,
+    manager: &EM,
+    idx: usize,
+) -> Result<Option<SerdeAnyMap>, Error>
+where
+    EM: EventFirer<I>,
+    I: Input,
+    S: HasCorpus<I>,
+{
+    let shared = manager.shared_metadata();
+    if shared.is_empty() {
+        return Ok(None);
+    }
+    let mut testcase = state.corpus().get(idx)?.borrow_mut();
+    let mut metadata = testcase.metadata().clone();
+    metadata.retain(|type_id| shared.contains(type_id));
+    testcase.add_metadata(PendingSharedMetadata {});
+    Ok(Some(metadata))
+}
+
+/// Send the shared metadata of the testcase at `idx` with an [`Event::TestcaseMetadata`],
+/// if it was sent with [`Event::NewTestcase`] before the stages ran on it and added theirs.
+fn send_pending_metadata<EM, I, S>(state: &mut S, manager: &mut EM, idx: usize) -> Result<(), Error>
+where
+    EM: EventFirer<I>,
+    I: Input,
+    S: HasCorpus<I>,
+{
+    let (hash, metadata) = {
+        let mut testcase = state.corpus().get(idx)?.borrow_mut();
+        if testcase
+            .metadata_mut()
+            .remove::<PendingSharedMetadata>()
+            .is_none()
+        {
+            return Ok(());
+        }
+        let hash = input_hash(testcase.load_input()?)?;
+        let mut metadata = testcase.metadata().clone();
+        let shared = manager.shared_metadata();
+        metadata.retain(|type_id| shared.contains(type_id));
+        (hash, metadata)
+    };
+    manager.fire(
+        state,
+        Event::TestcaseMetadata {
+            input_hash: hash,
+            metadata,
+            client_config: manager.configuration(),
+            phantom: PhantomData,
+        },
+    )
+}
+
 /// The corpus this input should be added to
 #[derive(Debug, PartialEq)]
 pub enum ExecuteInputResult {
@@ -374,11 +434,13 @@ where
                     } else {
                         Some(manager.serialize_observers(observers)?)
                     };
+                    let metadata = shared_testcase_metadata(state, manager, idx)?;
                     manager.fire(
                         state,
                         Event::NewTestcase {
                             input,
                             observers_buf,
+                            metadata,
                             exit_kind: *exit_kind,
                             corpus_size: state.corpus().count(),
                             client_config: manager.configuration(),
@@ -395,6 +457,7 @@ where
 
                 // The input is a solution, add it to the respective corpus
                 let mut testcase = Testcase::with_executions(input, *state.executions());
+                testcase.add_metadata(*exit_kind);
                 self.objective_mut().append_metadata(state, &mut testcase)?;
                 state.solutions_mut().add(testcase)?;
 
@@ -492,11 +555,13 @@ where
         } else {
             Some(manager.serialize_observers(observers)?)
         };
+        let metadata = shared_testcase_metadata(state, manager, idx)?;
         manager.fire(
             state,
             Event::NewTestcase {
                 input,
                 observers_buf,
+                metadata,
                 exit_kind,
                 corpus_size: state.corpus().count(),
                 client_config: manager.configuration(),
@@ -514,7 +579,7 @@ where
     EM: EventManager<E, I, S, Self>,
     F: Feedback<I, S>,
     I: Input,
-    S: HasClientPerfMonitor + HasExecutions,
+    S: HasClientPerfMonitor + HasExecutions + HasCorpus<I>,
     OF: Feedback<I, S>,
     ST: StagesTuple<E, EM, S, Self>,
 {
@@ -543,6 +608,9 @@ where
         // Execute all stages
         stages.perform_all(self, executor, state, manager, idx)?;
 
+        // Share the metadata the stages added to the testcase
+        send_pending_metadata(state, manager, idx)?;
+
         // Init timer for manager
         #[cfg(feature = "introspection")]
         state.introspection_monitor_mut().start_timer();
@@ -863,3 +931,88 @@ pub mod pybind {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::any::TypeId;
+
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::{Event, EventConfig, EventFirer},
+        feedbacks::{MapIndexesMetadata, MapNoveltiesMetadata},
+        fuzzer::{send_pending_metadata, shared_testcase_metadata},
+        inputs::BytesInput,
+        state::{HasCorpus, HasMetadata, StdState},
+        Error,
+    };
+
+    /// Records the fired events, sharing the [`MapIndexesMetadata`]
+    struct RecordingEventManager {
+        shared: Vec<TypeId>,
+        events: Vec<Event<BytesInput>>,
+    }
+
+    impl EventFirer<BytesInput> for RecordingEventManager {
+        fn fire<S>(&mut self, _state: &mut S, event: Event<BytesInput>) -> Result<(), Error> {
+            self.events.push(event);
+            Ok(())
+        }
+
+        fn configuration(&self) -> EventConfig {
+            EventConfig::from_name("test")
+        }
+
+        fn shared_metadata(&self) -> &[TypeId] {
+            &self.shared
+        }
+    }
+
+    #[test]
+    fn test_send_pending_metadata() {
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            (),
+        );
+        let mut mgr = RecordingEventManager {
+            shared: vec![TypeId::of::<MapIndexesMetadata>()],
+            events: vec![],
+        };
+        let idx = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(vec![1, 2])))
+            .unwrap();
+
+        // Nothing to share yet when the testcase is added
+        let metadata = shared_testcase_metadata(&state, &mgr, idx).unwrap();
+        assert!(metadata.unwrap().is_empty());
+
+        // A stage adds metadata later
+        {
+            let mut testcase = state.corpus().get(idx).unwrap().borrow_mut();
+            testcase.add_metadata(MapIndexesMetadata::new(vec![3]));
+            testcase.add_metadata(MapNoveltiesMetadata::new(vec![4]));
+        }
+        send_pending_metadata(&mut state, &mut mgr, idx).unwrap();
+        assert_eq!(mgr.events.len(), 1);
+        match &mgr.events[0] {
+            Event::TestcaseMetadata {
+                input_hash: _,
+                metadata,
+                client_config: _,
+                phantom: _,
+            } => {
+                assert_eq!(metadata.len(), 1);
+                assert_eq!(metadata.get::<MapIndexesMetadata>().unwrap().list, [3]);
+            }
+            _ => panic!("expected TestcaseMetadata"),
+        }
+
+        // The metadata is only sent again once
+        send_pending_metadata(&mut state, &mut mgr, idx).unwrap();
+        assert_eq!(mgr.events.len(), 1);
+    }
+}
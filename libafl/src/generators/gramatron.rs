@@ -1,6 +1,7 @@
 //! Gramatron generator
-use alloc::{string::String, vec::Vec};
+use alloc::{collections::VecDeque, string::String, vec::Vec};
 use core::marker::PhantomData;
+use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -31,6 +32,66 @@ pub struct Automaton {
     pub pda: Vec<Vec<Trigger>>,
 }
 
+impl Automaton {
+    /// Parse raw bytes, for example an existing seed, into the shortest walk through this automaton
+    /// producing exactly these bytes. Returns `None` if the bytes are not part of the grammar.
+    /// This is a search over (state, offset) pairs, so it is quadratic in the worst case.
+    #[must_use]
+    pub fn parse(&self, bytes: &[u8]) -> Option<GramatronInput> {
+        // (state, offset) -> (previous state, previous offset, trigger index)
+        let mut parents: HashMap<(usize, usize), (usize, usize, usize)> = HashMap::new();
+        let mut queue = VecDeque::new();
+        let start = (self.init_state, 0);
+        queue.push_back(start);
+        parents.insert(start, start_marker());
+
+        while let Some((current, offset)) = queue.pop_front() {
+            if current == self.final_state {
+                if offset == bytes.len() {
+                    return Some(self.walk_to(&parents, (current, offset)));
+                }
+                continue;
+            }
+            for (idx, trigger) in self.pda.get(current)?.iter().enumerate() {
+                let term = trigger.term.as_bytes();
+                if bytes[offset..].starts_with(term) {
+                    let next = (trigger.dest, offset + term.len());
+                    if !parents.contains_key(&next) {
+                        parents.insert(next, (current, offset, idx));
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Reconstruct the terminals leading to `end`
+    fn walk_to(
+        &self,
+        parents: &HashMap<(usize, usize), (usize, usize, usize)>,
+        end: (usize, usize),
+    ) -> GramatronInput {
+        let mut terms = vec![];
+        let mut current = end;
+        while let Some(&(state, offset, idx)) = parents.get(&current) {
+            if (state, offset, idx) == start_marker() {
+                break;
+            }
+            let term = self.pda[state][idx].term.clone();
+            terms.push(Terminal::new(state, idx, term));
+            current = (state, offset);
+        }
+        terms.reverse();
+        GramatronInput::new(terms)
+    }
+}
+
+/// The parent of the initial (state, offset) pair while parsing
+fn start_marker() -> (usize, usize, usize) {
+    (usize::MAX, usize::MAX, usize::MAX)
+}
+
 #[derive(Clone, Debug)]
 /// Generates random inputs from a grammar automaton
 pub struct GramatronGenerator<'a, S>
@@ -97,3 +158,96 @@ where
         counter
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::InMemoryCorpus,
+        generators::gramatron::{Automaton, Trigger},
+        inputs::GramatronInput,
+        mutators::{GramatronSubwalkRemovalMutator, MutationResult, Mutator},
+        state::StdState,
+    };
+
+    /// S -> "a" S | "b"
+    fn test_automaton() -> Automaton {
+        Automaton {
+            init_state: 0,
+            final_state: 1,
+            pda: vec![
+                vec![
+                    Trigger {
+                        dest: 0,
+                        term: "a".into(),
+                    },
+                    Trigger {
+                        dest: 1,
+                        term: "b".into(),
+                    },
+                ],
+                vec![],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_automaton_parse() {
+        let automaton = test_automaton();
+        let input = automaton.parse(b"aab").unwrap();
+        let symbols: Vec<&str> = input
+            .terminals()
+            .iter()
+            .map(|t| t.symbol.as_str())
+            .collect();
+        assert_eq!(symbols, ["a", "a", "b"]);
+        assert_eq!(input.terminals()[2].trigger_idx, 1);
+
+        assert!(automaton.parse(b"aba").is_none());
+        assert!(automaton.parse(b"").is_none());
+    }
+
+    #[test]
+    fn test_subwalk_removal_mutator() {
+        let automaton = test_automaton();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<GramatronInput>::new(),
+            InMemoryCorpus::<GramatronInput>::new(),
+            (),
+        );
+        let mut mutator = GramatronSubwalkRemovalMutator::new();
+
+        let original = automaton.parse(b"aaaab").unwrap();
+        for _ in 0..20 {
+            let mut input = original.clone();
+            assert_eq!(
+                mutator.mutate(&mut state, &mut input, 0).unwrap(),
+                MutationResult::Mutated
+            );
+            // The removed sub-walk loops on the same state, so the input is still a walk of the automaton
+            let bytes: String = input
+                .terminals()
+                .iter()
+                .map(|t| t.symbol.as_str())
+                .collect();
+            assert!(bytes.len() < 5);
+            assert_eq!(automaton.parse(bytes.as_bytes()).unwrap(), input);
+        }
+
+        // No state is visited twice, nothing to remove
+        let mut input = automaton.parse(b"b").unwrap();
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input, 0).unwrap(),
+            MutationResult::Skipped
+        );
+        let symbols: Vec<&str> = input
+            .terminals()
+            .iter()
+            .map(|t| t.symbol.as_str())
+            .collect();
+        assert_eq!(symbols, ["b"]);
+    }
+}
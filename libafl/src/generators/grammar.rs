@@ -0,0 +1,1257 @@
+//! Context-free grammars loaded at runtime from `ANTLR` (`.g4`), `Gramatron` `JSON` or `Nautilus` rules.
+//! A [`Grammar`] can be turned into `Nautilus` rules, or into a `Gramatron` [`Automaton`],
+//! without the `Python` preprocessing scripts in `utils/gramatron`.
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::{String, ToString},
+    vec::Vec,
+};
+use hashbrown::{HashMap, HashSet};
+use std::{fs, path::Path};
+
+use crate::{
+    generators::gramatron::{Automaton, Trigger},
+    Error,
+};
+
+/// The maximum number of characters a character set like `[a-z]` may expand to
+const GRAMMAR_MAX_CHARSET: usize = 256;
+
+/// The maximum stack depth of the states of an [`Automaton`] built without stack limit
+const GRAMMAR_MAX_STACK: usize = 1024;
+
+/// A symbol on the right hand side of a [`Grammar`] rule
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GrammarSymbol {
+    /// A literal string, the empty string stands for epsilon
+    Terminal(String),
+    /// A reference to another rule
+    NonTerminal(String),
+}
+
+/// A context-free grammar, as a map from nonterminals to their alternatives
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Grammar {
+    start: String,
+    rules: BTreeMap<String, Vec<Vec<GrammarSymbol>>>,
+    /// The source line each nonterminal was defined at, for error reporting
+    lines: HashMap<String, usize>,
+}
+
+/// Format an error in the grammar source
+fn grammar_error(line: usize, msg: &str) -> Error {
+    Error::IllegalArgument(format!("grammar line {}: {}", line, msg))
+}
+
+impl Grammar {
+    /// Creates a new, empty [`Grammar`] deriving from the `start` nonterminal
+    #[must_use]
+    pub fn new(start: &str) -> Self {
+        Self {
+            start: start.to_string(),
+            rules: BTreeMap::new(),
+            lines: HashMap::new(),
+        }
+    }
+
+    /// The start nonterminal
+    #[must_use]
+    pub fn start(&self) -> &str {
+        &self.start
+    }
+
+    /// Sets the start nonterminal
+    pub fn set_start(&mut self, start: &str) {
+        self.start = start.to_string();
+    }
+
+    /// The rules, from each nonterminal to its alternatives
+    #[must_use]
+    pub fn rules(&self) -> &BTreeMap<String, Vec<Vec<GrammarSymbol>>> {
+        &self.rules
+    }
+
+    /// The source line the nonterminal was defined at, `0` if unknown
+    #[must_use]
+    pub fn line(&self, nonterm: &str) -> usize {
+        self.lines.get(nonterm).copied().unwrap_or(0)
+    }
+
+    /// Add an alternative for `nonterm`, defined at the source `line`
+    pub fn add_rule(&mut self, nonterm: &str, alternative: Vec<GrammarSymbol>, line: usize) {
+        self.lines.entry(nonterm.to_string()).or_insert(line);
+        self.rules
+            .entry(nonterm.to_string())
+            .or_default()
+            .push(alternative);
+    }
+
+    /// A nonterminal name starting with `base` that is not used yet
+    fn fresh(&self, base: &str) -> String {
+        let mut i = 1;
+        loop {
+            let name = format!("{}_{}", base, i);
+            if !self.rules.contains_key(&name) {
+                return name;
+            }
+            i += 1;
+        }
+    }
+
+    /// Add a helper nonterminal with the given alternatives, returning its name
+    fn add_helper(
+        &mut self,
+        base: &str,
+        alternatives: Vec<Vec<GrammarSymbol>>,
+        line: usize,
+    ) -> String {
+        let name = self.fresh(base);
+        self.lines.insert(name.clone(), line);
+        self.rules.insert(name.clone(), alternatives);
+        name
+    }
+
+    /// Parse an `ANTLR` v4 grammar (`.g4`).
+    /// Parser and lexer rules are supported, with `?`, `*`, `+`, groups, character sets and ranges.
+    /// Actions, predicates, labels and lexer commands are ignored.
+    /// The first rule is the start nonterminal.
+    pub fn from_antlr(source: &str) -> Result<Self, Error> {
+        let tokens = antlr_tokenize(source)?;
+        let mut parser = AntlrParser {
+            tokens,
+            pos: 0,
+            grammar: Self::default(),
+        };
+        parser.parse_file()?;
+        let grammar = parser.grammar;
+        grammar.validate()?;
+        Ok(grammar)
+    }
+
+    /// Parse an `ANTLR` v4 grammar file (`.g4`), see [`Grammar::from_antlr`]
+    pub fn from_antlr_file<P: AsRef<Path>>(grammar_file: P) -> Result<Self, Error> {
+        Self::from_antlr(&fs::read_to_string(grammar_file)?)
+    }
+
+    /// Parse a grammar in the `Gramatron` `JSON` format, mapping each nonterminal to a list of rules,
+    /// like `{"EXPR": ["EXPR '+' EXPR", "'1'"]}`, with terminals in single quotes.
+    pub fn from_json(source: &str, start: &str) -> Result<Self, Error> {
+        let map: BTreeMap<String, Vec<String>> = serde_json::from_str(source)
+            .map_err(|e| grammar_error(e.line(), &format!("invalid JSON grammar: {}", e)))?;
+        let mut grammar = Self::new(start);
+        for (nonterm, rules) in &map {
+            let line = json_key_line(source, nonterm);
+            for rule in rules {
+                let alternative = parse_json_rule(rule)
+                    .map_err(|msg| grammar_error(line, &format!("rule `{}`: {}", nonterm, msg)))?;
+                grammar.add_rule(nonterm, alternative, line);
+            }
+        }
+        grammar.validate()?;
+        Ok(grammar)
+    }
+
+    /// Parse a grammar file in the `Gramatron` `JSON` format, see [`Grammar::from_json`]
+    pub fn from_json_file<P: AsRef<Path>>(grammar_file: P, start: &str) -> Result<Self, Error> {
+        Self::from_json(&fs::read_to_string(grammar_file)?, start)
+    }
+
+    /// Creates a [`Grammar`] from `Nautilus` rules, pairs of a nonterminal and a format like `{EXPR}+{EXPR}`.
+    /// The first rule is the start nonterminal.
+    pub fn from_nautilus_rules(rules: &[Vec<String>]) -> Result<Self, Error> {
+        let start = rules
+            .first()
+            .and_then(|rule| rule.first())
+            .ok_or_else(|| Error::IllegalArgument("empty nautilus grammar".into()))?;
+        let mut grammar = Self::new(start);
+        for (idx, rule) in rules.iter().enumerate() {
+            if rule.len() != 2 {
+                return Err(grammar_error(
+                    idx + 1,
+                    "expected a nonterminal and a format",
+                ));
+            }
+            let alternative =
+                parse_nautilus_format(&rule[1]).map_err(|msg| grammar_error(idx + 1, &msg))?;
+            grammar.add_rule(&rule[0], alternative, idx + 1);
+        }
+        grammar.validate()?;
+        Ok(grammar)
+    }
+
+    /// Check that the start nonterminal and all referenced nonterminals are defined
+    pub fn validate(&self) -> Result<(), Error> {
+        if !self.rules.contains_key(&self.start) {
+            return Err(Error::IllegalArgument(format!(
+                "grammar start rule `{}` is not defined",
+                self.start
+            )));
+        }
+        for (nonterm, alternatives) in &self.rules {
+            for symbol in alternatives.iter().flatten() {
+                if let GrammarSymbol::NonTerminal(name) = symbol {
+                    if !self.rules.contains_key(name) {
+                        return Err(grammar_error(
+                            self.line(nonterm),
+                            &format!("rule `{}` references undefined rule `{}`", nonterm, name),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The rules in the `Nautilus` format, the start rule first.
+    /// Nonterminals are renamed to valid `Nautilus` names, braces in terminals are escaped.
+    #[must_use]
+    pub fn to_nautilus_rules(&self) -> Vec<Vec<String>> {
+        let mut order: Vec<&String> = self.rules.keys().filter(|nt| **nt != self.start).collect();
+        if let Some((start, _)) = self.rules.get_key_value(&self.start) {
+            order.insert(0, start);
+        }
+        let mut out = vec![];
+        for nonterm in order {
+            for alternative in &self.rules[nonterm] {
+                let mut format = String::new();
+                for symbol in alternative {
+                    match symbol {
+                        GrammarSymbol::Terminal(term) => {
+                            for c in term.chars() {
+                                if matches!(c, '{' | '}' | '\\') {
+                                    format.push('\\');
+                                }
+                                format.push(c);
+                            }
+                        }
+                        GrammarSymbol::NonTerminal(name) => {
+                            format.push('{');
+                            format.push_str(&nautilus_name(name));
+                            format.push('}');
+                        }
+                    }
+                }
+                out.push(vec![nautilus_name(nonterm), format]);
+            }
+        }
+        out
+    }
+
+    /// Only keep the rules reachable from the start nonterminal
+    fn reachable(&self) -> Self {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert(self.start.clone());
+        queue.push_back(self.start.clone());
+        while let Some(nonterm) = queue.pop_front() {
+            for symbol in self.rules.get(&nonterm).into_iter().flatten().flatten() {
+                if let GrammarSymbol::NonTerminal(name) = symbol {
+                    if seen.insert(name.clone()) {
+                        queue.push_back(name.clone());
+                    }
+                }
+            }
+        }
+        let mut grammar = self.clone();
+        grammar.rules.retain(|nonterm, _| seen.contains(nonterm));
+        grammar
+    }
+
+    /// Rewrite direct left recursion `A -> A x | y` to `A -> y A'`, `A' -> x A' | ''`
+    fn remove_left_recursion(&mut self) -> Result<(), Error> {
+        let nonterms: Vec<String> = self.rules.keys().cloned().collect();
+        for nonterm in nonterms {
+            let is_recursive = |alt: &Vec<GrammarSymbol>| {
+                alt.first() == Some(&GrammarSymbol::NonTerminal(nonterm.clone()))
+            };
+            let alternatives = &self.rules[&nonterm];
+            if !alternatives.iter().any(is_recursive) {
+                continue;
+            }
+            let (recursive, others): (Vec<_>, Vec<_>) =
+                alternatives.iter().cloned().partition(is_recursive);
+            let line = self.line(&nonterm);
+            if others.is_empty() {
+                return Err(grammar_error(
+                    line,
+                    &format!("rule `{}` never terminates", nonterm),
+                ));
+            }
+            let tail = self.add_helper(&nonterm, vec![], line);
+            let tail_symbol = GrammarSymbol::NonTerminal(tail.clone());
+            let mut tail_alternatives: Vec<Vec<GrammarSymbol>> = recursive
+                .into_iter()
+                .filter(|alt| alt.len() > 1)
+                .map(|mut alt| {
+                    alt.remove(0);
+                    alt.push(tail_symbol.clone());
+                    alt
+                })
+                .collect();
+            tail_alternatives.push(vec![GrammarSymbol::Terminal(String::new())]);
+            self.rules.insert(tail, tail_alternatives);
+            let alternatives = others
+                .into_iter()
+                .map(|mut alt| {
+                    alt.push(tail_symbol.clone());
+                    alt
+                })
+                .collect();
+            self.rules.insert(nonterm, alternatives);
+        }
+        Ok(())
+    }
+
+    /// Order the nonterminals so that every nonterminal comes after the ones its alternatives start with
+    fn leading_order(&self) -> Result<Vec<String>, Error> {
+        // 0: not visited, 1: in progress, 2: done
+        fn visit(
+            grammar: &Grammar,
+            nonterm: &str,
+            state: &mut HashMap<String, u8>,
+            order: &mut Vec<String>,
+        ) -> Result<(), Error> {
+            match state.get(nonterm) {
+                Some(2) => return Ok(()),
+                Some(1) => {
+                    return Err(grammar_error(
+                        grammar.line(nonterm),
+                        &format!("rule `{}` is indirectly left recursive", nonterm),
+                    ))
+                }
+                _ => (),
+            }
+            state.insert(nonterm.to_string(), 1);
+            for alternative in &grammar.rules[nonterm] {
+                if let Some(GrammarSymbol::NonTerminal(name)) = alternative.first() {
+                    visit(grammar, name, state, order)?;
+                }
+            }
+            state.insert(nonterm.to_string(), 2);
+            order.push(nonterm.to_string());
+            Ok(())
+        }
+
+        let mut state = HashMap::new();
+        let mut order = vec![];
+        for nonterm in self.rules.keys() {
+            visit(self, nonterm, &mut state, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Convert the grammar to `Greibach` normal form, as needed by the `Gramatron` [`Automaton`]:
+    /// every alternative is a terminal followed by nonterminals.
+    /// Unreachable rules are dropped, direct left recursion is removed.
+    pub fn to_gnf(&self) -> Result<Self, Error> {
+        self.validate()?;
+        let mut grammar = self.reachable();
+        for alternatives in grammar.rules.values_mut() {
+            for alternative in alternatives.iter_mut() {
+                if alternative.is_empty() {
+                    alternative.push(GrammarSymbol::Terminal(String::new()));
+                }
+            }
+        }
+        grammar.remove_left_recursion()?;
+
+        for nonterm in grammar.leading_order()? {
+            let mut alternatives = vec![];
+            for alternative in &grammar.rules[&nonterm] {
+                match alternative.first() {
+                    Some(GrammarSymbol::NonTerminal(name)) => {
+                        for leading in &grammar.rules[name] {
+                            let mut new = leading.clone();
+                            new.extend_from_slice(&alternative[1..]);
+                            alternatives.push(new);
+                        }
+                    }
+                    _ => alternatives.push(alternative.clone()),
+                }
+            }
+            grammar.rules.insert(nonterm, alternatives);
+        }
+
+        // Replace terminals that are not leading by a nonterminal producing them
+        let mut terminals: BTreeMap<String, String> = BTreeMap::new();
+        let nonterms: Vec<String> = grammar.rules.keys().cloned().collect();
+        for nonterm in nonterms {
+            let line = grammar.line(&nonterm);
+            let mut alternatives = grammar.rules.remove(&nonterm).unwrap();
+            for alternative in &mut alternatives {
+                for symbol in alternative.iter_mut().skip(1) {
+                    if let GrammarSymbol::Terminal(term) = symbol {
+                        let name = if let Some(name) = terminals.get(term) {
+                            name.clone()
+                        } else {
+                            let name = grammar.add_helper(
+                                "T",
+                                vec![vec![GrammarSymbol::Terminal(term.clone())]],
+                                line,
+                            );
+                            terminals.insert(term.clone(), name.clone());
+                            name
+                        };
+                        *symbol = GrammarSymbol::NonTerminal(name);
+                    }
+                }
+            }
+            alternatives.sort();
+            alternatives.dedup();
+            grammar.rules.insert(nonterm, alternatives);
+        }
+        Ok(grammar)
+    }
+}
+
+/// A valid `Nautilus` nonterminal name for `name`
+fn nautilus_name(name: &str) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !out.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        out.insert(0, 'N');
+    }
+    out
+}
+
+/// The line of the first occurrence of `"key"` in the `JSON` source
+fn json_key_line(source: &str, key: &str) -> usize {
+    let quoted = format!("\"{}\"", key);
+    source
+        .find(&quoted)
+        .map_or(0, |idx| source[..idx].matches('\n').count() + 1)
+}
+
+/// Parse a `Gramatron` `JSON` rule like `EXPR '+' EXPR`
+fn parse_json_rule(rule: &str) -> Result<Vec<GrammarSymbol>, String> {
+    let chars: Vec<char> = rule.chars().collect();
+    let mut symbols = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' {
+            let mut term = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err("unterminated terminal".into()),
+                    Some('\'') => break,
+                    Some('\\') if chars.get(i + 1) == Some(&'n') => {
+                        term.push('\n');
+                        i += 1;
+                    }
+                    Some('\\') if matches!(chars.get(i + 1), Some('\'' | '\\')) => {
+                        term.push(chars[i + 1]);
+                        i += 1;
+                    }
+                    Some(&c) => term.push(c),
+                }
+                i += 1;
+            }
+            i += 1;
+            symbols.push(GrammarSymbol::Terminal(term));
+        } else {
+            let start = i;
+            while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '\'' {
+                i += 1;
+            }
+            symbols.push(GrammarSymbol::NonTerminal(chars[start..i].iter().collect()));
+        }
+    }
+    Ok(symbols)
+}
+
+/// Parse a `Nautilus` format like `{EXPR}+{EXPR}`, with `\{` and `\}` for literal braces
+fn parse_nautilus_format(format: &str) -> Result<Vec<GrammarSymbol>, String> {
+    let mut symbols = vec![];
+    let mut term = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => term.push(chars.next().unwrap_or('\\')),
+            '{' => {
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                if name.is_empty() {
+                    return Err(format!("empty nonterminal in `{}`", format));
+                }
+                if !term.is_empty() {
+                    symbols.push(GrammarSymbol::Terminal(core::mem::take(&mut term)));
+                }
+                symbols.push(GrammarSymbol::NonTerminal(name));
+            }
+            c => term.push(c),
+        }
+    }
+    if !term.is_empty() || symbols.is_empty() {
+        symbols.push(GrammarSymbol::Terminal(term));
+    }
+    Ok(symbols)
+}
+
+/// A token of an `ANTLR` grammar
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum AntlrToken {
+    Ident(String),
+    Literal(String),
+    CharSet(Vec<char>),
+    /// `{...}`, the contents are ignored
+    Action,
+    Colon,
+    Semi,
+    Pipe,
+    LParen,
+    RParen,
+    Question,
+    Star,
+    Plus,
+    Dot,
+    Tilde,
+    Range,
+    Arrow,
+    Assign,
+    Hash,
+    At,
+    Lt,
+    Gt,
+    Other(char),
+}
+
+/// Read an escape sequence after a `\`, at `chars[*i]`
+fn antlr_escape(chars: &[char], i: &mut usize, line: usize) -> Result<char, Error> {
+    let c = *chars
+        .get(*i)
+        .ok_or_else(|| grammar_error(line, "unterminated escape sequence"))?;
+    *i += 1;
+    Ok(match c {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'b' => '\u{8}',
+        'f' => '\u{c}',
+        'u' => {
+            let hex: String = chars.iter().skip(*i).take(4).collect();
+            *i += 4;
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .and_then(core::char::from_u32)
+                .ok_or_else(|| {
+                    grammar_error(line, &format!("invalid unicode escape `\\u{}`", hex))
+                })?
+        }
+        c => c,
+    })
+}
+
+/// Split an `ANTLR` grammar into tokens, with their line
+#[allow(clippy::too_many_lines)]
+fn antlr_tokenize(source: &str) -> Result<Vec<(AntlrToken, usize)>, Error> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let token_line = line;
+        i += 1;
+        let token = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '/' if next == Some('/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if next == Some('*') => {
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(grammar_error(token_line, "unterminated comment")),
+                        Some('*') if chars.get(i + 1) == Some(&'/') => break,
+                        Some('\n') => line += 1,
+                        _ => (),
+                    }
+                    i += 1;
+                }
+                i += 2;
+                continue;
+            }
+            '\'' => {
+                let mut literal = String::new();
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => {
+                            return Err(grammar_error(token_line, "unterminated literal"))
+                        }
+                        Some('\'') => break,
+                        Some('\\') => {
+                            i += 1;
+                            literal.push(antlr_escape(&chars, &mut i, line)?);
+                        }
+                        Some(&c) => {
+                            literal.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                AntlrToken::Literal(literal)
+            }
+            '[' => {
+                let mut set: Vec<char> = vec![];
+                let mut prev: Option<char> = None;
+                loop {
+                    let mut c = match chars.get(i) {
+                        None => {
+                            return Err(grammar_error(token_line, "unterminated character set"))
+                        }
+                        Some(']') => break,
+                        Some(&c) => c,
+                    };
+                    i += 1;
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    if c == '\\' {
+                        c = antlr_escape(&chars, &mut i, line)?;
+                    } else if c == '-' && prev.is_some() {
+                        let mut end = match chars.get(i) {
+                            None => {
+                                return Err(grammar_error(token_line, "unterminated character set"))
+                            }
+                            Some(']') => {
+                                set.push(c);
+                                continue;
+                            }
+                            Some(&end) => end,
+                        };
+                        i += 1;
+                        if end == '\\' {
+                            end = antlr_escape(&chars, &mut i, line)?;
+                        }
+                        let first = prev.take().unwrap();
+                        if (end as u32).saturating_sub(first as u32) as usize > GRAMMAR_MAX_CHARSET
+                        {
+                            return Err(grammar_error(
+                                line,
+                                &format!("character range `{}-{}` is too big", first, end),
+                            ));
+                        }
+                        set.extend((first..=end).skip(1));
+                        continue;
+                    }
+                    set.push(c);
+                    prev = Some(c);
+                }
+                i += 1;
+                AntlrToken::CharSet(set)
+            }
+            '{' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.get(i) {
+                        None => return Err(grammar_error(token_line, "unterminated action")),
+                        Some('{') => depth += 1,
+                        Some('}') => depth -= 1,
+                        Some('\n') => line += 1,
+                        _ => (),
+                    }
+                    i += 1;
+                }
+                AntlrToken::Action
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i - 1;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                AntlrToken::Ident(chars[start..i].iter().collect())
+            }
+            '-' if next == Some('>') => {
+                i += 1;
+                AntlrToken::Arrow
+            }
+            '.' if next == Some('.') => {
+                i += 1;
+                AntlrToken::Range
+            }
+            '+' if next == Some('=') => {
+                i += 1;
+                AntlrToken::Assign
+            }
+            ':' if next == Some(':') => {
+                i += 1;
+                AntlrToken::Other(':')
+            }
+            ':' => AntlrToken::Colon,
+            ';' => AntlrToken::Semi,
+            '|' => AntlrToken::Pipe,
+            '(' => AntlrToken::LParen,
+            ')' => AntlrToken::RParen,
+            '?' => AntlrToken::Question,
+            '*' => AntlrToken::Star,
+            '+' => AntlrToken::Plus,
+            '.' => AntlrToken::Dot,
+            '~' => AntlrToken::Tilde,
+            '=' => AntlrToken::Assign,
+            '#' => AntlrToken::Hash,
+            '@' => AntlrToken::At,
+            '<' => AntlrToken::Lt,
+            '>' => AntlrToken::Gt,
+            c => AntlrToken::Other(c),
+        };
+        tokens.push((token, token_line));
+    }
+    Ok(tokens)
+}
+
+/// The characters matched by `.` and negated sets: printable `ASCII`, tab and newline
+fn antlr_any_chars() -> Vec<char> {
+    let mut chars: Vec<char> = (' '..='~').collect();
+    chars.push('\t');
+    chars.push('\n');
+    chars
+}
+
+/// A recursive descent parser for `ANTLR` grammars
+struct AntlrParser {
+    tokens: Vec<(AntlrToken, usize)>,
+    pos: usize,
+    grammar: Grammar,
+}
+
+impl AntlrParser {
+    fn peek(&self) -> Option<&AntlrToken> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(0, |(_, line)| *line)
+    }
+
+    fn next(&mut self) -> Option<AntlrToken> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    /// Skip tokens up to and including the next `;`
+    fn skip_statement(&mut self) {
+        while let Some(token) = self.next() {
+            if token == AntlrToken::Semi {
+                break;
+            }
+        }
+    }
+
+    fn parse_file(&mut self) -> Result<(), Error> {
+        while let Some(token) = self.next() {
+            let line = self.tokens[self.pos - 1].1;
+            match token {
+                AntlrToken::Ident(ident) => match ident.as_str() {
+                    "grammar" | "parser" | "lexer" | "import" | "mode" => self.skip_statement(),
+                    "options" | "tokens" | "channels" => {
+                        if self.next() != Some(AntlrToken::Action) {
+                            return Err(grammar_error(
+                                line,
+                                &format!("expected `{{` after `{}`", ident),
+                            ));
+                        }
+                    }
+                    "fragment" => match self.next() {
+                        Some(AntlrToken::Ident(name)) => self.parse_rule(&name, line)?,
+                        _ => {
+                            return Err(grammar_error(
+                                line,
+                                "expected a rule name after `fragment`",
+                            ))
+                        }
+                    },
+                    _ => self.parse_rule(&ident, line)?,
+                },
+                AntlrToken::At => {
+                    // Named actions like `@header {...}` or `@parser::members {...}`
+                    while let Some(token) = self.next() {
+                        if token == AntlrToken::Action {
+                            break;
+                        }
+                    }
+                }
+                token => {
+                    return Err(grammar_error(line, &format!("unexpected `{:?}`", token)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_rule(&mut self, name: &str, line: usize) -> Result<(), Error> {
+        // Skip arguments, `returns`, `locals` and rule actions up to the `:`
+        loop {
+            match self.next() {
+                Some(AntlrToken::Colon) => break,
+                None | Some(AntlrToken::Semi) => {
+                    return Err(grammar_error(
+                        line,
+                        &format!("expected `:` in rule `{}`", name),
+                    ))
+                }
+                _ => (),
+            }
+        }
+        if self.grammar.start.is_empty() {
+            self.grammar.start = name.to_string();
+        }
+        if self.grammar.rules.contains_key(name) {
+            return Err(grammar_error(
+                line,
+                &format!("rule `{}` is defined twice", name),
+            ));
+        }
+        self.grammar.lines.insert(name.to_string(), line);
+        let alternatives = self.parse_alternatives(name, line)?;
+        match self.next() {
+            Some(AntlrToken::Semi) => (),
+            _ => {
+                return Err(grammar_error(
+                    self.line(),
+                    &format!("expected `;` at the end of rule `{}`", name),
+                ))
+            }
+        }
+        // Exception handlers, `catch [...] {...}` and `finally {...}`
+        while let Some(AntlrToken::Ident(ident)) = self.peek() {
+            if ident != "catch" && ident != "finally" {
+                break;
+            }
+            while let Some(token) = self.next() {
+                if token == AntlrToken::Action {
+                    break;
+                }
+            }
+        }
+        self.grammar.rules.insert(name.to_string(), alternatives);
+        Ok(())
+    }
+
+    /// Parse alternatives separated by `|`, up to a `;` or `)`
+    fn parse_alternatives(
+        &mut self,
+        name: &str,
+        line: usize,
+    ) -> Result<Vec<Vec<GrammarSymbol>>, Error> {
+        let mut alternatives = vec![self.parse_sequence(name, line)?];
+        while self.peek() == Some(&AntlrToken::Pipe) {
+            self.pos += 1;
+            alternatives.push(self.parse_sequence(name, line)?);
+        }
+        Ok(alternatives)
+    }
+
+    fn parse_sequence(&mut self, name: &str, line: usize) -> Result<Vec<GrammarSymbol>, Error> {
+        let mut sequence = vec![];
+        loop {
+            let element_line = self.line();
+            let mut symbol = match self.peek() {
+                None => {
+                    return Err(grammar_error(
+                        element_line,
+                        &format!("unterminated rule `{}`", name),
+                    ))
+                }
+                Some(AntlrToken::Pipe | AntlrToken::Semi | AntlrToken::RParen) => break,
+                Some(AntlrToken::Hash) => {
+                    // Alternative label
+                    self.pos += 2;
+                    continue;
+                }
+                Some(AntlrToken::Arrow) => {
+                    // Lexer commands like `-> skip` or `-> channel(HIDDEN)`
+                    let mut depth = 0;
+                    while let Some(token) = self.peek() {
+                        match token {
+                            AntlrToken::LParen => depth += 1,
+                            AntlrToken::RParen if depth > 0 => depth -= 1,
+                            AntlrToken::Pipe | AntlrToken::Semi | AntlrToken::RParen => break,
+                            _ => (),
+                        }
+                        self.pos += 1;
+                    }
+                    continue;
+                }
+                Some(AntlrToken::Action) => {
+                    // Actions and semantic predicates
+                    self.pos += 1;
+                    if self.peek() == Some(&AntlrToken::Question) {
+                        self.pos += 1;
+                    }
+                    continue;
+                }
+                Some(AntlrToken::Lt) => {
+                    // Element options like `<assoc=right>`
+                    while let Some(token) = self.next() {
+                        if token == AntlrToken::Gt {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                Some(AntlrToken::Ident(_))
+                    if self.tokens.get(self.pos + 1).map(|(t, _)| t)
+                        == Some(&AntlrToken::Assign) =>
+                {
+                    // Element label like `x=expr` or `x+=expr`
+                    self.pos += 2;
+                    continue;
+                }
+                Some(_) => self.parse_atom(name, line)?,
+            };
+            symbol = match self.peek() {
+                Some(AntlrToken::Question) => {
+                    self.pos += 1;
+                    let alternatives =
+                        vec![vec![symbol], vec![GrammarSymbol::Terminal(String::new())]];
+                    GrammarSymbol::NonTerminal(self.grammar.add_helper(name, alternatives, line))
+                }
+                Some(AntlrToken::Star | AntlrToken::Plus) => {
+                    let plus = self.next() == Some(AntlrToken::Plus);
+                    let repeat = self.grammar.fresh(name);
+                    let tail = if plus {
+                        symbol.clone()
+                    } else {
+                        GrammarSymbol::Terminal(String::new())
+                    };
+                    let alternatives = vec![
+                        vec![symbol, GrammarSymbol::NonTerminal(repeat.clone())],
+                        vec![tail],
+                    ];
+                    self.grammar.lines.insert(repeat.clone(), line);
+                    self.grammar.rules.insert(repeat.clone(), alternatives);
+                    GrammarSymbol::NonTerminal(repeat)
+                }
+                _ => symbol,
+            };
+            // Non-greedy suffix
+            if self.peek() == Some(&AntlrToken::Question) {
+                self.pos += 1;
+            }
+            sequence.push(symbol);
+        }
+        Ok(sequence)
+    }
+
+    /// A helper nonterminal deriving each of `chars`
+    fn charset(
+        &mut self,
+        name: &str,
+        line: usize,
+        chars: Vec<char>,
+    ) -> Result<GrammarSymbol, Error> {
+        if chars.is_empty() {
+            return Err(grammar_error(line, "empty character set"));
+        }
+        let mut seen = HashSet::new();
+        let alternatives = chars
+            .into_iter()
+            .filter(|c| seen.insert(*c))
+            .map(|c| vec![GrammarSymbol::Terminal(c.to_string())])
+            .collect();
+        Ok(GrammarSymbol::NonTerminal(self.grammar.add_helper(
+            name,
+            alternatives,
+            line,
+        )))
+    }
+
+    /// The characters of a set, literal or range after a `~`
+    fn negated_chars(&mut self, name: &str, line: usize) -> Result<Vec<char>, Error> {
+        let chars = match self.next() {
+            Some(AntlrToken::CharSet(chars)) => chars,
+            Some(AntlrToken::Literal(literal)) => literal.chars().collect(),
+            Some(AntlrToken::LParen) => {
+                let mut chars = vec![];
+                loop {
+                    match self.next() {
+                        Some(AntlrToken::CharSet(set)) => chars.extend(set),
+                        Some(AntlrToken::Literal(literal)) => chars.extend(literal.chars()),
+                        Some(AntlrToken::Pipe) => (),
+                        Some(AntlrToken::RParen) => break,
+                        _ => {
+                            return Err(grammar_error(
+                                line,
+                                &format!("unsupported negated set in rule `{}`", name),
+                            ))
+                        }
+                    }
+                }
+                chars
+            }
+            _ => {
+                return Err(grammar_error(
+                    line,
+                    &format!("expected a set after `~` in rule `{}`", name),
+                ))
+            }
+        };
+        Ok(antlr_any_chars()
+            .into_iter()
+            .filter(|c| !chars.contains(c))
+            .collect())
+    }
+
+    fn parse_atom(&mut self, name: &str, line: usize) -> Result<GrammarSymbol, Error> {
+        let atom_line = self.line();
+        match self.next() {
+            Some(AntlrToken::Literal(literal)) => {
+                if self.peek() != Some(&AntlrToken::Range) {
+                    return Ok(GrammarSymbol::Terminal(literal));
+                }
+                self.pos += 1;
+                let end = match self.next() {
+                    Some(AntlrToken::Literal(end)) => end,
+                    _ => return Err(grammar_error(atom_line, "expected a literal after `..`")),
+                };
+                match (single_char(&literal), single_char(&end)) {
+                    (Some(first), Some(last))
+                        if (last as u32).saturating_sub(first as u32) as usize
+                            <= GRAMMAR_MAX_CHARSET =>
+                    {
+                        self.charset(name, line, (first..=last).collect())
+                    }
+                    _ => Err(grammar_error(
+                        atom_line,
+                        &format!("unsupported range `'{}'..'{}'`", literal, end),
+                    )),
+                }
+            }
+            Some(AntlrToken::Ident(ident)) if ident == "EOF" => {
+                Ok(GrammarSymbol::Terminal(String::new()))
+            }
+            Some(AntlrToken::Ident(ident)) => Ok(GrammarSymbol::NonTerminal(ident)),
+            Some(AntlrToken::CharSet(chars)) => self.charset(name, atom_line, chars),
+            Some(AntlrToken::Dot) => self.charset(name, atom_line, antlr_any_chars()),
+            Some(AntlrToken::Tilde) => {
+                let chars = self.negated_chars(name, atom_line)?;
+                self.charset(name, atom_line, chars)
+            }
+            Some(AntlrToken::LParen) => {
+                // Subrule options like `( options {greedy=false;} : ...)`
+                if self.peek() == Some(&AntlrToken::Ident("options".into())) {
+                    self.pos += 2;
+                    if self.next() != Some(AntlrToken::Colon) {
+                        return Err(grammar_error(
+                            atom_line,
+                            "expected `:` after subrule options",
+                        ));
+                    }
+                }
+                let mut alternatives = self.parse_alternatives(name, line)?;
+                if self.next() != Some(AntlrToken::RParen) {
+                    return Err(grammar_error(atom_line, "unbalanced `(`"));
+                }
+                if alternatives.len() == 1 && alternatives[0].len() == 1 {
+                    return Ok(alternatives.remove(0).remove(0));
+                }
+                Ok(GrammarSymbol::NonTerminal(self.grammar.add_helper(
+                    name,
+                    alternatives,
+                    line,
+                )))
+            }
+            token => Err(grammar_error(
+                atom_line,
+                &format!("unexpected `{:?}` in rule `{}`", token, name),
+            )),
+        }
+    }
+}
+
+/// The only character of `s`, if it has exactly one
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+impl Automaton {
+    /// Build the `Gramatron` [`Automaton`] for a [`Grammar`].
+    /// Each state stands for a stack of nonterminals still to derive, states with the same stack content are merged.
+    /// If `stack_limit` is not `0`, no state with a deeper stack is created, which bounds the size of the automaton.
+    /// Without stack limit, self-embedding grammars have infinitely many states, so building fails
+    /// once a stack gets deeper than `1024`.
+    pub fn from_grammar(grammar: &Grammar, stack_limit: usize) -> Result<Self, Error> {
+        let gnf = grammar.to_gnf()?;
+
+        let mut stacks: Vec<Vec<&str>> = vec![vec![gnf.start()]];
+        let mut known: HashMap<Vec<&str>, usize> = HashMap::new();
+        let mut pda: Vec<Vec<Trigger>> = vec![vec![]];
+        let mut worklist = VecDeque::new();
+        worklist.push_back(0);
+
+        while let Some(state) = worklist.pop_front() {
+            let stack = stacks[state].clone();
+            let (nonterm, rest) = match stack.split_first() {
+                Some(split) => split,
+                None => continue,
+            };
+            for alternative in &gnf.rules[*nonterm] {
+                let term = match &alternative[0] {
+                    GrammarSymbol::Terminal(term) => term.clone(),
+                    GrammarSymbol::NonTerminal(_) => unreachable!("grammar is in GNF"),
+                };
+                let mut new_stack: Vec<&str> = alternative[1..]
+                    .iter()
+                    .map(|symbol| match symbol {
+                        GrammarSymbol::NonTerminal(name) => name.as_str(),
+                        GrammarSymbol::Terminal(_) => unreachable!("grammar is in GNF"),
+                    })
+                    .collect();
+                new_stack.extend_from_slice(rest);
+                let mut sorted = new_stack.clone();
+                sorted.sort_unstable();
+                let dest = if let Some(dest) = known.get(&sorted) {
+                    *dest
+                } else {
+                    if stack_limit > 0 && new_stack.len() > stack_limit {
+                        continue;
+                    }
+                    if stack_limit == 0 && new_stack.len() > GRAMMAR_MAX_STACK {
+                        return Err(grammar_error(
+                            gnf.line(gnf.start()),
+                            "the grammar is self-embedding, set a stack limit",
+                        ));
+                    }
+                    let dest = stacks.len();
+                    stacks.push(new_stack);
+                    pda.push(vec![]);
+                    known.insert(sorted, dest);
+                    worklist.push_back(dest);
+                    dest
+                };
+                pda[state].push(Trigger { dest, term });
+            }
+        }
+
+        let final_state = stacks.iter().position(Vec::is_empty).ok_or_else(|| {
+            grammar_error(
+                gnf.line(gnf.start()),
+                "the automaton never reaches the final state, try a bigger stack limit",
+            )
+        })?;
+
+        // Drop the transitions to states that cannot reach the final state, so walks never get stuck
+        let mut useful = vec![false; pda.len()];
+        useful[final_state] = true;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (state, triggers) in pda.iter().enumerate() {
+                if !useful[state] && triggers.iter().any(|t| useful[t.dest]) {
+                    useful[state] = true;
+                    changed = true;
+                }
+            }
+        }
+        if !useful[0] {
+            return Err(grammar_error(
+                gnf.line(gnf.start()),
+                "the start rule cannot derive a finite string within the stack limit",
+            ));
+        }
+        for (state, triggers) in pda.iter_mut().enumerate() {
+            if useful[state] {
+                triggers.retain(|t| useful[t.dest]);
+            } else {
+                triggers.clear();
+            }
+        }
+
+        Ok(Self {
+            final_state,
+            init_state: 0,
+            pda,
+        })
+    }
+
+    /// Build the [`Automaton`] for an `ANTLR` v4 grammar file, see [`Automaton::from_grammar`]
+    pub fn from_antlr<P: AsRef<Path>>(grammar_file: P, stack_limit: usize) -> Result<Self, Error> {
+        Self::from_grammar(&Grammar::from_antlr_file(grammar_file)?, stack_limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generators::{
+        grammar::{Grammar, GrammarSymbol},
+        Automaton,
+    };
+
+    #[test]
+    fn test_antlr_automaton() {
+        let source = "grammar Calc;\n\
+            // a comment\n\
+            expr : expr ('+' | '-') term | term ;\n\
+            term : NUM | '(' expr ')' ;\n\
+            NUM : [0-9]+ ;\n\
+            WS : [ \\t]+ -> skip ;\n";
+        let grammar = Grammar::from_antlr(source).unwrap();
+        assert_eq!(grammar.start(), "expr");
+        assert_eq!(grammar.line("term"), 4);
+        assert_eq!(
+            grammar.rules()["term"][1][0],
+            GrammarSymbol::Terminal("(".into())
+        );
+
+        let automaton = Automaton::from_grammar(&grammar, 8).unwrap();
+        for input in ["1", "12+3", "(4-5)+67"] {
+            assert!(automaton.parse(input.as_bytes()).is_some(), "{}", input);
+        }
+        assert!(automaton.parse(b"1+").is_none());
+
+        let err = Grammar::from_antlr("a : b ;\nb : 'x' c ;\n").unwrap_err();
+        assert!(format!("{}", err).contains("line 2"));
+
+        let json = Grammar::from_json("{\n\"S\": [\"'a' S\", \"'b'\"]\n}", "S").unwrap();
+        let automaton = Automaton::from_grammar(&json, 0).unwrap();
+        assert!(automaton.parse(b"aab").is_some());
+    }
+
+    #[test]
+    fn test_antlr_errors() {
+        for source in ["a : [a- ;", "a : [a-", "a : 'x"] {
+            let err = Grammar::from_antlr(source).unwrap_err();
+            assert!(format!("{}", err).contains("unterminated"), "{}", source);
+        }
+        // A trailing `-` is literal
+        let grammar = Grammar::from_antlr("a : [a-] ;").unwrap();
+        let automaton = Automaton::from_grammar(&grammar, 4).unwrap();
+        assert!(automaton.parse(b"-").is_some());
+        assert!(automaton.parse(b"a").is_some());
+
+        // `S -> ( S )` is self-embedding, without stack limit the automaton is infinite
+        let grammar = Grammar::from_antlr("s : '(' s ')' | 'x' ;").unwrap();
+        assert!(Automaton::from_grammar(&grammar, 0).is_err());
+        let automaton = Automaton::from_grammar(&grammar, 4).unwrap();
+        assert!(automaton.parse(b"((x))").is_some());
+    }
+}
@@ -13,6 +13,11 @@ use crate::{
 pub mod gramatron;
 pub use gramatron::*;
 
+#[cfg(feature = "std")]
+pub mod grammar;
+#[cfg(feature = "std")]
+pub use grammar::{Grammar, GrammarSymbol};
+
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "nautilus")]
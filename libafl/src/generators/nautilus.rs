@@ -1,5 +1,9 @@
 //! Generators for the [`Nautilus`](https://github.com/RUB-SysSec/nautilus) grammar fuzzer
-use crate::{generators::Generator, inputs::nautilus::NautilusInput, Error};
+use crate::{
+    generators::{Generator, Grammar},
+    inputs::nautilus::NautilusInput,
+    Error,
+};
 use alloc::{string::String, vec::Vec};
 use core::fmt::Debug;
 use grammartec::context::Context;
@@ -44,6 +48,19 @@ impl NautilusContext {
             serde_json::from_reader(reader).expect("Cannot parse grammar file");
         Self::new(tree_depth, &rules)
     }
+
+    /// Create a new [`NautilusContext`] from a [`Grammar`]
+    #[must_use]
+    pub fn from_grammar(tree_depth: usize, grammar: &Grammar) -> Self {
+        Self::new(tree_depth, &grammar.to_nautilus_rules())
+    }
+
+    /// Create a new [`NautilusContext`] from an `ANTLR` v4 grammar file (`.g4`).
+    /// Errors in the grammar are reported with their line.
+    pub fn from_antlr<P: AsRef<Path>>(tree_depth: usize, grammar_file: P) -> Result<Self, Error> {
+        let grammar = Grammar::from_antlr_file(grammar_file)?;
+        Ok(Self::from_grammar(tree_depth, &grammar))
+    }
 }
 
 #[derive(Clone)]
@@ -12,6 +12,14 @@ pub use gramatron::*;
 pub mod generalized;
 pub use generalized::*;
 
+pub mod tlv;
+pub use tlv::{ChecksumKind, Endianness, IntFormat, IntWidth, TlvChunk, TlvInput, TlvRecordFormat};
+
+pub mod model;
+#[cfg(feature = "std")]
+pub use model::{BincodeCodec, JsonCodec};
+pub use model::{ModelCodec, ModelInput, ModelNode, PostcardCodec};
+
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "nautilus")]
@@ -0,0 +1,1212 @@
+//! The `ModelInput` wraps any `serde` data model, for example the request type of a service.
+//! Mutators work on its structure through [`ModelNode`] trees, and a pluggable [`ModelCodec`]
+//! turns the value into the bytes sent to the target.
+
+use ahash::AHasher;
+use alloc::{
+    boxed::Box,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    cell::RefCell,
+    convert::From,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+use serde::{
+    de::{
+        self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess,
+        SeqAccess, VariantAccess, Visitor,
+    },
+    ser, Deserialize, Serialize,
+};
+#[cfg(feature = "std")]
+use std::{fs::File, io::Read, path::Path};
+
+#[cfg(feature = "std")]
+use crate::bolts::fs::write_file_atomic;
+use crate::{
+    bolts::{ownedref::OwnedSlice, HasLen},
+    inputs::{HasTargetBytes, Input},
+    Error,
+};
+
+/// The maximum nesting of a [`ModelNode`] tree while deserializing, to stop recursive types
+/// from growing forever out of default values.
+pub const MODEL_MAX_DEPTH: usize = 64;
+
+/// A node of the `serde` data model of a value
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ModelNode {
+    /// A boolean
+    Bool(bool),
+    /// An unsigned integer of any width
+    Uint(u64),
+    /// A signed integer of any width
+    Int(i64),
+    /// A floating point number
+    Float(f64),
+    /// A character
+    Char(char),
+    /// A string
+    Str(String),
+    /// A byte buffer
+    Bytes(Vec<u8>),
+    /// An `Option` without value
+    None,
+    /// An `Option` with value
+    Some(Box<ModelNode>),
+    /// A unit. It is also the placeholder for missing values, which deserialize to their default.
+    Unit,
+    /// A sequence of variable length, like a `Vec`
+    Seq(Vec<ModelNode>),
+    /// A fixed number of fields, like a tuple or a struct
+    Tuple(Vec<ModelNode>),
+    /// A map
+    Map(Vec<(ModelNode, ModelNode)>),
+    /// An enum variant and its content
+    Variant {
+        /// The index of the variant, taken modulo the number of variants on deserialization
+        index: u32,
+        /// The content of the variant
+        value: Box<ModelNode>,
+    },
+}
+
+static UNIT: ModelNode = ModelNode::Unit;
+
+impl ModelNode {
+    /// Count the nodes in this tree, this one included, that match `pred`
+    pub fn count<P>(&self, pred: &P) -> usize
+    where
+        P: Fn(&ModelNode) -> bool,
+    {
+        let children = match self {
+            Self::Some(inner) | Self::Variant { value: inner, .. } => inner.count(pred),
+            Self::Seq(items) | Self::Tuple(items) => items.iter().map(|i| i.count(pred)).sum(),
+            Self::Map(entries) => entries
+                .iter()
+                .map(|(key, value)| key.count(pred) + value.count(pred))
+                .sum(),
+            _ => 0,
+        };
+        usize::from(pred(self)) + children
+    }
+
+    /// Get the `n`-th node in this tree, in depth-first order, that matches `pred`
+    pub fn nth_mut<P>(&mut self, n: &mut usize, pred: &P) -> Option<&mut ModelNode>
+    where
+        P: Fn(&ModelNode) -> bool,
+    {
+        if pred(self) {
+            if *n == 0 {
+                return Some(self);
+            }
+            *n -= 1;
+        }
+        match self {
+            Self::Some(inner) | Self::Variant { value: inner, .. } => inner.nth_mut(n, pred),
+            Self::Seq(items) | Self::Tuple(items) => {
+                items.iter_mut().find_map(|item| item.nth_mut(n, pred))
+            }
+            Self::Map(entries) => entries
+                .iter_mut()
+                .find_map(|(key, value)| key.nth_mut(n, pred).or_else(|| value.nth_mut(n, pred))),
+            _ => None,
+        }
+    }
+
+    fn as_u64(&self) -> u64 {
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        match self {
+            Self::Bool(b) => u64::from(*b),
+            Self::Uint(v) => *v,
+            Self::Int(v) => *v as u64,
+            Self::Float(v) => *v as u64,
+            Self::Char(c) => u64::from(*c),
+            _ => 0,
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        match self {
+            Self::Uint(v) => *v as f64,
+            Self::Int(v) => *v as f64,
+            Self::Float(v) => *v,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Get the [`ModelNode`] tree of a value
+pub fn to_model_node<T>(value: &T) -> Result<ModelNode, Error>
+where
+    T: Serialize + ?Sized,
+{
+    value
+        .serialize(NodeSerializer)
+        .map_err(|e| Error::Serialize(e.0))
+}
+
+/// Create a value from a [`ModelNode`] tree.
+/// Values that do not fit are coerced, missing values and unknown variants are replaced by defaults.
+pub fn from_model_node<T>(node: &ModelNode) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(NodeDeserializer { node, depth: 0 }).map_err(|e| Error::Serialize(e.0))
+}
+
+/// The error of the (de)serialization from and to [`ModelNode`]s
+#[derive(Debug)]
+struct ModelError(String);
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ser::StdError for ModelError {}
+
+impl ser::Error for ModelError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl de::Error for ModelError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+/// Serializes any value to a [`ModelNode`]
+struct NodeSerializer;
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = ModelNode;
+    type Error = ModelError;
+    type SerializeSeq = SeqBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = SeqBuilder;
+    type SerializeTupleVariant = SeqBuilder;
+    type SerializeMap = MapBuilder;
+    type SerializeStruct = SeqBuilder;
+    type SerializeStructVariant = SeqBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Uint(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Uint(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Uint(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Uint(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Str(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<ModelNode, ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Ok(ModelNode::Some(Box::new(value.serialize(self)?)))
+    }
+
+    fn serialize_unit(self) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+    ) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Variant {
+            index,
+            value: Box::new(ModelNode::Unit),
+        })
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<ModelNode, ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<ModelNode, ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Ok(ModelNode::Variant {
+            index,
+            value: Box::new(value.serialize(self)?),
+        })
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, ModelError> {
+        Ok(SeqBuilder::new(len.unwrap_or(0), false, None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, ModelError> {
+        Ok(SeqBuilder::new(len, true, None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, ModelError> {
+        Ok(SeqBuilder::new(len, true, None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, ModelError> {
+        Ok(SeqBuilder::new(len, true, Some(index)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapBuilder, ModelError> {
+        Ok(MapBuilder {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SeqBuilder, ModelError> {
+        Ok(SeqBuilder::new(len, true, None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, ModelError> {
+        Ok(SeqBuilder::new(len, true, Some(index)))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Builds sequences, tuples and structs, optionally inside an enum variant
+struct SeqBuilder {
+    items: Vec<ModelNode>,
+    fixed: bool,
+    variant: Option<u32>,
+}
+
+impl SeqBuilder {
+    fn new(len: usize, fixed: bool, variant: Option<u32>) -> Self {
+        Self {
+            items: Vec::with_capacity(len),
+            fixed,
+            variant,
+        }
+    }
+
+    fn push<T>(&mut self, value: &T) -> Result<(), ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.items.push(value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> ModelNode {
+        let node = if self.fixed {
+            ModelNode::Tuple(self.items)
+        } else {
+            ModelNode::Seq(self.items)
+        };
+        match self.variant {
+            Some(index) => ModelNode::Variant {
+                index,
+                value: Box::new(node),
+            },
+            None => node,
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqBuilder {
+    type Ok = ModelNode;
+    type Error = ModelError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ModelNode, ModelError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+    type Ok = ModelNode;
+    type Error = ModelError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ModelNode, ModelError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+    type Ok = ModelNode;
+    type Error = ModelError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ModelNode, ModelError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqBuilder {
+    type Ok = ModelNode;
+    type Error = ModelError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ModelNode, ModelError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for SeqBuilder {
+    type Ok = ModelNode;
+    type Error = ModelError;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<(), ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ModelNode, ModelError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for SeqBuilder {
+    type Ok = ModelNode;
+    type Error = ModelError;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<(), ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ModelNode, ModelError> {
+        Ok(self.finish())
+    }
+}
+
+/// Builds maps
+struct MapBuilder {
+    entries: Vec<(ModelNode, ModelNode)>,
+    key: Option<ModelNode>,
+}
+
+impl ser::SerializeMap for MapBuilder {
+    type Ok = ModelNode;
+    type Error = ModelError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key = Some(key.serialize(NodeSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), ModelError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self.key.take().unwrap_or(ModelNode::Unit);
+        self.entries.push((key, value.serialize(NodeSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<ModelNode, ModelError> {
+        Ok(ModelNode::Map(self.entries))
+    }
+}
+
+/// Deserializes any value from a [`ModelNode`], coercing whatever does not fit
+#[derive(Clone, Copy)]
+struct NodeDeserializer<'a> {
+    node: &'a ModelNode,
+    depth: usize,
+}
+
+impl<'a> NodeDeserializer<'a> {
+    fn child(self, node: &'a ModelNode) -> Result<Self, ModelError> {
+        if self.depth >= MODEL_MAX_DEPTH {
+            Err(ModelError("Maximum depth of the model reached".into()))
+        } else {
+            Ok(Self {
+                node,
+                depth: self.depth + 1,
+            })
+        }
+    }
+
+    fn fields(self, items: &'a [ModelNode], len: Option<usize>) -> FieldsAccess<'a> {
+        let (items, pad) = match len {
+            Some(len) if len > items.len() => (items, len - items.len()),
+            Some(len) => (&items[..len], 0),
+            None => (items, 0),
+        };
+        FieldsAccess {
+            parent: self,
+            iter: items.iter(),
+            pad,
+        }
+    }
+
+    fn items(self) -> &'a [ModelNode] {
+        match self.node {
+            ModelNode::Seq(items) | ModelNode::Tuple(items) => items,
+            _ => &[],
+        }
+    }
+}
+
+macro_rules! deserialize_int {
+    ($method:ident, $visit:ident, $ty:ty) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value, ModelError>
+        where
+            V: Visitor<'de>,
+        {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            visitor.$visit(self.node.as_u64() as $ty)
+        }
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for NodeDeserializer<'a> {
+    type Error = ModelError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.node {
+            ModelNode::Bool(v) => visitor.visit_bool(*v),
+            ModelNode::Uint(v) => visitor.visit_u64(*v),
+            ModelNode::Int(v) => visitor.visit_i64(*v),
+            ModelNode::Float(v) => visitor.visit_f64(*v),
+            ModelNode::Char(v) => visitor.visit_char(*v),
+            ModelNode::Str(v) => visitor.visit_str(v),
+            ModelNode::Bytes(v) => visitor.visit_bytes(v),
+            ModelNode::None => visitor.visit_none(),
+            ModelNode::Some(inner) => visitor.visit_some(self.child(inner)?),
+            ModelNode::Unit => visitor.visit_unit(),
+            ModelNode::Seq(items) | ModelNode::Tuple(items) => {
+                visitor.visit_seq(self.fields(items, None))
+            }
+            ModelNode::Map(entries) => visitor.visit_map(EntriesAccess {
+                parent: self,
+                iter: entries.iter(),
+                value: None,
+            }),
+            ModelNode::Variant { .. } => self.deserialize_enum("", &[], visitor),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_bool(self.node.as_u64() & 1 == 1)
+    }
+
+    deserialize_int!(deserialize_i8, visit_i8, i8);
+    deserialize_int!(deserialize_i16, visit_i16, i16);
+    deserialize_int!(deserialize_i32, visit_i32, i32);
+    deserialize_int!(deserialize_i64, visit_i64, i64);
+    deserialize_int!(deserialize_u8, visit_u8, u8);
+    deserialize_int!(deserialize_u16, visit_u16, u16);
+    deserialize_int!(deserialize_u32, visit_u32, u32);
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u64(self.node.as_u64())
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        #[allow(clippy::cast_possible_truncation)]
+        visitor.visit_f32(self.node.as_f64() as f32)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_f64(self.node.as_f64())
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        #[allow(clippy::cast_possible_truncation)]
+        let c = match self.node {
+            ModelNode::Char(c) => *c,
+            node => char::from_u32(node.as_u64() as u32).unwrap_or('\0'),
+        };
+        visitor.visit_char(c)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.node {
+            ModelNode::Str(s) => visitor.visit_str(s),
+            ModelNode::Bytes(b) => visitor.visit_str(&String::from_utf8_lossy(b)),
+            ModelNode::Char(c) => visitor.visit_str(c.encode_utf8(&mut [0; 4])),
+            _ => visitor.visit_str(""),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.node {
+            ModelNode::Bytes(b) => visitor.visit_bytes(b),
+            ModelNode::Str(s) => visitor.visit_bytes(s.as_bytes()),
+            #[allow(clippy::cast_possible_truncation)]
+            ModelNode::Seq(items) => {
+                let bytes: Vec<u8> = items.iter().map(|item| item.as_u64() as u8).collect();
+                visitor.visit_bytes(&bytes)
+            }
+            _ => visitor.visit_bytes(&[]),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.node {
+            ModelNode::None | ModelNode::Unit => visitor.visit_none(),
+            ModelNode::Some(inner) => visitor.visit_some(self.child(inner)?),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(self.fields(self.items(), None))
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(self.fields(self.items(), Some(len)))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        let entries: &[(ModelNode, ModelNode)] = match self.node {
+            ModelNode::Map(entries) => entries,
+            _ => &[],
+        };
+        visitor.visit_map(EntriesAccess {
+            parent: self,
+            iter: entries.iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        let (index, value) = match self.node {
+            ModelNode::Variant { index, value } => (*index, &**value),
+            _ => (0, &UNIT),
+        };
+        // An unknown number of variants happens for self-describing types only, keep the index
+        #[allow(clippy::cast_possible_truncation)]
+        let index = if variants.is_empty() {
+            index
+        } else {
+            index % variants.len() as u32
+        };
+        visitor.visit_enum(VariantDeserializer {
+            index,
+            value: self.child(value)?,
+        })
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.node {
+            ModelNode::Str(s) => visitor.visit_str(s),
+            node => visitor.visit_u64(node.as_u64()),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Gives access to the items of a sequence, padded with defaults up to the expected length
+struct FieldsAccess<'a> {
+    parent: NodeDeserializer<'a>,
+    iter: core::slice::Iter<'a, ModelNode>,
+    pad: usize,
+}
+
+impl<'de, 'a> SeqAccess<'de> for FieldsAccess<'a> {
+    type Error = ModelError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, ModelError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let node = match self.iter.next() {
+            Some(node) => node,
+            None if self.pad > 0 => {
+                self.pad -= 1;
+                &UNIT
+            }
+            None => return Ok(None),
+        };
+        seed.deserialize(self.parent.child(node)?).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len() + self.pad)
+    }
+}
+
+/// Gives access to the entries of a map
+struct EntriesAccess<'a> {
+    parent: NodeDeserializer<'a>,
+    iter: core::slice::Iter<'a, (ModelNode, ModelNode)>,
+    value: Option<&'a ModelNode>,
+}
+
+impl<'de, 'a> MapAccess<'de> for EntriesAccess<'a> {
+    type Error = ModelError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, ModelError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(self.parent.child(key)?).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, ModelError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let value = self.value.take().unwrap_or(&UNIT);
+        seed.deserialize(self.parent.child(value)?)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Gives access to an enum variant and its content
+struct VariantDeserializer<'a> {
+    index: u32,
+    value: NodeDeserializer<'a>,
+}
+
+impl<'de, 'a> EnumAccess<'de> for VariantDeserializer<'a> {
+    type Error = ModelError;
+    type Variant = NodeDeserializer<'a>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, NodeDeserializer<'a>), ModelError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(IntoDeserializer::<ModelError>::into_deserializer(
+            self.index,
+        ))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for NodeDeserializer<'a> {
+    type Error = ModelError;
+
+    fn unit_variant(self) -> Result<(), ModelError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, ModelError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ModelError>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
+
+/// Turns the value of a [`ModelInput`] into bytes for the target, and back
+pub trait ModelCodec {
+    /// Encode the value
+    fn encode<T>(value: &T) -> Result<Vec<u8>, Error>
+    where
+        T: Serialize;
+
+    /// Decode a value, for example from a seed file
+    fn decode<T>(bytes: &[u8]) -> Result<T, Error>
+    where
+        T: DeserializeOwned;
+}
+
+/// A [`ModelCodec`] using `postcard`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PostcardCodec;
+
+impl ModelCodec for PostcardCodec {
+    fn encode<T>(value: &T) -> Result<Vec<u8>, Error>
+    where
+        T: Serialize,
+    {
+        Ok(postcard::to_allocvec(value)?)
+    }
+
+    fn decode<T>(bytes: &[u8]) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(postcard::from_bytes(bytes)?)
+    }
+}
+
+/// A [`ModelCodec`] using `bincode`
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "std")]
+impl ModelCodec for BincodeCodec {
+    fn encode<T>(value: &T) -> Result<Vec<u8>, Error>
+    where
+        T: Serialize,
+    {
+        bincode::serialize(value).map_err(|e| Error::Serialize(format!("{:?}", e)))
+    }
+
+    fn decode<T>(bytes: &[u8]) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        bincode::deserialize(bytes).map_err(|e| Error::Serialize(format!("{:?}", e)))
+    }
+}
+
+/// A [`ModelCodec`] using `JSON`
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "std")]
+impl ModelCodec for JsonCodec {
+    fn encode<T>(value: &T) -> Result<Vec<u8>, Error>
+    where
+        T: Serialize,
+    {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn decode<T>(bytes: &[u8]) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// An input wrapping any `serde` data model `T`, sent to the target encoded with the [`ModelCodec`] `C`.
+/// Seed files are decoded with the codec as well.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound = "T: Serialize + DeserializeOwned")]
+pub struct ModelInput<T, C> {
+    /// The value
+    value: T,
+    phantom: PhantomData<C>,
+}
+
+impl<T, C> Input for ModelInput<T, C>
+where
+    T: Serialize + DeserializeOwned + Clone + Debug,
+    C: ModelCodec + Clone + Debug,
+{
+    #[cfg(feature = "std")]
+    /// Write this input to the file, encoded with the codec
+    fn to_file<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        write_file_atomic(path, &C::encode(&self.value)?)
+    }
+
+    /// Load the content of this input from a file, decoded with the codec
+    #[cfg(feature = "std")]
+    fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file = File::open(path)?;
+        let mut bytes: Vec<u8> = vec![];
+        file.read_to_end(&mut bytes)?;
+        Ok(Self::new(C::decode(&bytes)?))
+    }
+
+    /// Generate a name for this input
+    fn generate_name(&self, _idx: usize) -> String {
+        let mut hasher = AHasher::new_with_keys(0, 0);
+        self.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// The hash of the encoded value, as `T` may contain floats
+impl<T, C> Hash for ModelInput<T, C>
+where
+    T: Serialize,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        postcard::to_allocvec(&self.value)
+            .unwrap_or_default()
+            .hash(state);
+    }
+}
+
+/// Rc Ref-cell from Input
+impl<T, C> From<ModelInput<T, C>> for Rc<RefCell<ModelInput<T, C>>> {
+    fn from(input: ModelInput<T, C>) -> Self {
+        Rc::new(RefCell::new(input))
+    }
+}
+
+impl<T, C> HasTargetBytes for ModelInput<T, C>
+where
+    T: Serialize,
+    C: ModelCodec,
+{
+    /// The encoded value, empty if it cannot be encoded
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        OwnedSlice::from(C::encode(&self.value).unwrap_or_default())
+    }
+}
+
+impl<T, C> HasLen for ModelInput<T, C>
+where
+    T: Serialize,
+    C: ModelCodec,
+{
+    /// The length of the encoded value
+    fn len(&self) -> usize {
+        C::encode(&self.value).map_or(0, |bytes| bytes.len())
+    }
+}
+
+impl<T, C> ModelInput<T, C> {
+    /// Creates a new [`ModelInput`] wrapping the given value
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The wrapped value
+    #[must_use]
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// The wrapped value (mutable)
+    #[must_use]
+    pub fn value_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+    use serde::{Deserialize, Serialize};
+
+    use crate::inputs::model::{from_model_node, to_model_node, ModelNode};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Command {
+        Ping,
+        Echo(String),
+        Move { x: i16, y: i16 },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Request {
+        id: u32,
+        commands: Vec<Command>,
+        token: Option<u64>,
+    }
+
+    #[test]
+    fn test_model_roundtrip() {
+        let request = Request {
+            id: 7,
+            commands: vec![Command::Echo("hi".into()), Command::Move { x: -1, y: 2 }],
+            token: None,
+        };
+        let mut node = to_model_node(&request).unwrap();
+        assert_eq!(from_model_node::<Request>(&node).unwrap(), request);
+
+        // Switch the first command to the `Move` variant, its fields become defaults
+        if let ModelNode::Tuple(fields) = &mut node {
+            if let ModelNode::Seq(commands) = &mut fields[1] {
+                commands[0] = ModelNode::Variant {
+                    index: 5,
+                    value: ModelNode::Unit.into(),
+                };
+            }
+        }
+        let mutated = from_model_node::<Request>(&node).unwrap();
+        assert_eq!(mutated.commands[0], Command::Move { x: 0, y: 0 });
+
+        // Missing fields become defaults as well
+        if let ModelNode::Tuple(fields) = &mut node {
+            fields.truncate(1);
+        }
+        let mutated = from_model_node::<Request>(&node).unwrap();
+        assert_eq!(
+            mutated,
+            Request {
+                id: 7,
+                commands: vec![],
+                token: None,
+            }
+        );
+    }
+}
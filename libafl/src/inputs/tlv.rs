@@ -0,0 +1,533 @@
+//! The `TlvInput` models length-prefixed and type-length-value binary formats as a tree of typed chunks.
+//! Length and checksum fields are recomputed every time the input is serialized, so mutations never break them.
+
+use ahash::AHasher;
+use alloc::{rc::Rc, string::String, vec::Vec};
+use core::hash::Hasher;
+use core::{cell::RefCell, convert::From};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{ownedref::OwnedSlice, HasLen},
+    inputs::{HasTargetBytes, Input},
+    Error,
+};
+
+/// The width of an integer field
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IntWidth {
+    /// One byte
+    U8,
+    /// Two bytes
+    U16,
+    /// Four bytes
+    U32,
+}
+
+impl IntWidth {
+    /// The number of bytes of this width
+    #[must_use]
+    pub fn bytes(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::U32 => 4,
+        }
+    }
+
+    /// The biggest value fitting this width
+    #[must_use]
+    pub fn max_value(self) -> u32 {
+        match self {
+            Self::U8 => u32::from(u8::MAX),
+            Self::U16 => u32::from(u16::MAX),
+            Self::U32 => u32::MAX,
+        }
+    }
+}
+
+/// The byte order of an integer field
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    /// Least significant byte first
+    Little,
+    /// Most significant byte first (network byte order)
+    Big,
+}
+
+/// The layout of an integer field
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IntFormat {
+    /// The width of the field
+    pub width: IntWidth,
+    /// The byte order of the field
+    pub endianness: Endianness,
+}
+
+impl IntFormat {
+    /// Creates a new [`IntFormat`]
+    #[must_use]
+    pub fn new(width: IntWidth, endianness: Endianness) -> Self {
+        Self { width, endianness }
+    }
+
+    /// Append `value`, truncated to the width of this format, to `out`
+    pub fn write(self, value: u32, out: &mut Vec<u8>) {
+        let bytes = match self.endianness {
+            Endianness::Little => value.to_le_bytes(),
+            Endianness::Big => value.to_be_bytes(),
+        };
+        let len = self.width.bytes();
+        match self.endianness {
+            Endianness::Little => out.extend_from_slice(&bytes[..len]),
+            Endianness::Big => out.extend_from_slice(&bytes[4 - len..]),
+        }
+    }
+
+    /// Read a value of this format from the start of `bytes`, if there are enough bytes
+    #[must_use]
+    pub fn read(self, bytes: &[u8]) -> Option<u32> {
+        let bytes = bytes.get(..self.width.bytes())?;
+        let mut value = 0_u32;
+        match self.endianness {
+            Endianness::Little => {
+                for &b in bytes.iter().rev() {
+                    value = (value << 8) | u32::from(b);
+                }
+            }
+            Endianness::Big => {
+                for &b in bytes {
+                    value = (value << 8) | u32::from(b);
+                }
+            }
+        }
+        Some(value)
+    }
+}
+
+/// The algorithm of a checksum field
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChecksumKind {
+    /// The wrapping sum of all bytes
+    Sum,
+    /// All bytes xored together
+    Xor,
+    /// The `CRC-32` (`IEEE 802.3`) of the bytes
+    Crc32,
+}
+
+impl ChecksumKind {
+    /// Compute the checksum of `bytes`
+    #[must_use]
+    pub fn compute(self, bytes: &[u8]) -> u32 {
+        match self {
+            Self::Sum => bytes
+                .iter()
+                .fold(0_u32, |acc, &b| acc.wrapping_add(u32::from(b))),
+            Self::Xor => bytes.iter().fold(0_u32, |acc, &b| acc ^ u32::from(b)),
+            Self::Crc32 => {
+                let mut crc = u32::MAX;
+                for &b in bytes {
+                    crc ^= u32::from(b);
+                    for _ in 0..8 {
+                        let mask = (crc & 1).wrapping_neg();
+                        crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+                    }
+                }
+                !crc
+            }
+        }
+    }
+}
+
+/// A chunk of a [`TlvInput`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TlvChunk {
+    /// An integer, for example the type of a `TLV` record
+    Int {
+        /// The layout of the integer
+        format: IntFormat,
+        /// The value, truncated to the width of the format on serialization
+        value: u32,
+    },
+    /// Raw bytes
+    Bytes(Vec<u8>),
+    /// A group of chunks, serialized one after the other
+    Node(Vec<TlvChunk>),
+    /// The length in bytes of the children, followed by the children
+    LengthOf {
+        /// The layout of the length field
+        format: IntFormat,
+        /// The chunks whose length is written
+        children: Vec<TlvChunk>,
+    },
+    /// The checksum of the children, before or after the children
+    ChecksumOf {
+        /// The layout of the checksum field
+        format: IntFormat,
+        /// The checksum algorithm
+        kind: ChecksumKind,
+        /// If the checksum is written after the children, instead of before
+        trailing: bool,
+        /// The chunks whose checksum is written
+        children: Vec<TlvChunk>,
+    },
+}
+
+impl TlvChunk {
+    /// Creates a new integer chunk
+    #[must_use]
+    pub fn int(width: IntWidth, endianness: Endianness, value: u32) -> Self {
+        Self::Int {
+            format: IntFormat::new(width, endianness),
+            value,
+        }
+    }
+
+    /// The children of this chunk, if it can have any
+    #[must_use]
+    pub fn children(&self) -> Option<&Vec<TlvChunk>> {
+        match self {
+            Self::Node(children)
+            | Self::LengthOf { children, .. }
+            | Self::ChecksumOf { children, .. } => Some(children),
+            Self::Int { .. } | Self::Bytes(_) => None,
+        }
+    }
+
+    /// The children of this chunk, if it can have any (mutable)
+    pub fn children_mut(&mut self) -> Option<&mut Vec<TlvChunk>> {
+        match self {
+            Self::Node(children)
+            | Self::LengthOf { children, .. }
+            | Self::ChecksumOf { children, .. } => Some(children),
+            Self::Int { .. } | Self::Bytes(_) => None,
+        }
+    }
+
+    /// Serialize this chunk to `out`, computing all lengths and checksums.
+    /// Fails if a length does not fit the width of its field.
+    pub fn serialize(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            Self::Int { format, value } => format.write(*value, out),
+            Self::Bytes(bytes) => out.extend_from_slice(bytes),
+            Self::Node(children) => {
+                for child in children {
+                    child.serialize(out)?;
+                }
+            }
+            Self::LengthOf { format, children } => {
+                let body = serialize_chunks(children)?;
+                if body.len() > format.width.max_value() as usize {
+                    return Err(Error::IllegalArgument(format!(
+                        "Length {} does not fit a {:?} length field",
+                        body.len(),
+                        format.width
+                    )));
+                }
+                #[allow(clippy::cast_possible_truncation)]
+                format.write(body.len() as u32, out);
+                out.extend_from_slice(&body);
+            }
+            Self::ChecksumOf {
+                format,
+                kind,
+                trailing,
+                children,
+            } => {
+                let body = serialize_chunks(children)?;
+                let checksum = kind.compute(&body);
+                if !*trailing {
+                    format.write(checksum, out);
+                }
+                out.extend_from_slice(&body);
+                if *trailing {
+                    format.write(checksum, out);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serialize the given chunks, one after the other
+fn serialize_chunks(chunks: &[TlvChunk]) -> Result<Vec<u8>, Error> {
+    let mut out = vec![];
+    for chunk in chunks {
+        chunk.serialize(&mut out)?;
+    }
+    Ok(out)
+}
+
+/// The layout of a flat sequence of `TLV` records, used to parse seeds into [`TlvInput`]s
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TlvRecordFormat {
+    /// The layout of the type field
+    pub type_format: IntFormat,
+    /// The layout of the length field, counting the bytes of the value
+    pub length_format: IntFormat,
+}
+
+impl TlvRecordFormat {
+    /// Creates a new [`TlvRecordFormat`]
+    #[must_use]
+    pub fn new(type_format: IntFormat, length_format: IntFormat) -> Self {
+        Self {
+            type_format,
+            length_format,
+        }
+    }
+}
+
+/// Count the chunks in the tree, recursively, that match `pred`
+pub fn count_chunks<P>(chunks: &[TlvChunk], pred: &P) -> usize
+where
+    P: Fn(&TlvChunk) -> bool,
+{
+    chunks
+        .iter()
+        .map(|chunk| {
+            usize::from(pred(chunk)) + chunk.children().map_or(0, |c| count_chunks(c, pred))
+        })
+        .sum()
+}
+
+/// Get the `n`-th chunk in the tree, in depth-first order, that matches `pred`
+pub fn nth_chunk_mut<'a, P>(
+    chunks: &'a mut [TlvChunk],
+    n: &mut usize,
+    pred: &P,
+) -> Option<&'a mut TlvChunk>
+where
+    P: Fn(&TlvChunk) -> bool,
+{
+    for chunk in chunks.iter_mut() {
+        if pred(chunk) {
+            if *n == 0 {
+                return Some(chunk);
+            }
+            *n -= 1;
+        }
+        if let Some(children) = chunk.children_mut() {
+            if let Some(found) = nth_chunk_mut(children, n, pred) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// Count the lists of chunks in the tree, the root list included
+#[must_use]
+pub fn count_chunk_lists(chunks: &[TlvChunk]) -> usize {
+    1 + chunks
+        .iter()
+        .filter_map(TlvChunk::children)
+        .map(|c| count_chunk_lists(c))
+        .sum::<usize>()
+}
+
+/// Get the `n`-th list of chunks in the tree, in depth-first order, the root list being the first
+pub fn nth_chunk_list_mut<'a>(
+    chunks: &'a mut Vec<TlvChunk>,
+    n: &mut usize,
+) -> Option<&'a mut Vec<TlvChunk>> {
+    if *n == 0 {
+        return Some(chunks);
+    }
+    *n -= 1;
+    for chunk in chunks.iter_mut() {
+        if let Some(children) = chunk.children_mut() {
+            if let Some(found) = nth_chunk_list_mut(children, n) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// An input for length-prefixed and `TLV` binary formats, as a tree of [`TlvChunk`]s
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TlvInput {
+    /// The top-level chunks
+    chunks: Vec<TlvChunk>,
+}
+
+impl Input for TlvInput {
+    /// Generate a name for this input
+    fn generate_name(&self, _idx: usize) -> String {
+        let mut hasher = AHasher::new_with_keys(0, 0);
+        hasher.write(&self.serialized());
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Rc Ref-cell from Input
+impl From<TlvInput> for Rc<RefCell<TlvInput>> {
+    fn from(input: TlvInput) -> Self {
+        Rc::new(RefCell::new(input))
+    }
+}
+
+impl HasTargetBytes for TlvInput {
+    #[inline]
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        OwnedSlice::from(self.serialized())
+    }
+}
+
+impl HasLen for TlvInput {
+    /// The length of the serialized input
+    #[inline]
+    fn len(&self) -> usize {
+        self.serialized().len()
+    }
+}
+
+impl TlvInput {
+    /// Creates a new [`TlvInput`] from the given top-level chunks.
+    /// Fails if a length does not fit the width of its field.
+    pub fn new(chunks: Vec<TlvChunk>) -> Result<Self, Error> {
+        let input = Self { chunks };
+        input.to_bytes()?;
+        Ok(input)
+    }
+
+    /// Parse a flat sequence of `TLV` records, for example a seed file.
+    /// Each record becomes a [`TlvChunk::Node`] of its type and a [`TlvChunk::LengthOf`] its value.
+    /// Use it as the loader of [`crate::state::StdState::load_from_directory`] to load seeds.
+    pub fn from_records(bytes: &[u8], format: &TlvRecordFormat) -> Result<Self, Error> {
+        let mut chunks = vec![];
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let truncated = || {
+                Error::IllegalArgument(format!(
+                    "Truncated TLV record at offset {}",
+                    bytes.len() - rest.len()
+                ))
+            };
+            let header_len = format.type_format.width.bytes() + format.length_format.width.bytes();
+            let kind = format.type_format.read(rest).ok_or_else(truncated)?;
+            let len = format
+                .length_format
+                .read(&rest[format.type_format.width.bytes()..])
+                .ok_or_else(truncated)? as usize;
+            let value = rest
+                .get(header_len..header_len + len)
+                .ok_or_else(truncated)?;
+            chunks.push(TlvChunk::Node(vec![
+                TlvChunk::Int {
+                    format: format.type_format,
+                    value: kind,
+                },
+                TlvChunk::LengthOf {
+                    format: format.length_format,
+                    children: vec![TlvChunk::Bytes(value.to_vec())],
+                },
+            ]));
+            rest = &rest[header_len + len..];
+        }
+        Ok(Self { chunks })
+    }
+
+    /// Serialize this input, computing all lengths and checksums.
+    /// Fails if a length does not fit the width of its field.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        serialize_chunks(&self.chunks)
+    }
+
+    /// Serialize this input, which the constructors and the [`crate::mutators::tlv`] mutations keep valid
+    fn serialized(&self) -> Vec<u8> {
+        self.to_bytes()
+            .expect("A length of the TlvInput does not fit the width of its field")
+    }
+
+    /// The top-level chunks of this input
+    #[must_use]
+    pub fn chunks(&self) -> &[TlvChunk] {
+        &self.chunks
+    }
+
+    /// The top-level chunks of this input (mutable).
+    /// Changes must keep every length within the width of its field, see [`TlvInput::to_bytes`].
+    #[must_use]
+    pub fn chunks_mut(&mut self) -> &mut Vec<TlvChunk> {
+        &mut self.chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bolts::AsSlice,
+        inputs::{
+            tlv::{
+                ChecksumKind, Endianness, IntFormat, IntWidth, TlvChunk, TlvInput, TlvRecordFormat,
+            },
+            HasTargetBytes,
+        },
+    };
+
+    #[test]
+    fn test_tlv_serialize() {
+        let input = TlvInput::new(vec![
+            TlvChunk::int(IntWidth::U8, Endianness::Little, 7),
+            TlvChunk::LengthOf {
+                format: IntFormat::new(IntWidth::U16, Endianness::Big),
+                children: vec![TlvChunk::ChecksumOf {
+                    format: IntFormat::new(IntWidth::U8, Endianness::Little),
+                    kind: ChecksumKind::Sum,
+                    trailing: true,
+                    children: vec![
+                        TlvChunk::Bytes(b"AB".to_vec()),
+                        TlvChunk::int(IntWidth::U32, Endianness::Little, 0x0102_0304),
+                    ],
+                }],
+            },
+        ])
+        .unwrap();
+        assert_eq!(
+            input.target_bytes().as_slice(),
+            b"\x07\x00\x07AB\x04\x03\x02\x01\x8d"
+        );
+        assert_eq!(ChecksumKind::Crc32.compute(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_tlv_length_overflow() {
+        let chunks = vec![TlvChunk::LengthOf {
+            format: IntFormat::new(IntWidth::U8, Endianness::Little),
+            children: vec![TlvChunk::Bytes(vec![0; 256])],
+        }];
+        assert!(TlvInput::new(chunks.clone()).is_err());
+
+        let mut input = TlvInput::new(vec![]).unwrap();
+        *input.chunks_mut() = chunks;
+        assert!(input.to_bytes().is_err());
+    }
+
+    #[test]
+    fn test_tlv_from_records() {
+        let format = TlvRecordFormat::new(
+            IntFormat::new(IntWidth::U8, Endianness::Little),
+            IntFormat::new(IntWidth::U16, Endianness::Big),
+        );
+        let bytes = b"\x01\x00\x02AB\x02\x00\x00";
+        let input = TlvInput::from_records(bytes, &format).unwrap();
+        assert_eq!(input.chunks().len(), 2);
+        assert_eq!(
+            input.chunks()[0],
+            TlvChunk::Node(vec![
+                TlvChunk::int(IntWidth::U8, Endianness::Little, 1),
+                TlvChunk::LengthOf {
+                    format: format.length_format,
+                    children: vec![TlvChunk::Bytes(b"AB".to_vec())],
+                },
+            ])
+        );
+        assert_eq!(input.to_bytes().unwrap(), bytes);
+
+        assert!(TlvInput::from_records(b"\x01\x00\x03AB", &format).is_err());
+        assert!(TlvInput::from_records(b"\x01\x00", &format).is_err());
+    }
+}
@@ -132,6 +132,9 @@ pub enum Error {
     IllegalArgument(String),
     /// Forkserver related Error
     Forkserver(String),
+    /// The forkserver target failed to initialize
+    #[cfg(all(feature = "std", feature = "fork", unix))]
+    ForkserverInit(executors::forkserver::ForkserverError),
     /// MOpt related Error
     MOpt(String),
     /// Shutting down, not really an error.
@@ -158,6 +161,8 @@ impl fmt::Display for Error {
             Self::IllegalState(s) => write!(f, "Illegal state: {0}", &s),
             Self::IllegalArgument(s) => write!(f, "Illegal argument: {0}", &s),
             Self::Forkserver(s) => write!(f, "Forkserver : {0}", &s),
+            #[cfg(all(feature = "std", feature = "fork", unix))]
+            Self::ForkserverInit(err) => write!(f, "Forkserver init: {0}", &err),
             Self::MOpt(s) => write!(f, "MOpt: {0}", &s),
             Self::ShuttingDown => write!(f, "Shutting down!"),
             Self::Unknown(s) => write!(f, "Unknown error: {0}", &s),
@@ -187,6 +192,13 @@ impl From<nix::Error> for Error {
     }
 }
 
+#[cfg(all(feature = "std", feature = "fork", unix))]
+impl From<executors::forkserver::ForkserverError> for Error {
+    fn from(err: executors::forkserver::ForkserverError) -> Self {
+        Self::ForkserverInit(err)
+    }
+}
+
 /// Create an AFL Error from io Error
 #[cfg(feature = "std")]
 impl From<io::Error> for Error {
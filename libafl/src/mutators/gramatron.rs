@@ -248,3 +248,66 @@ impl GramatronRecursionMutator {
         Self::default()
     }
 }
+
+/// A [`Mutator`] removing a random sub-walk of a [`GramatronInput`], starting and ending in the same
+/// automaton state, so that the input stays a valid walk through the grammar.
+/// Use it with a [`crate::stages::MinimizationStage`] to minimize inputs while keeping their coverage.
+#[derive(Default, Debug)]
+pub struct GramatronSubwalkRemovalMutator {
+    positions: HashMap<usize, Vec<usize>>,
+    states: Vec<usize>,
+}
+
+impl<S> Mutator<GramatronInput, S> for GramatronSubwalkRemovalMutator
+where
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut GramatronInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        self.positions.clear();
+        self.states.clear();
+        for (i, term) in input.terminals().iter().enumerate() {
+            let positions = self.positions.entry(term.state).or_insert_with(Vec::new);
+            if positions.len() == 1 {
+                // Keep track only of states visited more than once
+                self.states.push(term.state);
+            }
+            positions.push(i);
+        }
+
+        if self.states.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let chosen = *state.rand_mut().choose(&self.states);
+        let positions = &self.positions[&chosen];
+        let first = state.rand_mut().below(positions.len() as u64 - 1) as usize;
+        let second = state
+            .rand_mut()
+            .between(first as u64 + 1, positions.len() as u64 - 1) as usize;
+
+        input
+            .terminals_mut()
+            .drain(positions[first]..positions[second]);
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for GramatronSubwalkRemovalMutator {
+    fn name(&self) -> &str {
+        "GramatronSubwalkRemovalMutator"
+    }
+}
+
+impl GramatronSubwalkRemovalMutator {
+    /// Creates a new [`GramatronSubwalkRemovalMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
@@ -14,6 +14,10 @@ pub mod gramatron;
 pub use gramatron::*;
 pub mod grimoire;
 pub use grimoire::*;
+pub mod tlv;
+pub use tlv::*;
+pub mod model;
+pub use model::*;
 
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
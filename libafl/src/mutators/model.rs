@@ -0,0 +1,462 @@
+//! Mutations for [`ModelInput`]s.
+//! They walk the [`ModelNode`] tree of the wrapped value, so every mutated input still deserializes to the model.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::Debug;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    bolts::{
+        rands::Rand,
+        tuples::{tuple_list, tuple_list_type},
+    },
+    inputs::{
+        model::{from_model_node, to_model_node},
+        ModelCodec, ModelInput, ModelNode,
+    },
+    mutators::{
+        mutations::{ARITH_MAX, INTERESTING_32},
+        MutationResult, Mutator, Named,
+    },
+    state::{HasMaxSize, HasRand},
+    Error,
+};
+
+/// Interesting floating point values
+const INTERESTING_FLOATS: [f64; 10] = [
+    0.0,
+    -0.0,
+    1.0,
+    -1.0,
+    f64::INFINITY,
+    f64::NEG_INFINITY,
+    f64::NAN,
+    f64::MIN_POSITIVE,
+    f64::MAX,
+    f64::MIN,
+];
+
+/// Pick a random node of the model of `input` matching `pred`, and apply `mutate` to it.
+/// The input is only changed if the mutated model deserializes again.
+fn mutate_model<C, F, P, S, T>(
+    state: &mut S,
+    input: &mut ModelInput<T, C>,
+    pred: P,
+    mut mutate: F,
+) -> Result<MutationResult, Error>
+where
+    F: FnMut(&mut S, &mut ModelNode) -> bool,
+    P: Fn(&ModelNode) -> bool,
+    S: HasRand,
+    T: Serialize + DeserializeOwned,
+{
+    let mut root = to_model_node(input.value())?;
+    let count = root.count(&pred);
+    if count == 0 {
+        return Ok(MutationResult::Skipped);
+    }
+    let mut n = state.rand_mut().below(count as u64) as usize;
+    let mutated = match root.nth_mut(&mut n, &pred) {
+        Some(node) => mutate(state, node),
+        None => false,
+    };
+    if !mutated {
+        return Ok(MutationResult::Skipped);
+    }
+
+    match from_model_node(&root) {
+        Ok(value) => {
+            *input.value_mut() = value;
+            Ok(MutationResult::Mutated)
+        }
+        // For example, a recursive type that grew too deep
+        Err(_) => Ok(MutationResult::Skipped),
+    }
+}
+
+/// Mutate a random integer, float, boolean or char of the model
+#[derive(Debug, Default)]
+pub struct ModelScalarMutator;
+
+impl<C, S, T> Mutator<ModelInput<T, C>, S> for ModelScalarMutator
+where
+    C: ModelCodec + Clone + Debug,
+    S: HasRand,
+    T: Serialize + DeserializeOwned + Clone + Debug,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ModelInput<T, C>,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let pred = |node: &ModelNode| {
+            matches!(
+                node,
+                ModelNode::Bool(_)
+                    | ModelNode::Uint(_)
+                    | ModelNode::Int(_)
+                    | ModelNode::Float(_)
+                    | ModelNode::Char(_)
+            )
+        };
+        mutate_model(state, input, pred, |state, node| {
+            let choice = state.rand_mut().below(4);
+            let rand = state.rand_mut().next();
+            let delta = 1 + state.rand_mut().below(ARITH_MAX);
+            // Most integers are narrow, so flip low bits more often
+            let bit = rand % (8 << ((rand >> 32) % 4));
+            let interesting = i64::from(INTERESTING_32[rand as usize % INTERESTING_32.len()]);
+
+            #[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
+            match node {
+                ModelNode::Bool(v) => *v = !*v,
+                ModelNode::Uint(v) => {
+                    *v = match choice {
+                        0 => rand,
+                        1 => interesting as u64,
+                        2 if rand & 1 == 0 => v.wrapping_add(delta),
+                        2 => v.wrapping_sub(delta),
+                        _ => *v ^ (1 << bit),
+                    }
+                }
+                ModelNode::Int(v) => {
+                    *v = match choice {
+                        0 => rand as i64,
+                        1 => interesting,
+                        2 if rand & 1 == 0 => v.wrapping_add(delta as i64),
+                        2 => v.wrapping_sub(delta as i64),
+                        _ => *v ^ (1 << bit),
+                    }
+                }
+                #[allow(clippy::cast_precision_loss)]
+                ModelNode::Float(v) => {
+                    *v = match choice {
+                        0 => f64::from_bits(rand),
+                        1 => INTERESTING_FLOATS[rand as usize % INTERESTING_FLOATS.len()],
+                        2 if rand & 1 == 0 => *v + delta as f64,
+                        2 => *v - delta as f64,
+                        _ => -*v,
+                    }
+                }
+                #[allow(clippy::cast_possible_truncation)]
+                ModelNode::Char(v) => {
+                    *v = if choice == 0 {
+                        char::from_u32((rand % 0x11_0000) as u32).unwrap_or('\0')
+                    } else {
+                        char::from((rand & 0x7f) as u8)
+                    }
+                }
+                _ => return false,
+            }
+            true
+        })
+    }
+}
+
+impl Named for ModelScalarMutator {
+    fn name(&self) -> &str {
+        "ModelScalarMutator"
+    }
+}
+
+impl ModelScalarMutator {
+    /// Creates a new [`ModelScalarMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Mutate a random string or byte buffer of the model. Strings stay valid `UTF-8`.
+#[derive(Debug, Default)]
+pub struct ModelBytesMutator;
+
+impl<C, S, T> Mutator<ModelInput<T, C>, S> for ModelBytesMutator
+where
+    C: ModelCodec + Clone + Debug,
+    S: HasRand + HasMaxSize,
+    T: Serialize + DeserializeOwned + Clone + Debug,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ModelInput<T, C>,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let max_size = state.max_size();
+        let pred = |node: &ModelNode| matches!(node, ModelNode::Str(_) | ModelNode::Bytes(_));
+        mutate_model(state, input, pred, |state, node| {
+            let mut bytes = match node {
+                ModelNode::Str(s) => s.clone().into_bytes(),
+                ModelNode::Bytes(b) => b.clone(),
+                _ => return false,
+            };
+            if !mutate_bytes(state, &mut bytes, max_size) {
+                return false;
+            }
+            match node {
+                ModelNode::Str(s) => *s = String::from_utf8_lossy(&bytes).into_owned(),
+                ModelNode::Bytes(b) => *b = bytes,
+                _ => return false,
+            }
+            true
+        })
+    }
+}
+
+/// Apply a random byte-level mutation, returning `false` if none was possible
+fn mutate_bytes<S>(state: &mut S, bytes: &mut Vec<u8>, max_size: usize) -> bool
+where
+    S: HasRand,
+{
+    let len = bytes.len();
+    let choice = state.rand_mut().below(5);
+    let rand = state.rand_mut().next();
+    let pos = state.rand_mut().below(len as u64 + 1) as usize;
+    let end = pos + state.rand_mut().below((len - pos) as u64 + 1) as usize;
+
+    #[allow(clippy::cast_possible_truncation)]
+    match choice {
+        0 if pos < len => bytes[pos] ^= 1 << (rand % 8),
+        1 if pos < len => bytes[pos] = rand as u8,
+        2 if len < max_size => bytes.insert(pos, rand as u8),
+        3 if pos < end => {
+            bytes.drain(pos..end);
+        }
+        4 if pos < end && len + (end - pos) <= max_size => {
+            let copy = bytes[pos..end].to_vec();
+            bytes.splice(end..end, copy);
+        }
+        _ => return false,
+    }
+    true
+}
+
+impl Named for ModelBytesMutator {
+    fn name(&self) -> &str {
+        "ModelBytesMutator"
+    }
+}
+
+impl ModelBytesMutator {
+    /// Creates a new [`ModelBytesMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Switch a random enum of the model to another variant, or toggle a random `Option`.
+/// New content is filled with defaults.
+#[derive(Debug, Default)]
+pub struct ModelVariantMutator;
+
+impl<C, S, T> Mutator<ModelInput<T, C>, S> for ModelVariantMutator
+where
+    C: ModelCodec + Clone + Debug,
+    S: HasRand,
+    T: Serialize + DeserializeOwned + Clone + Debug,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ModelInput<T, C>,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let pred = |node: &ModelNode| {
+            matches!(
+                node,
+                ModelNode::Variant { .. } | ModelNode::None | ModelNode::Some(_)
+            )
+        };
+        mutate_model(state, input, pred, |state, node| {
+            match node {
+                ModelNode::Variant { index, value } => {
+                    // Taken modulo the number of variants on deserialization
+                    let new_index = state.rand_mut().next() as u32;
+                    if new_index == *index {
+                        return false;
+                    }
+                    *index = new_index;
+                    **value = ModelNode::Unit;
+                }
+                ModelNode::None => *node = ModelNode::Some(ModelNode::Unit.into()),
+                ModelNode::Some(_) => *node = ModelNode::None,
+                _ => return false,
+            }
+            true
+        })
+    }
+}
+
+impl Named for ModelVariantMutator {
+    fn name(&self) -> &str {
+        "ModelVariantMutator"
+    }
+}
+
+impl ModelVariantMutator {
+    /// Creates a new [`ModelVariantMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Change the length of a random sequence or map of the model,
+/// by removing, duplicating, or appending default elements
+#[derive(Debug, Default)]
+pub struct ModelLengthMutator;
+
+impl<C, S, T> Mutator<ModelInput<T, C>, S> for ModelLengthMutator
+where
+    C: ModelCodec + Clone + Debug,
+    S: HasRand + HasMaxSize,
+    T: Serialize + DeserializeOwned + Clone + Debug,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ModelInput<T, C>,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let max_size = state.max_size();
+        let pred = |node: &ModelNode| matches!(node, ModelNode::Seq(_) | ModelNode::Map(_));
+        mutate_model(state, input, pred, |state, node| {
+            let choice = state.rand_mut().below(4);
+            let rand = state.rand_mut().next() as usize;
+            match node {
+                ModelNode::Seq(items) => resize(items, choice, rand, max_size, ModelNode::Unit),
+                ModelNode::Map(entries) => resize(
+                    entries,
+                    choice,
+                    rand,
+                    max_size,
+                    (ModelNode::Unit, ModelNode::Unit),
+                ),
+                _ => false,
+            }
+        })
+    }
+}
+
+/// Remove, duplicate, or append an element, or truncate the list
+fn resize<E>(items: &mut Vec<E>, choice: u64, rand: usize, max_size: usize, default: E) -> bool
+where
+    E: Clone,
+{
+    let len = items.len();
+    match choice {
+        0 if len > 0 => {
+            items.remove(rand % len);
+        }
+        1 if len > 0 && len < max_size => {
+            let idx = rand % len;
+            let item = items[idx].clone();
+            items.insert(idx + 1, item);
+        }
+        2 if len < max_size => items.push(default),
+        3 if len > 0 => items.truncate(rand % len),
+        _ => return false,
+    }
+    true
+}
+
+impl Named for ModelLengthMutator {
+    fn name(&self) -> &str {
+        "ModelLengthMutator"
+    }
+}
+
+impl ModelLengthMutator {
+    /// Creates a new [`ModelLengthMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Get the mutations for [`ModelInput`]s
+#[must_use]
+pub fn model_mutations() -> tuple_list_type!(
+    ModelScalarMutator,
+    ModelBytesMutator,
+    ModelVariantMutator,
+    ModelLengthMutator,
+) {
+    tuple_list!(
+        ModelScalarMutator::new(),
+        ModelBytesMutator::new(),
+        ModelVariantMutator::new(),
+        ModelLengthMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        bolts::{
+            rands::StdRand,
+            tuples::{tuple_list, HasConstLen},
+        },
+        corpus::InMemoryCorpus,
+        inputs::{ModelInput, PostcardCodec},
+        mutators::{model_mutations, MutationResult, MutatorsTuple},
+        state::StdState,
+    };
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    enum Command {
+        Ping,
+        Echo(String),
+        Move { x: i16, y: f32 },
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    struct Request {
+        id: u32,
+        commands: Vec<Command>,
+        token: Option<u64>,
+    }
+
+    #[test]
+    fn test_model_mutators() {
+        let mut inputs = vec![ModelInput::<Request, PostcardCodec>::new(Request {
+            id: 1,
+            commands: vec![Command::Ping, Command::Echo("hello".into())],
+            token: Some(42),
+        })];
+
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<ModelInput<Request, PostcardCodec>>::new(),
+            InMemoryCorpus::new(),
+            tuple_list!(),
+        );
+
+        let mut mutations = model_mutations();
+        let mut mutated = 0;
+        for _ in 0..3 {
+            let mut new_testcases = vec![];
+            for idx in 0..mutations.len() {
+                for input in &inputs {
+                    let mut mutant = input.clone();
+                    if mutations
+                        .get_and_mutate(idx, &mut state, &mut mutant, 0)
+                        .unwrap()
+                        == MutationResult::Mutated
+                    {
+                        new_testcases.push(mutant);
+                    }
+                }
+            }
+            mutated += new_testcases.len();
+            inputs.append(&mut new_testcases);
+        }
+        assert!(mutated > 0);
+    }
+}
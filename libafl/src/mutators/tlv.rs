@@ -0,0 +1,497 @@
+//! Mutations for [`TlvInput`]s.
+//! They work on single chunks, so that length and checksum fields stay valid.
+
+use crate::{
+    bolts::{
+        rands::Rand,
+        tuples::{tuple_list, tuple_list_type},
+    },
+    inputs::{
+        tlv::{count_chunk_lists, count_chunks, nth_chunk_list_mut, nth_chunk_mut},
+        BytesInput, HasBytesVec, TlvChunk, TlvInput,
+    },
+    mutators::{
+        mutations::{ARITH_MAX, INTERESTING_32},
+        MutationResult, Mutator, Named,
+    },
+    state::{HasMaxSize, HasRand},
+    Error,
+};
+
+/// Mutate the value of a random integer chunk: a random value, an interesting value, an arithmetic change or a bitflip
+#[derive(Debug, Default)]
+pub struct TlvIntMutator;
+
+impl<S: HasRand> Mutator<TlvInput, S> for TlvIntMutator {
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut TlvInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let is_int = |chunk: &TlvChunk| matches!(chunk, TlvChunk::Int { .. });
+        let count = count_chunks(input.chunks(), &is_int);
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let mut n = state.rand_mut().below(count as u64) as usize;
+        let choice = state.rand_mut().below(4);
+        let delta = 1 + state.rand_mut().below(ARITH_MAX) as u32;
+        let random = state.rand_mut().next();
+
+        if let Some(TlvChunk::Int { format, value }) =
+            nth_chunk_mut(input.chunks_mut(), &mut n, &is_int)
+        {
+            let max = format.width.max_value();
+            let new_value = match choice {
+                0 => random as u32,
+                #[allow(clippy::cast_sign_loss)]
+                1 => INTERESTING_32[random as usize % INTERESTING_32.len()] as u32,
+                2 => {
+                    if random & 1 == 0 {
+                        value.wrapping_add(delta)
+                    } else {
+                        value.wrapping_sub(delta)
+                    }
+                }
+                _ => *value ^ (1 << (random % (format.width.bytes() as u64 * 8))),
+            } & max;
+            if new_value == *value & max {
+                return Ok(MutationResult::Skipped);
+            }
+            *value = new_value;
+            Ok(MutationResult::Mutated)
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+}
+
+impl Named for TlvIntMutator {
+    fn name(&self) -> &str {
+        "TlvIntMutator"
+    }
+}
+
+impl TlvIntMutator {
+    /// Creates a new [`TlvIntMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Mutate the contents of a random bytes chunk using the given [`BytesInput`] [`Mutator`], for example havoc.
+/// Mutations growing a chunk beyond the width of a length field are reverted.
+#[derive(Debug, Default)]
+pub struct TlvBytesMutator<M> {
+    mutator: M,
+}
+
+impl<M, S> Mutator<TlvInput, S> for TlvBytesMutator<M>
+where
+    M: Mutator<BytesInput, S>,
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut TlvInput,
+        stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let is_bytes = |chunk: &TlvChunk| matches!(chunk, TlvChunk::Bytes(_));
+        let count = count_chunks(input.chunks(), &is_bytes);
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = state.rand_mut().below(count as u64) as usize;
+
+        let mut n = idx;
+        let original = if let Some(TlvChunk::Bytes(bytes)) =
+            nth_chunk_mut(input.chunks_mut(), &mut n, &is_bytes)
+        {
+            let original = bytes.clone();
+            let mut bytes_input = BytesInput::new(core::mem::take(bytes));
+            let result = self.mutator.mutate(state, &mut bytes_input, stage_idx);
+            *bytes = core::mem::take(bytes_input.bytes_mut());
+            if result? == MutationResult::Skipped {
+                return Ok(MutationResult::Skipped);
+            }
+            original
+        } else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        if input.to_bytes().is_err() {
+            let mut n = idx;
+            if let Some(TlvChunk::Bytes(bytes)) =
+                nth_chunk_mut(input.chunks_mut(), &mut n, &is_bytes)
+            {
+                *bytes = original;
+            }
+            return Ok(MutationResult::Skipped);
+        }
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut S,
+        stage_idx: i32,
+        corpus_idx: Option<usize>,
+    ) -> Result<(), Error> {
+        self.mutator.post_exec(state, stage_idx, corpus_idx)
+    }
+}
+
+impl<M> Named for TlvBytesMutator<M> {
+    fn name(&self) -> &str {
+        "TlvBytesMutator"
+    }
+}
+
+impl<M> TlvBytesMutator<M> {
+    /// Creates a new [`TlvBytesMutator`], mutating bytes chunks with the given mutator
+    #[must_use]
+    pub fn new(mutator: M) -> Self {
+        Self { mutator }
+    }
+}
+
+/// Duplicate a random chunk next to itself, for example a `TLV` record.
+/// Duplicates growing a chunk beyond the width of a length field are skipped.
+#[derive(Debug, Default)]
+pub struct TlvDuplicateMutator;
+
+impl<S: HasRand + HasMaxSize> Mutator<TlvInput, S> for TlvDuplicateMutator {
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut TlvInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let max_size = state.max_size();
+        let lists = count_chunk_lists(input.chunks());
+        let mut n = state.rand_mut().below(lists as u64) as usize;
+        let rand = state.rand_mut().next() as usize;
+
+        let mut mutated = input.clone();
+        match nth_chunk_list_mut(mutated.chunks_mut(), &mut n) {
+            Some(list) if !list.is_empty() => {
+                let idx = rand % list.len();
+                let chunk = list[idx].clone();
+                list.insert(idx + 1, chunk);
+            }
+            _ => return Ok(MutationResult::Skipped),
+        }
+        match mutated.to_bytes() {
+            Ok(bytes) if bytes.len() <= max_size => (),
+            _ => return Ok(MutationResult::Skipped),
+        }
+        *input = mutated;
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for TlvDuplicateMutator {
+    fn name(&self) -> &str {
+        "TlvDuplicateMutator"
+    }
+}
+
+impl TlvDuplicateMutator {
+    /// Creates a new [`TlvDuplicateMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Delete a random chunk
+#[derive(Debug, Default)]
+pub struct TlvDeleteMutator;
+
+impl<S: HasRand> Mutator<TlvInput, S> for TlvDeleteMutator {
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut TlvInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let lists = count_chunk_lists(input.chunks());
+        let mut n = state.rand_mut().below(lists as u64) as usize;
+        let rand = state.rand_mut().next() as usize;
+
+        match nth_chunk_list_mut(input.chunks_mut(), &mut n) {
+            Some(list) if !list.is_empty() => {
+                let idx = rand % list.len();
+                list.remove(idx);
+                Ok(MutationResult::Mutated)
+            }
+            _ => Ok(MutationResult::Skipped),
+        }
+    }
+}
+
+impl Named for TlvDeleteMutator {
+    fn name(&self) -> &str {
+        "TlvDeleteMutator"
+    }
+}
+
+impl TlvDeleteMutator {
+    /// Creates a new [`TlvDeleteMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Swap two random chunks of the same list, for example two `TLV` records
+#[derive(Debug, Default)]
+pub struct TlvSwapMutator;
+
+impl<S: HasRand> Mutator<TlvInput, S> for TlvSwapMutator {
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut TlvInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let lists = count_chunk_lists(input.chunks());
+        let mut n = state.rand_mut().below(lists as u64) as usize;
+        let first = state.rand_mut().next() as usize;
+        let second = state.rand_mut().next() as usize;
+
+        match nth_chunk_list_mut(input.chunks_mut(), &mut n) {
+            Some(list) if list.len() > 1 => {
+                let (a, b) = (first % list.len(), second % list.len());
+                if a == b || list[a] == list[b] {
+                    return Ok(MutationResult::Skipped);
+                }
+                list.swap(a, b);
+                Ok(MutationResult::Mutated)
+            }
+            _ => Ok(MutationResult::Skipped),
+        }
+    }
+}
+
+impl Named for TlvSwapMutator {
+    fn name(&self) -> &str {
+        "TlvSwapMutator"
+    }
+}
+
+impl TlvSwapMutator {
+    /// Creates a new [`TlvSwapMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Get the structural mutations for [`TlvInput`]s.
+/// Combine them with a [`TlvBytesMutator`] to also mutate the contents of bytes chunks.
+#[must_use]
+pub fn tlv_mutations() -> tuple_list_type!(
+    TlvIntMutator,
+    TlvDuplicateMutator,
+    TlvDeleteMutator,
+    TlvSwapMutator,
+) {
+    tuple_list!(
+        TlvIntMutator::new(),
+        TlvDuplicateMutator::new(),
+        TlvDeleteMutator::new(),
+        TlvSwapMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::InMemoryCorpus,
+        inputs::{Endianness, IntFormat, IntWidth, TlvChunk, TlvInput, TlvRecordFormat},
+        mutators::{
+            mutations::{BitFlipMutator, BytesExpandMutator},
+            tlv::{
+                TlvBytesMutator, TlvDeleteMutator, TlvDuplicateMutator, TlvIntMutator,
+                TlvSwapMutator,
+            },
+            MutationResult, Mutator,
+        },
+        state::StdState,
+    };
+
+    type TestState =
+        StdState<InMemoryCorpus<TlvInput>, (), TlvInput, StdRand, InMemoryCorpus<TlvInput>>;
+
+    fn test_state() -> TestState {
+        StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            (),
+        )
+    }
+
+    fn test_input(value_len: u8) -> TlvInput {
+        let format = TlvRecordFormat::new(
+            IntFormat::new(IntWidth::U8, Endianness::Little),
+            IntFormat::new(IntWidth::U8, Endianness::Little),
+        );
+        let mut bytes = vec![1, value_len];
+        bytes.resize(2 + usize::from(value_len), b'A');
+        bytes.extend_from_slice(b"\x02\x02BC");
+        TlvInput::from_records(&bytes, &format).unwrap()
+    }
+
+    /// Mutate `input` a hundred times with `mutator`, checking that it always serializes, and count the mutations
+    fn mutate_valid<M>(mutator: &mut M, input: &TlvInput) -> usize
+    where
+        M: Mutator<TlvInput, TestState>,
+    {
+        let mut state = test_state();
+        let mut mutated = 0;
+        for _ in 0..100 {
+            let mut mutant = input.clone();
+            if mutator.mutate(&mut state, &mut mutant, 0).unwrap() == MutationResult::Mutated {
+                assert_ne!(&mutant, input);
+                mutant.to_bytes().unwrap();
+                mutated += 1;
+            } else {
+                assert_eq!(&mutant, input);
+            }
+        }
+        mutated
+    }
+
+    #[test]
+    fn test_tlv_int_mutator() {
+        let input = test_input(2);
+        let mut state = test_state();
+        let mut mutant = input.clone();
+        while TlvIntMutator::new()
+            .mutate(&mut state, &mut mutant, 0)
+            .unwrap()
+            == MutationResult::Skipped
+        {}
+        let bytes = mutant.to_bytes().unwrap();
+        let original = input.to_bytes().unwrap();
+        assert_eq!(bytes.len(), original.len());
+        assert_eq!(
+            bytes
+                .iter()
+                .zip(original.iter())
+                .filter(|(a, b)| a != b)
+                .count(),
+            1
+        );
+        // The type fields are the only integers, the length fields are recomputed
+        assert_eq!(&bytes[1..4], b"\x02AA");
+        assert_eq!(&bytes[5..], b"\x02BC");
+
+        assert!(mutate_valid(&mut TlvIntMutator::new(), &input) > 0);
+    }
+
+    #[test]
+    fn test_tlv_bytes_mutator() {
+        let input = test_input(2);
+        let mut state = test_state();
+        let mut mutant = input.clone();
+        assert_eq!(
+            TlvBytesMutator::new(BytesExpandMutator::new())
+                .mutate(&mut state, &mut mutant, 0)
+                .unwrap(),
+            MutationResult::Mutated
+        );
+        let bytes = mutant.to_bytes().unwrap();
+        assert!(bytes.len() > input.to_bytes().unwrap().len());
+        assert!(TlvInput::from_records(
+            &bytes,
+            &TlvRecordFormat::new(
+                IntFormat::new(IntWidth::U8, Endianness::Little),
+                IntFormat::new(IntWidth::U8, Endianness::Little),
+            )
+        )
+        .is_ok());
+
+        assert!(mutate_valid(&mut TlvBytesMutator::new(BitFlipMutator::new()), &input) > 0);
+
+        // The first value cannot grow beyond its one byte length field
+        let full = test_input(u8::MAX);
+        let mut expand = TlvBytesMutator::new(BytesExpandMutator::new());
+        mutate_valid(&mut expand, &full);
+    }
+
+    #[test]
+    fn test_tlv_duplicate_mutator() {
+        let input = test_input(2);
+        assert!(mutate_valid(&mut TlvDuplicateMutator::new(), &input) > 0);
+
+        let full = test_input(u8::MAX);
+        mutate_valid(&mut TlvDuplicateMutator::new(), &full);
+
+        let mut state = test_state();
+        let mut mutant = TlvInput::new(vec![TlvChunk::LengthOf {
+            format: IntFormat::new(IntWidth::U8, Endianness::Little),
+            children: vec![TlvChunk::Bytes(vec![0; 200])],
+        }])
+        .unwrap();
+        for _ in 0..100 {
+            let result = TlvDuplicateMutator::new()
+                .mutate(&mut state, &mut mutant, 0)
+                .unwrap();
+            // Only the root list can be grown, duplicating the whole length-prefixed chunk
+            if result == MutationResult::Mutated {
+                assert!(mutant.chunks().iter().all(|chunk| matches!(
+                    chunk,
+                    TlvChunk::LengthOf { children, .. } if children.len() == 1
+                )));
+            }
+        }
+    }
+
+    #[test]
+    fn test_tlv_delete_mutator() {
+        let input = test_input(2);
+        let mut state = test_state();
+        let mut mutant = input.clone();
+        assert_eq!(
+            TlvDeleteMutator::new()
+                .mutate(&mut state, &mut mutant, 0)
+                .unwrap(),
+            MutationResult::Mutated
+        );
+        assert!(mutant.to_bytes().unwrap().len() < input.to_bytes().unwrap().len());
+
+        let mut empty = TlvInput::default();
+        assert_eq!(
+            TlvDeleteMutator::new()
+                .mutate(&mut state, &mut empty, 0)
+                .unwrap(),
+            MutationResult::Skipped
+        );
+    }
+
+    #[test]
+    fn test_tlv_swap_mutator() {
+        let input = test_input(2);
+        assert!(mutate_valid(&mut TlvSwapMutator::new(), &input) > 0);
+
+        let mut state = test_state();
+        let mut mutant = input.clone();
+        while TlvSwapMutator::new()
+            .mutate(&mut state, &mut mutant, 0)
+            .unwrap()
+            == MutationResult::Skipped
+        {}
+        let mut bytes = mutant.to_bytes().unwrap();
+        bytes.sort_unstable();
+        let mut original = input.to_bytes().unwrap();
+        original.sort_unstable();
+        assert_eq!(bytes, original);
+    }
+}
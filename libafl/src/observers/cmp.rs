@@ -15,7 +15,7 @@ use crate::{
 };
 
 /// Compare values collected during a run
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CmpValues {
     /// Two u8 values
     U8((u8, u8)),
@@ -50,6 +50,32 @@ impl CmpValues {
             CmpValues::Bytes(_) => None,
         }
     }
+
+    /// Returns the number of equal bits of the operands.
+    /// For bytes, this is eight times the length of their common prefix.
+    #[must_use]
+    pub fn matching_bits(&self) -> usize {
+        match self {
+            CmpValues::U8(t) => (!(t.0 ^ t.1)).count_ones() as usize,
+            CmpValues::U16(t) => (!(t.0 ^ t.1)).count_ones() as usize,
+            CmpValues::U32(t) => (!(t.0 ^ t.1)).count_ones() as usize,
+            CmpValues::U64(t) => (!(t.0 ^ t.1)).count_ones() as usize,
+            CmpValues::Bytes(t) => {
+                t.0.iter()
+                    .zip(t.1.iter())
+                    .take_while(|(a, b)| a == b)
+                    .count()
+                    * 8
+            }
+        }
+    }
+
+    /// Returns the absolute distance of the operands, if numerical
+    #[must_use]
+    pub fn distance(&self) -> Option<u64> {
+        self.to_u64_tuple()
+            .map(|(a, b)| if a > b { a - b } else { b - a })
+    }
 }
 
 /// A state metadata holding a list of values logged from comparisons
@@ -2,6 +2,7 @@
 
 use ahash::AHasher;
 use alloc::{
+    boxed::Box,
     string::{String, ToString},
     vec::Vec,
 };
@@ -12,8 +13,12 @@ use core::{
     marker::PhantomData,
     slice::{from_raw_parts, Iter, IterMut},
 };
+use hashbrown::{
+    hash_map::{Values, ValuesMut},
+    HashMap,
+};
 use intervaltree::IntervalTree;
-use num_traits::PrimInt;
+use num_traits::{Bounded, PrimInt, ToPrimitive};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -118,6 +123,17 @@ pub trait MapObserver: HasLen + Named + Serialize + serde::de::DeserializeOwned
         }
         res
     }
+
+    /// Call `f` with the index and the value of every entry the target may have touched, in no particular order.
+    /// All usable entries by default, only the entries of the hash map for the [`SparseMapObserver`].
+    fn for_each_touched<F>(&self, mut f: F)
+    where
+        F: FnMut(usize, Self::Entry),
+    {
+        for i in 0..self.usable_count() {
+            f(i, *self.get(i));
+        }
+    }
 }
 
 /// A Simple iterator calling `MapObserver::get`
@@ -768,7 +784,177 @@ where
     }
 }
 
-/// Map observer with hitcounts postprocessing
+/// A table of hitcount buckets, as used by [`HitcountsMapObserver`].
+/// Each bound is the lowest count of a bucket, counts in the `i`-th bucket are classified as `1 << i`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HitcountsBuckets {
+    bounds: Vec<u64>,
+    /// The classification of the counts up to `255`, for the fast `u8` path
+    lookup: Vec<u8>,
+}
+
+/// The lowest counts of the AFL buckets
+const AFL_BUCKET_BOUNDS: [u64; 8] = [1, 2, 3, 4, 8, 16, 32, 128];
+
+impl HitcountsBuckets {
+    /// Creates new [`HitcountsBuckets`] from the lowest count of each bucket, in increasing order
+    pub fn new(bounds: Vec<u64>) -> Result<Self, Error> {
+        if bounds.is_empty() || bounds.len() > 64 || bounds[0] == 0 {
+            return Err(Error::IllegalArgument(
+                "Hitcount buckets need between 1 and 64 non-zero bounds".into(),
+            ));
+        }
+        if bounds.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(Error::IllegalArgument(
+                "Hitcount bucket bounds must be strictly increasing".into(),
+            ));
+        }
+        let mut buckets = Self {
+            bounds,
+            lookup: vec![],
+        };
+        buckets.lookup = (0..256)
+            .map(|count| buckets.classify(count).min(u64::from(u8::MAX)) as u8)
+            .collect();
+        Ok(buckets)
+    }
+
+    /// The AFL buckets: 1, 2, 3, 4-7, 8-15, 16-31, 32-127, 128+
+    #[must_use]
+    pub fn afl() -> Self {
+        Self {
+            bounds: AFL_BUCKET_BOUNDS.to_vec(),
+            lookup: COUNT_CLASS_LOOKUP.to_vec(),
+        }
+    }
+
+    /// The AFL buckets, extended with one bucket per power of two above `128`, up to `bits` buckets.
+    /// Use `16` for `u16` maps and `32` for `u32` maps.
+    #[must_use]
+    pub fn with_bits(bits: usize) -> Self {
+        let mut bounds = AFL_BUCKET_BOUNDS.to_vec();
+        while bounds.len() < bits.min(64) {
+            bounds.push(bounds[bounds.len() - 1] * 2);
+        }
+        Self::new(bounds).unwrap()
+    }
+
+    /// The lowest count of each bucket
+    #[must_use]
+    pub fn bounds(&self) -> &[u64] {
+        &self.bounds
+    }
+
+    /// Classify a count: `0` stays `0`, counts in the `i`-th bucket become `1 << i`
+    #[inline]
+    #[must_use]
+    pub fn classify(&self, count: u64) -> u64 {
+        match self.bounds.iter().rposition(|bound| *bound <= count) {
+            Some(bucket) => 1 << bucket,
+            None => 0,
+        }
+    }
+
+    /// Classify a count of a `u8` map with a table lookup
+    #[inline]
+    #[must_use]
+    pub fn classify_u8(&self, count: u8) -> u8 {
+        self.lookup[count as usize]
+    }
+}
+
+/// The entries of maps supported by the [`HitcountsMapObserver`]
+pub trait HitcountsEntry: PrimInt + Default + Debug + 'static {
+    /// The default buckets for this entry type
+    fn default_buckets() -> HitcountsBuckets;
+
+    /// Replace each count by its bucket of the [`HitcountsEntry::default_buckets`], without a table
+    fn classify_counts_default<'it, It>(entries: It)
+    where
+        It: Iterator<Item = &'it mut Self>;
+
+    /// Replace each count by its bucket
+    fn classify_counts<'it, It>(entries: It, buckets: &HitcountsBuckets)
+    where
+        It: Iterator<Item = &'it mut Self>;
+}
+
+impl HitcountsEntry for u8 {
+    fn default_buckets() -> HitcountsBuckets {
+        HitcountsBuckets::afl()
+    }
+
+    #[inline]
+    fn classify_counts_default<'it, It>(entries: It)
+    where
+        It: Iterator<Item = &'it mut Self>,
+    {
+        for elem in entries {
+            *elem = COUNT_CLASS_LOOKUP[*elem as usize];
+        }
+    }
+
+    #[inline]
+    fn classify_counts<'it, It>(entries: It, buckets: &HitcountsBuckets)
+    where
+        It: Iterator<Item = &'it mut Self>,
+    {
+        for elem in entries {
+            *elem = buckets.classify_u8(*elem);
+        }
+    }
+}
+
+macro_rules! impl_hitcounts_entry {
+    ($t:ty) => {
+        impl HitcountsEntry for $t {
+            fn default_buckets() -> HitcountsBuckets {
+                HitcountsBuckets::with_bits(<$t>::BITS as usize)
+            }
+
+            #[inline]
+            fn classify_counts_default<'it, It>(entries: It)
+            where
+                It: Iterator<Item = &'it mut Self>,
+            {
+                for elem in entries {
+                    *elem = match *elem {
+                        0..=2 => *elem,
+                        3 => 4,
+                        4..=7 => 8,
+                        8..=15 => 16,
+                        16..=31 => 32,
+                        32..=127 => 64,
+                        // One bucket per power of two from `128` on
+                        count => 1 << (<$t>::BITS - 1 - count.leading_zeros()),
+                    };
+                }
+            }
+
+            #[inline]
+            fn classify_counts<'it, It>(entries: It, buckets: &HitcountsBuckets)
+            where
+                It: Iterator<Item = &'it mut Self>,
+            {
+                for elem in entries {
+                    if *elem != 0 {
+                        // Bigger classes than the entry type are checked for in `with_buckets`
+                        *elem =
+                            <$t>::try_from(buckets.classify(u64::from(*elem))).unwrap_or(<$t>::MAX);
+                    }
+                }
+            }
+        }
+    };
+}
+
+impl_hitcounts_entry!(u16);
+impl_hitcounts_entry!(u32);
+impl_hitcounts_entry!(u64);
+
+/// Map observer with hitcounts postprocessing.
+/// `u8`, `u16`, `u32` and `u64` maps are supported, with the [`HitcountsBuckets`] of [`HitcountsEntry::default_buckets`]
+/// or custom ones.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(bound = "M: serde::de::DeserializeOwned")]
 pub struct HitcountsMapObserver<M>
@@ -776,6 +962,8 @@ where
     M: Serialize + serde::de::DeserializeOwned,
 {
     base: M,
+    /// The custom buckets, `None` for the default buckets, that are not serialized
+    buckets: Option<HitcountsBuckets>,
 }
 
 static COUNT_CLASS_LOOKUP: [u8; 256] = [
@@ -795,8 +983,9 @@ static COUNT_CLASS_LOOKUP: [u8; 256] = [
 
 impl<I, S, M> Observer<I, S> for HitcountsMapObserver<M>
 where
-    M: MapObserver<Entry = u8> + Observer<I, S>,
-    for<'it> M: AsMutIterator<'it, Item = u8>,
+    M: MapObserver + Observer<I, S>,
+    M::Entry: HitcountsEntry,
+    for<'it> M: AsMutIterator<'it, Item = M::Entry>,
 {
     #[inline]
     fn pre_exec(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
@@ -805,8 +994,9 @@ where
 
     #[inline]
     fn post_exec(&mut self, state: &mut S, input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
-        for elem in self.as_mut_iter() {
-            *elem = COUNT_CLASS_LOOKUP[*elem as usize];
+        match &self.buckets {
+            Some(buckets) => M::Entry::classify_counts(self.base.as_mut_iter(), buckets),
+            None => M::Entry::classify_counts_default(self.base.as_mut_iter()),
         }
         self.base.post_exec(state, input, exit_kind)
     }
@@ -834,18 +1024,19 @@ where
 
 impl<M> MapObserver for HitcountsMapObserver<M>
 where
-    M: MapObserver<Entry = u8>,
-    for<'it> M: AsMutIterator<'it, Item = u8>,
+    M: MapObserver,
+    M::Entry: HitcountsEntry,
+    for<'it> M: AsMutIterator<'it, Item = M::Entry>,
 {
-    type Entry = u8;
+    type Entry = M::Entry;
 
     #[inline]
-    fn initial(&self) -> u8 {
+    fn initial(&self) -> M::Entry {
         self.base.initial()
     }
 
     #[inline]
-    fn initial_mut(&mut self) -> &mut u8 {
+    fn initial_mut(&mut self) -> &mut M::Entry {
         self.base.initial_mut()
     }
 
@@ -855,57 +1046,106 @@ where
     }
 
     #[inline]
-    fn get(&self, idx: usize) -> &u8 {
+    fn get(&self, idx: usize) -> &M::Entry {
         self.base.get(idx)
     }
 
     #[inline]
-    fn get_mut(&mut self, idx: usize) -> &mut u8 {
+    fn get_mut(&mut self, idx: usize) -> &mut M::Entry {
         self.base.get_mut(idx)
     }
 
+    #[inline]
+    fn count_bytes(&self) -> u64 {
+        self.base.count_bytes()
+    }
+
     fn hash(&self) -> u64 {
         self.base.hash()
     }
-    fn to_vec(&self) -> Vec<u8> {
+
+    #[inline]
+    fn reset_map(&mut self) -> Result<(), Error> {
+        self.base.reset_map()
+    }
+
+    fn to_vec(&self) -> Vec<M::Entry> {
         self.base.to_vec()
     }
+
+    fn how_many_set(&self, indexes: &[usize]) -> usize {
+        self.base.how_many_set(indexes)
+    }
+
+    #[inline]
+    fn for_each_touched<F>(&self, f: F)
+    where
+        F: FnMut(usize, M::Entry),
+    {
+        self.base.for_each_touched(f);
+    }
 }
 
-impl<M> AsSlice<u8> for HitcountsMapObserver<M>
+impl<M, T> AsSlice<T> for HitcountsMapObserver<M>
 where
-    M: MapObserver + AsSlice<u8>,
+    M: MapObserver + AsSlice<T>,
 {
     #[inline]
-    fn as_slice(&self) -> &[u8] {
+    fn as_slice(&self) -> &[T] {
         self.base.as_slice()
     }
 }
-impl<M> AsMutSlice<u8> for HitcountsMapObserver<M>
+impl<M, T> AsMutSlice<T> for HitcountsMapObserver<M>
 where
-    M: MapObserver + AsMutSlice<u8>,
+    M: MapObserver + AsMutSlice<T>,
 {
     #[inline]
-    fn as_mut_slice(&mut self) -> &mut [u8] {
+    fn as_mut_slice(&mut self) -> &mut [T] {
         self.base.as_mut_slice()
     }
 }
 
 impl<M> HitcountsMapObserver<M>
 where
-    M: Serialize + serde::de::DeserializeOwned,
+    M: MapObserver,
+    M::Entry: HitcountsEntry,
 {
-    /// Creates a new [`MapObserver`]
+    /// Creates a new [`MapObserver`], with the default buckets of the map entries
     pub fn new(base: M) -> Self {
-        Self { base }
+        Self {
+            base,
+            buckets: None,
+        }
+    }
+
+    /// Creates a new [`MapObserver`], with custom buckets.
+    /// Fails if the classes of the buckets do not fit the map entries.
+    pub fn with_buckets(base: M, buckets: HitcountsBuckets) -> Result<Self, Error> {
+        let max = M::Entry::max_value().to_u64().unwrap();
+        if buckets.classify(max) > max {
+            return Err(Error::IllegalArgument(format!(
+                "Hitcount buckets classify counts up to {}, more than the map entries can hold",
+                buckets.classify(max)
+            )));
+        }
+        Ok(Self {
+            base,
+            buckets: Some(buckets),
+        })
+    }
+
+    /// The custom buckets used to classify the counts, `None` for the [`HitcountsEntry::default_buckets`]
+    #[must_use]
+    pub fn buckets(&self) -> Option<&HitcountsBuckets> {
+        self.buckets.as_ref()
     }
 }
 
 impl<'it, M> AsRefIterator<'it> for HitcountsMapObserver<M>
 where
-    M: Named + Serialize + serde::de::DeserializeOwned + AsRefIterator<'it, Item = u8>,
+    M: Named + Serialize + serde::de::DeserializeOwned + AsRefIterator<'it>,
 {
-    type Item = u8;
+    type Item = <M as AsRefIterator<'it>>::Item;
     type IntoIter = <M as AsRefIterator<'it>>::IntoIter;
 
     fn as_ref_iter(&'it self) -> Self::IntoIter {
@@ -915,9 +1155,9 @@ where
 
 impl<'it, M> AsMutIterator<'it> for HitcountsMapObserver<M>
 where
-    M: Named + Serialize + serde::de::DeserializeOwned + AsMutIterator<'it, Item = u8>,
+    M: Named + Serialize + serde::de::DeserializeOwned + AsMutIterator<'it>,
 {
-    type Item = u8;
+    type Item = <M as AsMutIterator<'it>>::Item;
     type IntoIter = <M as AsMutIterator<'it>>::IntoIter;
 
     fn as_mut_iter(&'it mut self) -> Self::IntoIter {
@@ -928,9 +1168,9 @@ where
 impl<'it, M> IntoIterator for &'it HitcountsMapObserver<M>
 where
     M: Named + Serialize + serde::de::DeserializeOwned,
-    &'it M: IntoIterator<Item = &'it u8>,
+    &'it M: IntoIterator,
 {
-    type Item = &'it u8;
+    type Item = <&'it M as IntoIterator>::Item;
     type IntoIter = <&'it M as IntoIterator>::IntoIter;
 
     fn into_iter(self) -> Self::IntoIter {
@@ -941,9 +1181,9 @@ where
 impl<'it, M> IntoIterator for &'it mut HitcountsMapObserver<M>
 where
     M: Named + Serialize + serde::de::DeserializeOwned,
-    &'it mut M: IntoIterator<Item = &'it mut u8>,
+    &'it mut M: IntoIterator,
 {
-    type Item = &'it mut u8;
+    type Item = <&'it mut M as IntoIterator>::Item;
     type IntoIter = <&'it mut M as IntoIterator>::IntoIter;
 
     fn into_iter(self) -> Self::IntoIter {
@@ -1635,3 +1875,280 @@ pub mod pybind {
         Ok(())
     }
 }
+
+/// A sparse map observer, backed by a hash map of the touched entries.
+/// Use it for huge coverage spaces, like path-sensitive coverage or call stack hashes:
+/// resetting, hashing and evaluating the map only walks the touched entries.
+/// Untouched entries have the initial value.
+/// Pair it with a sparse [`crate::feedbacks::MapFeedbackState`], that also only stores the entries seen so far.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound = "T: serde::de::DeserializeOwned")]
+pub struct SparseMapObserver<'a, T>
+where
+    T: PrimInt + Default + Copy + 'static + Serialize + serde::de::DeserializeOwned,
+{
+    map: OwnedRefMut<'a, HashMap<usize, T>>,
+    size: usize,
+    initial: T,
+    name: String,
+}
+
+impl<'a, I, S, T> Observer<I, S> for SparseMapObserver<'a, T>
+where
+    T: PrimInt + Default + Copy + 'static + Serialize + serde::de::DeserializeOwned + Debug,
+{
+    #[inline]
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.reset_map()
+    }
+}
+
+impl<'a, T> Named for SparseMapObserver<'a, T>
+where
+    T: PrimInt + Default + Copy + 'static + Serialize + serde::de::DeserializeOwned,
+{
+    #[inline]
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl<'a, T> HasLen for SparseMapObserver<'a, T>
+where
+    T: PrimInt + Default + Copy + 'static + Serialize + serde::de::DeserializeOwned,
+{
+    /// The size of the index space, not the number of touched entries
+    #[inline]
+    fn len(&self) -> usize {
+        self.size
+    }
+}
+
+impl<'a, 'it, T> AsMutIterator<'it> for SparseMapObserver<'a, T>
+where
+    T: PrimInt + Default + Copy + 'static + Serialize + serde::de::DeserializeOwned + Debug,
+{
+    type Item = T;
+    type IntoIter = ValuesMut<'it, usize, T>;
+
+    /// Iterate over the touched entries only
+    fn as_mut_iter(&'it mut self) -> Self::IntoIter {
+        self.map.as_mut().values_mut()
+    }
+}
+
+impl<'a, 'it, T> AsRefIterator<'it> for SparseMapObserver<'a, T>
+where
+    T: PrimInt + Default + Copy + 'static + Serialize + serde::de::DeserializeOwned + Debug,
+{
+    type Item = T;
+    type IntoIter = Values<'it, usize, T>;
+
+    /// Iterate over the touched entries only, use [`MapObserver::for_each_touched`] to get their indexes
+    fn as_ref_iter(&'it self) -> Self::IntoIter {
+        self.map.as_ref().values()
+    }
+}
+
+impl<'a, T> MapObserver for SparseMapObserver<'a, T>
+where
+    T: PrimInt + Default + Copy + 'static + Serialize + serde::de::DeserializeOwned + Debug,
+{
+    type Entry = T;
+
+    #[inline]
+    fn get(&self, idx: usize) -> &T {
+        self.map.as_ref().get(&idx).unwrap_or(&self.initial)
+    }
+
+    #[inline]
+    fn get_mut(&mut self, idx: usize) -> &mut T {
+        let initial = self.initial;
+        self.map.as_mut().entry(idx).or_insert(initial)
+    }
+
+    #[inline]
+    fn usable_count(&self) -> usize {
+        self.size
+    }
+
+    fn count_bytes(&self) -> u64 {
+        let initial = self.initial;
+        self.map
+            .as_ref()
+            .values()
+            .filter(|x| **x != initial)
+            .count() as u64
+    }
+
+    fn hash(&self) -> u64 {
+        let initial = self.initial;
+        let mut entries: Vec<(usize, T)> = self
+            .map
+            .as_ref()
+            .iter()
+            .filter(|(_, x)| **x != initial)
+            .map(|(idx, x)| (*idx, *x))
+            .collect();
+        entries.sort_unstable_by_key(|(idx, _)| *idx);
+        let mut hasher = AHasher::new_with_keys(0, 0);
+        for (idx, x) in entries {
+            hasher.write_usize(idx);
+            hasher.write_u64(x.to_u64().unwrap_or_default());
+        }
+        hasher.finish()
+    }
+
+    #[inline]
+    fn initial(&self) -> T {
+        self.initial
+    }
+
+    #[inline]
+    fn initial_mut(&mut self) -> &mut T {
+        &mut self.initial
+    }
+
+    #[inline]
+    fn reset_map(&mut self) -> Result<(), Error> {
+        self.map.as_mut().clear();
+        Ok(())
+    }
+
+    /// Get the dense contents of the map, this allocates the whole index space
+    fn to_vec(&self) -> Vec<T> {
+        let mut res = vec![self.initial; self.size];
+        for (idx, x) in self.map.as_ref() {
+            if *idx < self.size {
+                res[*idx] = *x;
+            }
+        }
+        res
+    }
+
+    fn how_many_set(&self, indexes: &[usize]) -> usize {
+        indexes
+            .iter()
+            .filter(|idx| **idx < self.size && *self.get(**idx) != self.initial)
+            .count()
+    }
+
+    fn for_each_touched<F>(&self, mut f: F)
+    where
+        F: FnMut(usize, T),
+    {
+        for (idx, x) in self.map.as_ref() {
+            f(*idx, *x);
+        }
+    }
+}
+
+impl<'a, T> SparseMapObserver<'a, T>
+where
+    T: PrimInt + Default + Copy + 'static + Serialize + serde::de::DeserializeOwned,
+{
+    /// Creates a new [`SparseMapObserver`] for the indexes up to `size`, for example `usize::MAX`
+    #[must_use]
+    pub fn new(name: &'static str, map: &'a mut HashMap<usize, T>, size: usize) -> Self {
+        Self {
+            map: OwnedRefMut::Ref(map),
+            size,
+            initial: T::default(),
+            name: name.to_string(),
+        }
+    }
+
+    /// Creates a new [`SparseMapObserver`] with an owned map
+    #[must_use]
+    pub fn new_owned(name: &'static str, size: usize) -> Self {
+        Self {
+            map: OwnedRefMut::Owned(Box::new(HashMap::new())),
+            size,
+            initial: T::default(),
+            name: name.to_string(),
+        }
+    }
+
+    /// The touched entries
+    #[must_use]
+    pub fn map(&self) -> &HashMap<usize, T> {
+        self.map.as_ref()
+    }
+
+    /// The touched entries (mutable), for targets that record their coverage through the observer
+    pub fn map_mut(&mut self) -> &mut HashMap<usize, T> {
+        self.map.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hashbrown::HashMap;
+
+    use crate::executors::ExitKind;
+    use crate::observers::{
+        HitcountsBuckets, HitcountsEntry, HitcountsMapObserver, MapObserver, Observer,
+        SparseMapObserver, StdMapObserver,
+    };
+
+    #[test]
+    fn test_hitcounts_buckets() {
+        let afl = HitcountsBuckets::afl();
+        assert_eq!(HitcountsBuckets::new(afl.bounds().to_vec()).unwrap(), afl);
+        assert_eq!(afl.classify(5), 8);
+        assert_eq!(afl.classify(300), 128);
+
+        let buckets = u16::default_buckets();
+        assert_eq!(buckets.classify(300), 256);
+        assert_eq!(buckets.classify(u64::from(u16::MAX)), 1 << 15);
+
+        // The default paths without a table classify like the default buckets
+        let mut counts: Vec<u8> = (0..=u8::MAX).collect();
+        u8::classify_counts_default(counts.iter_mut());
+        assert!((0..=u8::MAX).all(|c| u64::from(counts[c as usize]) == afl.classify(c.into())));
+        let mut counts: Vec<u16> = (0..=u16::MAX).collect();
+        u16::classify_counts_default(counts.iter_mut());
+        assert!((0..=u16::MAX).all(|c| u64::from(counts[c as usize]) == buckets.classify(c.into())));
+        let mut counts: Vec<u32> = vec![u32::MAX, 1 << 20 | 5];
+        u32::classify_counts_default(counts.iter_mut());
+        assert_eq!(counts, vec![1 << 31, 1 << 20]);
+
+        let mut map: [u16; 4] = [0, 3, 1000, 65535];
+        let mut observer = HitcountsMapObserver::new(StdMapObserver::new("map", &mut map));
+        observer.post_exec(&mut (), &(), &ExitKind::Ok).unwrap();
+        assert_eq!(observer.to_vec(), vec![0, 4, 512, 1 << 15]);
+        assert!(observer.buckets().is_none());
+
+        let mut map: [u8; 1] = [0];
+        assert!(HitcountsMapObserver::with_buckets(
+            StdMapObserver::new("map", &mut map),
+            HitcountsBuckets::new((1..10).collect()).unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_sparse_map() {
+        let mut map: HashMap<usize, u8> = HashMap::new();
+        map.insert(1 << 40, 3);
+        map.insert(7, 1);
+        let mut observer =
+            HitcountsMapObserver::new(SparseMapObserver::new("sparse", &mut map, usize::MAX));
+        observer.post_exec(&mut (), &(), &ExitKind::Ok).unwrap();
+        assert_eq!(*observer.get(1 << 40), 4);
+        assert_eq!(*observer.get(8), 0);
+        assert_eq!(observer.count_bytes(), 2);
+        assert_eq!(observer.how_many_set(&[7, 8, 1 << 40]), 2);
+
+        let mut touched = vec![];
+        observer.for_each_touched(|idx, x| touched.push((idx, x)));
+        touched.sort_unstable();
+        assert_eq!(touched, vec![(7, 1), (1 << 40, 4)]);
+
+        let hash = observer.hash();
+        *observer.get_mut(9) = 0;
+        assert_eq!(observer.hash(), hash);
+        observer.reset_map().unwrap();
+        assert_eq!(observer.count_bytes(), 0);
+    }
+}
@@ -16,6 +16,11 @@ pub mod stacktrace;
 #[cfg(feature = "std")]
 pub use stacktrace::*;
 
+#[cfg(feature = "std")]
+pub mod sanitizer;
+#[cfg(feature = "std")]
+pub use sanitizer::*;
+
 pub mod concolic;
 
 #[cfg(unstable_feature)]
@@ -182,6 +187,10 @@ pub trait ObserverWithHashField {
     fn update_hash(&mut self, hash: u64);
     /// clears the current value of the hash and sets it to None
     fn clear_hash(&mut self);
+    /// the address of the faulting instruction of the last crash, if the observer knows it
+    fn fault_address(&self) -> Option<u64> {
+        None
+    }
 }
 /// A simple observer, just overlooking the runtime of the target.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -0,0 +1,590 @@
+//! Parses the reports of the sanitizers (`ASan`, `UBSan`, `MSan`, `TSan` and `LSan`) into a [`SanitizerReport`],
+//! with the bug type, the memory access, the stack frames and the allocation and free stacks.
+//! The [`SanitizerReportObserver`] keeps the report of the last run, filled by the `CommandExecutor` from the
+//! `stderr` of the target, by the `ForkserverExecutor` from the sanitizer log file, or from the log file of an
+//! in-process harness. The [`crate::feedbacks::SanitizerReportFeedback`] adds it to the objectives as metadata.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{fs, io::ErrorKind, path::Path, process};
+
+use crate::{
+    bolts::tuples::Named,
+    executors::ExitKind,
+    observers::{Observer, ASAN_LOG_PATH},
+    Error,
+};
+
+/// The sanitizer that reported a bug
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sanitizer {
+    /// `AddressSanitizer`
+    Address,
+    /// `UndefinedBehaviorSanitizer`
+    UndefinedBehavior,
+    /// `MemorySanitizer`
+    Memory,
+    /// `ThreadSanitizer`
+    Thread,
+    /// `LeakSanitizer`
+    Leak,
+}
+
+impl Sanitizer {
+    /// The sanitizer of the given name, as in the reports, i.e. `AddressSanitizer`
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "AddressSanitizer" => Some(Sanitizer::Address),
+            "UndefinedBehaviorSanitizer" => Some(Sanitizer::UndefinedBehavior),
+            "MemorySanitizer" => Some(Sanitizer::Memory),
+            "ThreadSanitizer" => Some(Sanitizer::Thread),
+            "LeakSanitizer" => Some(Sanitizer::Leak),
+            _ => None,
+        }
+    }
+}
+
+/// The kind of a faulting memory access
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAccess {
+    /// A read
+    Read,
+    /// A write
+    Write,
+}
+
+/// A frame of a stack trace of a [`SanitizerReport`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    /// The index in the stack, `0` is the innermost frame
+    pub index: usize,
+    /// The program counter, not printed by `TSan`
+    pub pc: Option<u64>,
+    /// The function, if symbolized
+    pub function: Option<String>,
+    /// The source location or module and offset
+    pub location: Option<String>,
+}
+
+impl StackFrame {
+    /// Parses a frame line, i.e. `#0 0x4f4b1c in main /src/main.c:10:5`
+    #[must_use]
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim().strip_prefix('#')?;
+        let (index, mut rest) = line.split_once(' ')?;
+        let index = index.parse().ok()?;
+        rest = rest.trim_start();
+
+        let mut pc = None;
+        if let Some(hex) = rest.strip_prefix("0x") {
+            let (addr, tail) = hex.split_once(' ').unwrap_or((hex, ""));
+            pc = Some(u64::from_str_radix(addr, 16).ok()?);
+            rest = tail.trim_start();
+        }
+        rest = rest.strip_prefix("in ").unwrap_or(rest);
+
+        // The location is `file:line[:column]`, `(module+0xoffset)`, or both for `TSan`
+        let mut location = vec![];
+        let mut function = rest;
+        while let Some((head, tail)) = function.rsplit_once(' ') {
+            if location.len() < 2 && is_location(tail) {
+                location.insert(0, tail);
+                function = head.trim_end();
+            } else {
+                break;
+            }
+        }
+        if location.is_empty() && is_location(function) {
+            location.push(function);
+            function = "";
+        }
+
+        Some(Self {
+            index,
+            pc,
+            function: (!function.is_empty()).then(|| function.to_string()),
+            location: (!location.is_empty()).then(|| location.join(" ")),
+        })
+    }
+}
+
+/// If `token` is the location of a frame, i.e. `/src/main.c:10:5`, `main.c:10` or `(/out/fuzz+0x4e9f12)`
+fn is_location(token: &str) -> bool {
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+
+    if let Some(module) = token.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        return module.rsplit_once("+0x").map_or(false, |(module, offset)| {
+            !module.is_empty()
+                && !offset.is_empty()
+                && offset.chars().all(|c| c.is_ascii_hexdigit())
+        });
+    }
+    match token.rsplit_once(':') {
+        Some((rest, line)) if is_number(line) => match rest.rsplit_once(':') {
+            Some((path, column)) if is_number(column) => !path.is_empty(),
+            _ => !rest.is_empty(),
+        },
+        _ => false,
+    }
+}
+
+/// The stack of the report a frame belongs to
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Stack {
+    Crash,
+    Allocation,
+    Free,
+    Other,
+}
+
+/// A report of a sanitizer, parsed from its output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SanitizerReport {
+    /// The sanitizer reporting the bug
+    pub sanitizer: Sanitizer,
+    /// The type of bug, i.e. `heap-buffer-overflow`, or the `UBSan` runtime error
+    pub bug_type: String,
+    /// The kind of the faulting memory access
+    pub access: Option<MemoryAccess>,
+    /// The size of the faulting memory access
+    pub access_size: Option<usize>,
+    /// The faulting address
+    pub address: Option<u64>,
+    /// The faulting program counter
+    pub pc: Option<u64>,
+    /// The stack trace of the bug
+    pub frames: Vec<StackFrame>,
+    /// The stack trace of the allocation of the accessed memory, or of the origin of uninitialized memory
+    pub allocation_frames: Vec<StackFrame>,
+    /// The stack trace of the free of the accessed memory
+    pub free_frames: Vec<StackFrame>,
+    /// The shadow bytes around the faulting address, as printed by `ASan`
+    pub shadow_bytes: Vec<String>,
+    /// The summary line
+    pub summary: Option<String>,
+}
+
+crate::impl_serdeany!(SanitizerReport);
+
+/// Parses a hexadecimal address, with `0x` prefix
+fn parse_hex(hex: &str) -> Option<u64> {
+    u64::from_str_radix(hex.trim_start_matches("0x"), 16).ok()
+}
+
+impl SanitizerReport {
+    /// Parses the first report in the output of a sanitizer, `None` if there is none
+    #[must_use]
+    pub fn parse(output: &str) -> Option<Self> {
+        // `==1337==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011 at pc 0x4f4b1d bp ...`
+        let header =
+            Regex::new(r"(?m)^(?:==\d+==)?(?:ERROR|WARNING): (\w+Sanitizer): (.+)$").unwrap();
+        let ubsan = Regex::new(r"(?m)^\S+: runtime error: (.+)$").unwrap();
+        let address = Regex::new(r"\baddress (0x[0-9a-f]+)").unwrap();
+        let pc = Regex::new(r"\bpc (0x[0-9a-f]+)").unwrap();
+
+        let (mut report, start) = if let Some(m) = header.captures(output) {
+            let message = &m[2];
+            let bug_type = message
+                .split(" on ")
+                .next()
+                .and_then(|bug| bug.split(" (").next())
+                .unwrap_or(message);
+            let report = Self::new(
+                Sanitizer::from_name(&m[1])?,
+                bug_type.trim().to_string(),
+                address.captures(message).and_then(|a| parse_hex(&a[1])),
+                pc.captures(message).and_then(|pc| parse_hex(&pc[1])),
+            );
+            (report, m.get(0).unwrap().start())
+        } else if let Some(m) = ubsan.captures(output) {
+            let report = Self::new(Sanitizer::UndefinedBehavior, m[1].to_string(), None, None);
+            (report, m.get(0).unwrap().start())
+        } else {
+            return None;
+        };
+        let output = &output[start..];
+
+        // `READ of size 1 at 0x602000000011` for `ASan`, `Write of size 4 at 0x7b04...` for `TSan`
+        let access =
+            Regex::new(r"(?mi)^\s*(?:atomic )?(read|write) of size (\d+) at (0x[0-9a-f]+)")
+                .unwrap();
+        let signal_access =
+            Regex::new(r"The signal is caused by a (READ|WRITE) memory access").unwrap();
+        if let Some(m) = access.captures(output) {
+            report.access = Some(if m[1].eq_ignore_ascii_case("read") {
+                MemoryAccess::Read
+            } else {
+                MemoryAccess::Write
+            });
+            report.access_size = m[2].parse().ok();
+            report.address = report.address.or_else(|| parse_hex(&m[3]));
+        } else if let Some(m) = signal_access.captures(output) {
+            report.access = Some(if &m[1] == "READ" {
+                MemoryAccess::Read
+            } else {
+                MemoryAccess::Write
+            });
+        }
+
+        report.parse_stacks(output);
+        Some(report)
+    }
+
+    fn new(sanitizer: Sanitizer, bug_type: String, address: Option<u64>, pc: Option<u64>) -> Self {
+        Self {
+            sanitizer,
+            bug_type,
+            access: None,
+            access_size: None,
+            address,
+            pc,
+            frames: vec![],
+            allocation_frames: vec![],
+            free_frames: vec![],
+            shadow_bytes: vec![],
+            summary: None,
+        }
+    }
+
+    /// Sorts the frames into the stacks, and collects the shadow bytes and summary
+    fn parse_stacks(&mut self, output: &str) {
+        let mut stack = Stack::Crash;
+        let mut in_shadow = false;
+        for line in output.lines() {
+            if let Some(frame) = StackFrame::parse(line) {
+                match stack {
+                    Stack::Crash => self.frames.push(frame),
+                    Stack::Allocation => self.allocation_frames.push(frame),
+                    Stack::Free => self.free_frames.push(frame),
+                    Stack::Other => (),
+                }
+                continue;
+            }
+
+            if in_shadow {
+                if line.trim_start().starts_with("0x") || line.starts_with("=>") {
+                    self.shadow_bytes.push(line.trim().to_string());
+                    continue;
+                }
+                in_shadow = false;
+            }
+
+            if let Some(summary) = line.strip_prefix("SUMMARY: ") {
+                self.summary = Some(summary.to_string());
+            } else if line.starts_with("Shadow bytes around the buggy address") {
+                in_shadow = true;
+                stack = Stack::Other;
+            } else if line.contains("freed by thread") {
+                stack = if self.free_frames.is_empty() {
+                    Stack::Free
+                } else {
+                    Stack::Other
+                };
+            } else if line.contains("allocated by thread")
+                || line.contains("allocated from:")
+                || line.contains("was created by")
+            {
+                stack = if self.allocation_frames.is_empty() {
+                    Stack::Allocation
+                } else {
+                    Stack::Other
+                };
+            } else if stack != Stack::Crash || !self.frames.is_empty() {
+                // The end of a stack, or the other stacks of `TSan`
+                stack = Stack::Other;
+            }
+        }
+    }
+}
+
+/// An observer keeping the [`SanitizerReport`] of the last run.
+/// Executors of child processes fill it, the `CommandExecutor` from the `stderr` of the target,
+/// the `ForkserverExecutor` from the sanitizer log files.
+/// For in-process harnesses, set the `log_path` of the sanitizer options and create it [`SanitizerReportObserver::with_log_path`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SanitizerReportObserver {
+    observer_name: String,
+    log_path: Option<String>,
+    report: Option<SanitizerReport>,
+}
+
+impl SanitizerReportObserver {
+    /// Creates a new [`SanitizerReportObserver`] with the given name.
+    #[must_use]
+    pub fn new(observer_name: &str) -> Self {
+        Self {
+            observer_name: observer_name.to_string(),
+            log_path: None,
+            report: None,
+        }
+    }
+
+    /// Creates a new [`SanitizerReportObserver`] for an in-process harness, reading the report of a crash
+    /// from `<log_path>.<pid>`, where `log_path` is set in the sanitizer options, i.e. `ASAN_OPTIONS=log_path=./asanlog`.
+    #[must_use]
+    pub fn with_log_path(observer_name: &str, log_path: &str) -> Self {
+        Self {
+            observer_name: observer_name.to_string(),
+            log_path: Some(log_path.to_string()),
+            report: None,
+        }
+    }
+
+    /// The report of the last run, if any
+    #[must_use]
+    pub fn report(&self) -> &Option<SanitizerReport> {
+        &self.report
+    }
+
+    /// Parses the output of the sanitizer, i.e. the `stderr` of the target
+    pub fn parse_output(&mut self, output: &str) {
+        self.report = SanitizerReport::parse(output);
+    }
+
+    /// Parses the sanitizer log file of the process `pid`, `<log_path>.<pid>`, if it exists.
+    /// The log file is removed if `remove` is set.
+    pub fn parse_log_file(&mut self, log_path: &str, pid: i32, remove: bool) -> Result<(), Error> {
+        let path = format!("{}.{}", log_path, pid);
+        match fs::read_to_string(Path::new(&path)) {
+            Ok(output) => {
+                if remove {
+                    fs::remove_file(&path)?;
+                }
+                self.parse_output(&output);
+                Ok(())
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                self.report = None;
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl Default for SanitizerReportObserver {
+    fn default() -> Self {
+        Self::new("SanitizerReportObserver")
+    }
+}
+
+impl<I, S> Observer<I, S> for SanitizerReportObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.report = None;
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        if *exit_kind == ExitKind::Crash {
+            if let Some(log_path) = self.log_path.clone() {
+                #[allow(clippy::cast_possible_wrap)]
+                self.parse_log_file(&log_path, process::id() as i32, true)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Named for SanitizerReportObserver {
+    fn name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+/// returns the recommended runtime flags of `UBSan`, `MSan` and `TSan` to report to the log files read by the `ForkserverExecutor`
+#[must_use]
+pub fn get_sanitizers_runtime_flags_with_log_path() -> String {
+    let mut flags = String::from("abort_on_error=1:halt_on_error=1:log_path=");
+    flags.push_str(ASAN_LOG_PATH);
+    flags
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::observers::sanitizer::{MemoryAccess, Sanitizer, SanitizerReport, StackFrame};
+
+    const ASAN_HEAP_OVERFLOW: &str = "=================================================================
+==1337==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011 at pc 0x0000004f4b1d bp 0x7ffd5ae1b3f0 sp 0x7ffd5ae1b3e8
+WRITE of size 1 at 0x602000000011 thread T0
+    #0 0x4f4b1c in LLVMFuzzerTestOneInput /src/fuzz.c:10:5
+    #1 0x4e9f12 in main (/out/fuzz+0x4e9f12)
+
+0x602000000011 is located 0 bytes to the right of 1-byte region [0x602000000010,0x602000000011)
+allocated by thread T0 here:
+    #0 0x4a0b2d in malloc (/out/fuzz+0x4a0b2d)
+    #1 0x4f4a9e in LLVMFuzzerTestOneInput /src/fuzz.c:8:15
+
+SUMMARY: AddressSanitizer: heap-buffer-overflow /src/fuzz.c:10:5 in LLVMFuzzerTestOneInput
+Shadow bytes around the buggy address:
+  0x0c047fff7fb0: 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+=>0x0c047fff8000: fa fa[01]fa fa fa fa fa fa fa fa fa fa fa fa fa
+Shadow byte legend (one shadow byte represents 8 application bytes):
+==1337==ABORTING
+";
+
+    const ASAN_UAF: &str = "==7==ERROR: AddressSanitizer: heap-use-after-free on address 0x602000000010 at pc 0x0000004f4b1d bp 0x7ffd sp 0x7ffd
+READ of size 4 at 0x602000000010 thread T0
+    #0 0x4f4b1c in parse(char const*, unsigned long) /src/parse.cc:42:7
+
+freed by thread T0 here:
+    #0 0x4a0c3d in free (/out/fuzz+0x4a0c3d)
+    #1 0x4f4a00 in cleanup /src/parse.cc:30:3
+
+previously allocated by thread T0 here:
+    #0 0x4a0b2d in malloc (/out/fuzz+0x4a0b2d)
+
+SUMMARY: AddressSanitizer: heap-use-after-free /src/parse.cc:42:7 in parse(char const*, unsigned long)
+";
+
+    #[test]
+    fn test_asan_report() {
+        let report = SanitizerReport::parse(ASAN_HEAP_OVERFLOW).unwrap();
+        assert_eq!(report.sanitizer, Sanitizer::Address);
+        assert_eq!(report.bug_type, "heap-buffer-overflow");
+        assert_eq!(report.access, Some(MemoryAccess::Write));
+        assert_eq!(report.access_size, Some(1));
+        assert_eq!(report.address, Some(0x6020_0000_0011));
+        assert_eq!(report.pc, Some(0x4f_4b1d));
+        assert_eq!(report.frames.len(), 2);
+        assert_eq!(
+            report.frames[0],
+            StackFrame {
+                index: 0,
+                pc: Some(0x4f_4b1c),
+                function: Some("LLVMFuzzerTestOneInput".to_string()),
+                location: Some("/src/fuzz.c:10:5".to_string()),
+            }
+        );
+        assert_eq!(
+            report.frames[1].location.as_deref(),
+            Some("(/out/fuzz+0x4e9f12)")
+        );
+        assert_eq!(report.allocation_frames.len(), 2);
+        assert!(report.free_frames.is_empty());
+        assert_eq!(report.shadow_bytes.len(), 2);
+        assert!(report.shadow_bytes[1].contains("[01]"));
+        assert!(report.summary.unwrap().starts_with("AddressSanitizer"));
+
+        let report = SanitizerReport::parse(ASAN_UAF).unwrap();
+        assert_eq!(report.bug_type, "heap-use-after-free");
+        assert_eq!(report.access, Some(MemoryAccess::Read));
+        assert_eq!(report.access_size, Some(4));
+        assert_eq!(
+            report.frames[0].function.as_deref(),
+            Some("parse(char const*, unsigned long)")
+        );
+        assert_eq!(report.free_frames.len(), 2);
+        assert_eq!(report.allocation_frames.len(), 1);
+
+        let report = SanitizerReport::parse(
+            "==1==ERROR: AddressSanitizer: SEGV on unknown address 0x000000000000 (pc 0x0000004f4b1d bp 0x7ffd sp 0x7ffd T0)
+==1==The signal is caused by a WRITE memory access.
+    #0 0x4f4b1c in main /src/main.c:3:8
+",
+        )
+        .unwrap();
+        assert_eq!(report.bug_type, "SEGV");
+        assert_eq!(report.address, Some(0));
+        assert_eq!(report.pc, Some(0x4f_4b1d));
+        assert_eq!(report.access, Some(MemoryAccess::Write));
+        assert_eq!(report.frames.len(), 1);
+    }
+
+    #[test]
+    fn test_stack_frame() {
+        let frame = StackFrame::parse("#3 0x4f4b1c in f(int, std::string) /a.cc:1:2").unwrap();
+        assert_eq!(frame.index, 3);
+        assert_eq!(frame.function.as_deref(), Some("f(int, std::string)"));
+        assert_eq!(frame.location.as_deref(), Some("/a.cc:1:2"));
+
+        let frame =
+            StackFrame::parse("#0 0x4a in std::map<int, int>::at(int const&) (/out/fuzz+0x4a)")
+                .unwrap();
+        assert_eq!(
+            frame.function.as_deref(),
+            Some("std::map<int, int>::at(int const&)")
+        );
+        assert_eq!(frame.location.as_deref(), Some("(/out/fuzz+0x4a)"));
+
+        let frame = StackFrame::parse("#1 0x4e9f12 (/out/fuzz+0x4e9f12)").unwrap();
+        assert_eq!(frame.function, None);
+        assert_eq!(frame.location.as_deref(), Some("(/out/fuzz+0x4e9f12)"));
+
+        // Unsymbolized C++ frames without location keep their whole name
+        let frame = StackFrame::parse("#2 0x4f in ns::f(ns::T)").unwrap();
+        assert_eq!(frame.function.as_deref(), Some("ns::f(ns::T)"));
+        assert_eq!(frame.location, None);
+
+        let frame = StackFrame::parse("#0 worker /src/race.c:3 (race+0x4a1b2c)").unwrap();
+        assert_eq!(frame.pc, None);
+        assert_eq!(frame.function.as_deref(), Some("worker"));
+        assert_eq!(
+            frame.location.as_deref(),
+            Some("/src/race.c:3 (race+0x4a1b2c)")
+        );
+    }
+
+    #[test]
+    fn test_other_sanitizer_reports() {
+        let report = SanitizerReport::parse(
+            "/src/int.c:12:7: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'
+    #0 0x4f4b1c in add /src/int.c:12:7
+SUMMARY: UndefinedBehaviorSanitizer: undefined-behavior /src/int.c:12:7 in
+",
+        )
+        .unwrap();
+        assert_eq!(report.sanitizer, Sanitizer::UndefinedBehavior);
+        assert!(report.bug_type.starts_with("signed integer overflow"));
+        assert_eq!(report.frames.len(), 1);
+
+        let report = SanitizerReport::parse(
+            "==42==WARNING: MemorySanitizer: use-of-uninitialized-value
+    #0 0x4f4b1c in main /src/main.c:5:9
+
+  Uninitialized value was created by a heap allocation
+    #0 0x4a0b2d in malloc (/out/fuzz+0x4a0b2d)
+    #1 0x4f4a9e in main /src/main.c:3:13
+
+SUMMARY: MemorySanitizer: use-of-uninitialized-value /src/main.c:5:9 in main
+",
+        )
+        .unwrap();
+        assert_eq!(report.sanitizer, Sanitizer::Memory);
+        assert_eq!(report.bug_type, "use-of-uninitialized-value");
+        assert_eq!(report.frames.len(), 1);
+        assert_eq!(report.allocation_frames.len(), 2);
+
+        let report = SanitizerReport::parse(
+            "==================
+WARNING: ThreadSanitizer: data race (pid=1234)
+  Write of size 4 at 0x7b0400000000 by thread T1:
+    #0 worker /src/race.c:3 (race+0x4a1b2c)
+
+  Previous read of size 4 at 0x7b0400000000 by main thread:
+    #0 main /src/race.c:9 (race+0x4a1c3d)
+
+SUMMARY: ThreadSanitizer: data race /src/race.c:3 in worker
+",
+        )
+        .unwrap();
+        assert_eq!(report.sanitizer, Sanitizer::Thread);
+        assert_eq!(report.bug_type, "data race");
+        assert_eq!(report.access, Some(MemoryAccess::Write));
+        assert_eq!(report.access_size, Some(4));
+        assert_eq!(report.address, Some(0x7b04_0000_0000));
+        assert_eq!(report.frames.len(), 1);
+        assert_eq!(report.frames[0].pc, None);
+        assert_eq!(report.frames[0].function.as_deref(), Some("worker"));
+        assert_eq!(
+            report.frames[0].location.as_deref(),
+            Some("/src/race.c:3 (race+0x4a1b2c)")
+        );
+
+        assert!(SanitizerReport::parse("no report here").is_none());
+    }
+}
@@ -167,6 +167,7 @@ pub fn get_asan_runtime_flags() -> String {
 pub struct ASANBacktraceObserver {
     observer_name: String,
     hash: Option<u64>,
+    fault_address: Option<u64>,
 }
 
 impl ASANBacktraceObserver {
@@ -176,6 +177,7 @@ impl ASANBacktraceObserver {
         Self {
             observer_name: observer_name.to_string(),
             hash: None,
+            fault_address: None,
         }
     }
 
@@ -212,6 +214,14 @@ impl ASANBacktraceObserver {
             hash ^= u64::from_str_radix(g.as_str(), 16).unwrap();
         });
         self.update_hash(hash);
+
+        // The faulting pc is part of the report header, i.e. `ERROR: AddressSanitizer: SEGV on ... (pc 0x55d1 bp ...`
+        // or `ERROR: AddressSanitizer: heap-buffer-overflow on address 0x6020 at pc 0x55d1 bp ...`
+        let pc_matcher =
+            Regex::new("ERROR: AddressSanitizer:.*(?:at |\\()pc 0x([0-9a-f]+)").unwrap();
+        self.fault_address = pc_matcher
+            .captures(output)
+            .and_then(|m| u64::from_str_radix(m.get(1).unwrap().as_str(), 16).ok());
     }
 }
 
@@ -230,6 +240,12 @@ impl ObserverWithHashField for ASANBacktraceObserver {
     /// Clears the current hash value
     fn clear_hash(&mut self) {
         self.hash = None;
+        self.fault_address = None;
+    }
+
+    /// The faulting pc ASAN reported for the last crash
+    fn fault_address(&self) -> Option<u64> {
+        self.fault_address
     }
 }
 
@@ -32,3 +32,22 @@ where
         Ok(entry.exec_time().map_or(1, |d| d.as_millis()) as u64 * entry.cached_len()? as u64)
     }
 }
+
+/// Rate all testcases the same.
+/// Useful where only the number of testcases matters, such as unweighted corpus minimization.
+#[derive(Debug, Clone)]
+pub struct UniformFavFactor<I>
+where
+    I: Input,
+{
+    phantom: PhantomData<I>,
+}
+
+impl<I> FavFactor<I> for UniformFavFactor<I>
+where
+    I: Input,
+{
+    fn compute(_entry: &mut Testcase<I>) -> Result<u64, Error> {
+        Ok(1)
+    }
+}
@@ -0,0 +1,325 @@
+//! The [`AflStatsStage`] writes an `AFL++`-compatible `fuzzer_stats` file and `plot_data`,
+//! so that tools like `afl-whatsup` and `afl-plot` work on `LibAFL` output directories.
+
+use core::{marker::PhantomData, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::{
+    bolts::current_time,
+    corpus::Corpus,
+    executors::ExitKind,
+    inputs::Input,
+    stages::Stage,
+    state::{HasClientPerfMonitor, HasCorpus, HasExecutions, HasMetadata, HasSolutions},
+    Error,
+};
+
+/// The default interval between two updates of the `fuzzer_stats` file, as in `AFL++`
+pub const AFL_STATS_UPDATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The header of the `plot_data` file, as written by `AFL++`
+pub const AFL_PLOT_DATA_HEADER: &str = "# relative_time, cycles_done, cur_item, corpus_count, pending_total, pending_favs, map_size, saved_crashes, saved_hangs, max_depth, execs_per_sec, total_execs, edges_found";
+
+/// Metadata used by the [`AflStatsStage`] to keep track of the fuzzing campaign across restarts
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AflStatsMetadata {
+    /// When fuzzing started
+    pub start_time: Duration,
+    /// When the last `fuzzer_stats` file was written
+    pub last_update: Duration,
+    /// When the last new corpus entry was found
+    pub last_find: Duration,
+    /// The size of the corpus when the `fuzzer_stats` file was last written
+    pub last_corpus_count: usize,
+}
+
+crate::impl_serdeany!(AflStatsMetadata);
+
+/// A stage that periodically writes an `AFL++`-compatible `fuzzer_stats` file to the output directory,
+/// and appends a line to its `plot_data`.
+/// All keys of `AFL++`, and the older keys still read by some versions of `afl-whatsup`, are written.
+/// Values `LibAFL` does not track, such as the cycles or the map density, are written as `0`.
+#[derive(Debug)]
+pub struct AflStatsStage<E, EM, I, S, Z>
+where
+    I: Input,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasSolutions<I> + HasExecutions + HasMetadata,
+{
+    stats_file: PathBuf,
+    plot_file: PathBuf,
+    banner: String,
+    interval: Duration,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, I, S, Z)>,
+}
+
+impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for AflStatsStage<E, EM, I, S, Z>
+where
+    I: Input,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasSolutions<I> + HasExecutions + HasMetadata,
+{
+    #[inline]
+    #[allow(clippy::cast_precision_loss)]
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        _manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let now = current_time();
+        let corpus_count = state.corpus().count();
+
+        if state.metadata().get::<AflStatsMetadata>().is_none() {
+            state.add_metadata(AflStatsMetadata {
+                start_time: now,
+                last_update: Duration::from_secs(0),
+                last_find: Duration::from_secs(0),
+                last_corpus_count: corpus_count,
+            });
+        }
+        let meta = state.metadata_mut().get_mut::<AflStatsMetadata>().unwrap();
+        if corpus_count > meta.last_corpus_count {
+            meta.last_find = now;
+            meta.last_corpus_count = corpus_count;
+        }
+        if now.saturating_sub(meta.last_update) < self.interval {
+            return Ok(());
+        }
+        meta.last_update = now;
+        let (start_time, last_find) = (meta.start_time, meta.last_find);
+
+        let mut saved_crashes = 0;
+        let mut saved_hangs = 0;
+        for idx in 0..state.solutions().count() {
+            let solution = state.solutions().get(idx)?.borrow();
+            if solution.metadata().get::<ExitKind>() == Some(&ExitKind::Timeout) {
+                saved_hangs += 1;
+            } else {
+                saved_crashes += 1;
+            }
+        }
+
+        let run_time = now.saturating_sub(start_time).as_secs();
+        let execs_done = *state.executions();
+        let execs_per_sec = if run_time == 0 {
+            0.0
+        } else {
+            execs_done as f64 / run_time as f64
+        };
+
+        let execs_per_sec = format!("{:.2}", execs_per_sec);
+        let command_line = env::args().collect::<Vec<_>>().join(" ");
+        let mut content = String::new();
+        let mut line = |key: &str, value: &dyn core::fmt::Display| {
+            writeln!(content, "{:<18}: {}", key, value).unwrap();
+        };
+        line("start_time", &start_time.as_secs());
+        line("last_update", &now.as_secs());
+        line("run_time", &run_time);
+        line("fuzzer_pid", &process::id());
+        line("cycles_done", &0);
+        line("cycles_wo_finds", &0);
+        line("time_wo_finds", &now.saturating_sub(last_find).as_secs());
+        line("execs_done", &execs_done);
+        line("execs_per_sec", &execs_per_sec);
+        line("execs_ps_last_min", &execs_per_sec);
+        line("corpus_count", &corpus_count);
+        line("corpus_favored", &0);
+        line("corpus_found", &corpus_count);
+        line("corpus_imported", &0);
+        line("corpus_variable", &0);
+        line("max_depth", &0);
+        line("cur_item", &corpus_idx);
+        line("pending_favs", &0);
+        line("pending_total", &0);
+        line("stability", &"0.00%");
+        line("bitmap_cvg", &"0.00%");
+        line("saved_crashes", &saved_crashes);
+        line("saved_hangs", &saved_hangs);
+        line("last_find", &last_find.as_secs());
+        line("last_crash", &0);
+        line("last_hang", &0);
+        line("execs_since_crash", &0);
+        line("exec_timeout", &0);
+        line("slowest_exec_ms", &0);
+        line("peak_rss_mb", &0);
+        line("cpu_affinity", &0);
+        line("edges_found", &0);
+        line("total_edges", &0);
+        line("var_byte_count", &0);
+        line("havoc_expansion", &0);
+        line("auto_dict_entries", &0);
+        line("testcache_size", &0);
+        line("testcache_count", &0);
+        line("testcache_evict", &0);
+        // Read by older versions of `afl-whatsup`
+        line("paths_total", &corpus_count);
+        line("paths_favored", &0);
+        line("paths_found", &corpus_count);
+        line("paths_imported", &0);
+        line("cur_path", &corpus_idx);
+        line("unique_crashes", &saved_crashes);
+        line("unique_hangs", &saved_hangs);
+        line("last_path", &last_find.as_secs());
+        line("afl_banner", &self.banner);
+        line(
+            "afl_version",
+            &concat!("libafl-", env!("CARGO_PKG_VERSION")),
+        );
+        line("target_mode", &"default");
+        line("command_line", &command_line);
+
+        // Write to a temporary file first, so that readers never see a partial file
+        let tmp_file = self.stats_file.with_extension("tmp");
+        fs::write(&tmp_file, content)?;
+        fs::rename(&tmp_file, &self.stats_file)?;
+
+        let new_plot = !self.plot_file.exists();
+        let mut plot = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.plot_file)?;
+        if new_plot {
+            writeln!(plot, "{}", AFL_PLOT_DATA_HEADER)?;
+        }
+        writeln!(
+            plot,
+            "{}, 0, {}, {}, 0, 0, 0.00%, {}, {}, 0, {:.2}, {}, 0",
+            run_time,
+            corpus_idx,
+            corpus_count,
+            saved_crashes,
+            saved_hangs,
+            execs_per_sec,
+            execs_done
+        )?;
+
+        Ok(())
+    }
+}
+
+impl<E, EM, I, S, Z> AflStatsStage<E, EM, I, S, Z>
+where
+    I: Input,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasSolutions<I> + HasExecutions + HasMetadata,
+{
+    /// Creates a new [`AflStatsStage`], writing `out_dir/fuzzer_stats` and `out_dir/plot_data` every [`AFL_STATS_UPDATE_INTERVAL`]
+    #[must_use]
+    pub fn new<P>(out_dir: P, banner: &str) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::with_interval(out_dir, banner, AFL_STATS_UPDATE_INTERVAL)
+    }
+
+    /// Creates a new [`AflStatsStage`], writing `out_dir/fuzzer_stats` and `out_dir/plot_data` every `interval`
+    #[must_use]
+    pub fn with_interval<P>(out_dir: P, banner: &str, interval: Duration) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            stats_file: out_dir.as_ref().join("fuzzer_stats"),
+            plot_file: out_dir.as_ref().join("plot_data"),
+            banner: banner.to_string(),
+            interval,
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use hashbrown::HashMap;
+    use std::{env, fs};
+
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        inputs::BytesInput,
+        stages::{AflStatsStage, Stage},
+        state::StdState,
+    };
+
+    #[test]
+    fn test_afl_stats_stage() {
+        let out_dir = env::temp_dir().join(format!("libafl_afl_stats_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out_dir);
+        fs::create_dir_all(&out_dir).unwrap();
+
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        corpus
+            .add(Testcase::new(BytesInput::new(b"a".to_vec())))
+            .unwrap();
+        corpus
+            .add(Testcase::new(BytesInput::new(b"b".to_vec())))
+            .unwrap();
+        let mut solutions = InMemoryCorpus::<BytesInput>::new();
+        solutions
+            .add(Testcase::new(BytesInput::new(b"c".to_vec())))
+            .unwrap();
+        let mut state = StdState::new(StdRand::with_seed(0), corpus, solutions, tuple_list!());
+
+        let mut stage = AflStatsStage::with_interval(&out_dir, "test", Duration::ZERO);
+        stage
+            .perform(&mut (), &mut (), &mut state, &mut (), 1)
+            .unwrap();
+
+        let content = fs::read_to_string(out_dir.join("fuzzer_stats")).unwrap();
+        let stats: HashMap<&str, &str> = content
+            .lines()
+            .map(|line| {
+                let (key, value) = line.split_once(':').unwrap();
+                (key.trim(), value.trim())
+            })
+            .collect();
+        // The keys read by `afl-whatsup` and `afl-plot`
+        for key in [
+            "start_time",
+            "last_update",
+            "run_time",
+            "fuzzer_pid",
+            "cycles_done",
+            "execs_done",
+            "execs_per_sec",
+            "corpus_count",
+            "pending_total",
+            "pending_favs",
+            "bitmap_cvg",
+            "saved_crashes",
+            "saved_hangs",
+            "paths_total",
+            "paths_found",
+            "unique_crashes",
+            "afl_banner",
+        ] {
+            assert!(stats.contains_key(key), "missing {}", key);
+        }
+        assert_eq!(stats["corpus_count"], "2");
+        assert_eq!(stats["cur_item"], "1");
+        assert_eq!(stats["saved_crashes"], "1");
+        assert_eq!(stats["saved_hangs"], "0");
+        assert_eq!(stats["cycles_done"], "0");
+        assert_eq!(stats["afl_banner"], "test");
+
+        let plot = fs::read_to_string(out_dir.join("plot_data")).unwrap();
+        let mut lines = plot.lines();
+        assert!(lines.next().unwrap().starts_with("# relative_time"));
+        let values: Vec<&str> = lines.next().unwrap().split(", ").collect();
+        assert_eq!(values.len(), 13);
+        assert_eq!(values[3], "2");
+
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+//! The colorization stage randomizes as many bytes of a testcase as possible while keeping its coverage,
+//! the first step of the `Redqueen` input-to-state pipeline.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{marker::PhantomData, ops::Range};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::rands::Rand,
+    corpus::Corpus,
+    executors::{Executor, HasObservers},
+    fuzzer::ExecutesInput,
+    inputs::{HasBytesVec, Input},
+    observers::{MapObserver, ObserversTuple},
+    stages::Stage,
+    state::{HasClientPerfMonitor, HasCorpus, HasMetadata, HasRand},
+    Error,
+};
+
+/// Metadata holding the colorized version of the current testcase.
+/// The bytes in `ranges` were randomized without changing the coverage hash.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TaintMetadata {
+    /// The colorized input
+    pub input_vec: Vec<u8>,
+    /// The ranges of bytes that differ from the original input
+    pub ranges: Vec<Range<usize>>,
+}
+
+crate::impl_serdeany!(TaintMetadata);
+
+/// The colorization stage, randomizing ranges of the current testcase as long as the hash of the
+/// [`MapObserver`] stays the same. Ranges that change the hash are split in halves and tried again.
+/// The result is stored as [`TaintMetadata`] in the state, for the [`crate::stages::RedqueenTracingStage`].
+#[derive(Clone, Debug)]
+pub struct ColorizationStage<E, EM, I, O, OT, S, Z>
+where
+    I: Input + HasBytesVec,
+    O: MapObserver,
+{
+    map_observer_name: String,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, I, O, OT, S, Z)>,
+}
+
+impl<E, EM, I, O, OT, S, Z> Stage<E, EM, S, Z> for ColorizationStage<E, EM, I, O, OT, S, Z>
+where
+    E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    I: Input + HasBytesVec,
+    O: MapObserver,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasMetadata + HasRand,
+    Z: ExecutesInput<I, OT, S, Z>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let input = state
+            .corpus()
+            .get(corpus_idx)?
+            .borrow_mut()
+            .load_input()?
+            .clone();
+
+        let orig_hash = self.run_hash(fuzzer, executor, state, manager, &input)?;
+        let len = input.bytes().len();
+
+        let mut colorized = input.clone();
+        let mut changed = vec![];
+        let mut pending = Vec::new();
+        if len > 0 {
+            pending.push(0..len);
+        }
+        // Every byte can at most be in a run on each level of the splitting
+        let mut budget = 2 * len;
+
+        while let Some(range) = pending.pop() {
+            if budget == 0 {
+                break;
+            }
+            budget -= 1;
+
+            let mut candidate = colorized.clone();
+            for byte in &mut candidate.bytes_mut()[range.clone()] {
+                *byte = state.rand_mut().next() as u8;
+            }
+
+            if self.run_hash(fuzzer, executor, state, manager, &candidate)? == orig_hash {
+                colorized = candidate;
+                changed.push(range);
+            } else if range.len() > 1 {
+                let mid = range.start + range.len() / 2;
+                pending.push(mid..range.end);
+                pending.push(range.start..mid);
+            }
+        }
+
+        changed.sort_by_key(|range| range.start);
+        let meta = TaintMetadata {
+            input_vec: colorized.bytes().to_vec(),
+            ranges: changed,
+        };
+        state.add_metadata(meta);
+
+        Ok(())
+    }
+}
+
+impl<E, EM, I, O, OT, S, Z> ColorizationStage<E, EM, I, O, OT, S, Z>
+where
+    E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    I: Input + HasBytesVec,
+    O: MapObserver,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasMetadata + HasRand,
+    Z: ExecutesInput<I, OT, S, Z>,
+{
+    /// Creates a new [`ColorizationStage`] keeping the hash of the given [`MapObserver`]
+    #[must_use]
+    pub fn new(map_observer: &O) -> Self {
+        Self {
+            map_observer_name: map_observer.name().to_string(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Run the input and get the hash of the map
+    fn run_hash(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+    ) -> Result<u64, Error> {
+        fuzzer.execute_input(state, executor, manager, input)?;
+        let observer = executor
+            .observers()
+            .match_name::<O>(&self.map_observer_name)
+            .ok_or_else(|| Error::KeyNotFound("MapObserver not found".to_string()))?;
+        Ok(observer.hash())
+    }
+}
@@ -0,0 +1,544 @@
+//! The deterministic stage runs the `AFL`-style deterministic mutations once on every testcase:
+//! walking bitflips, arithmetics, interesting values, and dictionary tokens at every offset.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, StageOpMetadata},
+    executors::{Executor, HasObservers},
+    fuzzer::{Evaluator, ExecutesInput},
+    inputs::{HasBytesVec, Input},
+    mark_feature_time,
+    mutators::{
+        mutations::{ARITH_MAX, INTERESTING_16, INTERESTING_32, INTERESTING_8},
+        token_mutations::Tokens,
+    },
+    observers::{MapObserver, ObserversTuple},
+    stages::Stage,
+    start_timer,
+    state::{HasClientPerfMonitor, HasCorpus, HasMetadata},
+    Error,
+};
+
+#[cfg(feature = "introspection")]
+use crate::monitors::PerfFeature;
+
+/// Inputs shorter than this are not worth an effector map, every byte is considered effective, as in `AFL`
+pub const EFF_MIN_LEN: usize = 128;
+/// If more than this percentage of bytes is effective, the effector map is dropped, as in `AFL`
+pub const EFF_MAX_PERC: usize = 90;
+
+/// A single deterministic pass over the input
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeterministicPass {
+    /// Flip a single bit at every bit offset
+    BitFlip1,
+    /// Flip two consecutive bits at every bit offset
+    BitFlip2,
+    /// Flip four consecutive bits at every bit offset
+    BitFlip4,
+    /// Flip a byte at every offset
+    ByteFlip1,
+    /// Flip two bytes at every offset
+    ByteFlip2,
+    /// Flip four bytes at every offset
+    ByteFlip4,
+    /// Add and subtract up to [`ARITH_MAX`] to every byte
+    Arith8,
+    /// Add and subtract up to [`ARITH_MAX`] to every word, in both endiannesses
+    Arith16,
+    /// Add and subtract up to [`ARITH_MAX`] to every dword, in both endiannesses
+    Arith32,
+    /// Set every byte to each of [`INTERESTING_8`]
+    Interesting8,
+    /// Set every word to each of [`INTERESTING_16`], in both endiannesses
+    Interesting16,
+    /// Set every dword to each of [`INTERESTING_32`], in both endiannesses
+    Interesting32,
+    /// Overwrite the input with every [`Tokens`] entry at every offset
+    TokenOverwrite,
+    /// Insert every [`Tokens`] entry at every offset
+    TokenInsert,
+}
+
+/// All deterministic passes, in the order they are run
+pub const DETERMINISTIC_PASSES: [DeterministicPass; 14] = [
+    DeterministicPass::BitFlip1,
+    DeterministicPass::BitFlip2,
+    DeterministicPass::BitFlip4,
+    DeterministicPass::ByteFlip1,
+    DeterministicPass::ByteFlip2,
+    DeterministicPass::ByteFlip4,
+    DeterministicPass::Arith8,
+    DeterministicPass::Arith16,
+    DeterministicPass::Arith32,
+    DeterministicPass::Interesting8,
+    DeterministicPass::Interesting16,
+    DeterministicPass::Interesting32,
+    DeterministicPass::TokenOverwrite,
+    DeterministicPass::TokenInsert,
+];
+
+impl DeterministicPass {
+    /// The width in bits (for bitflips) or bytes (for everything else) this pass works on
+    fn width(self) -> usize {
+        match self {
+            Self::BitFlip1 | Self::ByteFlip1 | Self::Arith8 | Self::Interesting8 => 1,
+            Self::BitFlip2 | Self::ByteFlip2 | Self::Arith16 | Self::Interesting16 => 2,
+            Self::BitFlip4 | Self::ByteFlip4 | Self::Arith32 | Self::Interesting32 => 4,
+            Self::TokenOverwrite | Self::TokenInsert => 0,
+        }
+    }
+
+    /// The short name of this pass used by `AFL++`, for example in the `op:` part of queue filenames
+    #[must_use]
+    pub fn afl_op(self) -> &'static str {
+        match self {
+            Self::BitFlip1 => "flip1",
+            Self::BitFlip2 => "flip2",
+            Self::BitFlip4 => "flip4",
+            Self::ByteFlip1 => "flip8",
+            Self::ByteFlip2 => "flip16",
+            Self::ByteFlip4 => "flip32",
+            Self::Arith8 => "arith8",
+            Self::Arith16 => "arith16",
+            Self::Arith32 => "arith32",
+            Self::Interesting8 => "int8",
+            Self::Interesting16 => "int16",
+            Self::Interesting32 => "int32",
+            Self::TokenOverwrite => "ext_UO",
+            Self::TokenInsert => "ext_UI",
+        }
+    }
+
+    /// The number of byte orders tried for each value
+    fn endians(self) -> usize {
+        if self.width() > 1 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// The number of interesting values for this pass
+    fn interesting_count(self) -> usize {
+        match self {
+            Self::Interesting8 => INTERESTING_8.len(),
+            Self::Interesting16 => INTERESTING_16.len(),
+            Self::Interesting32 => INTERESTING_32.len(),
+            _ => 0,
+        }
+    }
+
+    /// The `idx`-th interesting value for this pass, sign-extended
+    fn interesting_value(self, idx: usize) -> i64 {
+        match self {
+            Self::Interesting8 => i64::from(INTERESTING_8[idx]),
+            Self::Interesting16 => i64::from(INTERESTING_16[idx]),
+            Self::Interesting32 => i64::from(INTERESTING_32[idx]),
+            _ => 0,
+        }
+    }
+
+    /// The number of steps of this pass for an input of length `len`
+    #[must_use]
+    pub fn steps(self, len: usize, tokens: &[Vec<u8>]) -> usize {
+        let width = self.width();
+        match self {
+            Self::BitFlip1 | Self::BitFlip2 | Self::BitFlip4 => (len * 8).saturating_sub(width - 1),
+            Self::ByteFlip1 | Self::ByteFlip2 | Self::ByteFlip4 => (len + 1).saturating_sub(width),
+            Self::Arith8 | Self::Arith16 | Self::Arith32 => {
+                (len + 1).saturating_sub(width) * self.endians() * 2 * ARITH_MAX as usize
+            }
+            Self::Interesting8 | Self::Interesting16 | Self::Interesting32 => {
+                (len + 1).saturating_sub(width) * self.endians() * self.interesting_count()
+            }
+            Self::TokenOverwrite => tokens.len() * len,
+            Self::TokenInsert => tokens.len() * (len + 1),
+        }
+    }
+
+    /// Apply the given step of this pass to `bytes`.
+    /// Returns `false` if the step was skipped, because it would not change the input,
+    /// or because it only touches bytes the `effector` map marked as having no effect.
+    pub fn apply(
+        self,
+        step: usize,
+        bytes: &mut Vec<u8>,
+        tokens: &[Vec<u8>],
+        effector: Option<&[bool]>,
+    ) -> bool {
+        let width = self.width();
+        let endians = self.endians();
+        let effective =
+            |from: usize, to: usize| effector.map_or(true, |eff| eff[from..to].iter().any(|&e| e));
+
+        match self {
+            Self::BitFlip1 | Self::BitFlip2 | Self::BitFlip4 => {
+                for bit in step..step + width {
+                    bytes[bit >> 3] ^= 128 >> (bit & 7);
+                }
+                true
+            }
+            Self::ByteFlip1 | Self::ByteFlip2 | Self::ByteFlip4 => {
+                for byte in &mut bytes[step..step + width] {
+                    *byte ^= 0xff;
+                }
+                true
+            }
+            Self::Arith8 | Self::Arith16 | Self::Arith32 => {
+                let per_offset = endians * 2 * ARITH_MAX as usize;
+                let offset = step / per_offset;
+                let rem = step % per_offset;
+                if !effective(offset, offset + width) {
+                    return false;
+                }
+                let delta = (rem / (endians * 2)) as u64 + 1;
+                let sub = (rem / endians) % 2 == 1;
+                let big_endian = rem % endians == 1;
+
+                let orig = read_uint(&bytes[offset..offset + width], big_endian);
+                let value = if sub {
+                    orig.wrapping_sub(delta)
+                } else {
+                    orig.wrapping_add(delta)
+                };
+                write_uint(&mut bytes[offset..offset + width], value, big_endian)
+            }
+            Self::Interesting8 | Self::Interesting16 | Self::Interesting32 => {
+                let per_offset = endians * self.interesting_count();
+                let offset = step / per_offset;
+                let rem = step % per_offset;
+                if !effective(offset, offset + width) {
+                    return false;
+                }
+                let big_endian = rem % endians == 1;
+                #[allow(clippy::cast_sign_loss)]
+                let value = self.interesting_value(rem / endians) as u64;
+                write_uint(&mut bytes[offset..offset + width], value, big_endian)
+            }
+            Self::TokenOverwrite => {
+                let len = bytes.len();
+                let token = &tokens[step / len];
+                let offset = step % len;
+                if token.is_empty()
+                    || offset + token.len() > len
+                    || !effective(offset, offset + token.len())
+                    || bytes[offset..offset + token.len()] == token[..]
+                {
+                    return false;
+                }
+                bytes[offset..offset + token.len()].copy_from_slice(token);
+                true
+            }
+            Self::TokenInsert => {
+                let token = &tokens[step / (bytes.len() + 1)];
+                let offset = step % (bytes.len() + 1);
+                if token.is_empty() {
+                    return false;
+                }
+                bytes.splice(offset..offset, token.iter().copied());
+                true
+            }
+        }
+    }
+}
+
+/// Read an unsigned integer of `bytes.len()` bytes
+fn read_uint(bytes: &[u8], big_endian: bool) -> u64 {
+    let fold = |acc: u64, &b: &u8| (acc << 8) | u64::from(b);
+    if big_endian {
+        bytes.iter().fold(0, fold)
+    } else {
+        bytes.iter().rev().fold(0, fold)
+    }
+}
+
+/// Write the low `bytes.len()` bytes of `value`, returning `false` if nothing changed
+fn write_uint(bytes: &mut [u8], mut value: u64, big_endian: bool) -> bool {
+    let len = bytes.len();
+    let mut changed = false;
+    for i in 0..len {
+        let idx = if big_endian { len - 1 - i } else { i };
+        let byte = value as u8;
+        changed |= bytes[idx] != byte;
+        bytes[idx] = byte;
+        value >>= 8;
+    }
+    changed
+}
+
+/// Metadata of a testcase, recording how far the [`DeterministicStage`] got.
+/// It is updated after every execution, so that the stage resumes where it stopped after a restart.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DeterministicMetadata {
+    /// The index of the current pass in [`DETERMINISTIC_PASSES`]
+    pub pass: usize,
+    /// The next step of the current pass
+    pub step: usize,
+    /// For each byte, if flipping it changed the coverage. `None` if every byte is considered effective.
+    pub effector: Option<Vec<bool>>,
+}
+
+crate::impl_serdeany!(DeterministicMetadata);
+
+impl DeterministicMetadata {
+    /// If all deterministic passes are done for this testcase
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.pass >= DETERMINISTIC_PASSES.len()
+    }
+}
+
+/// A stage running all [`DETERMINISTIC_PASSES`] once on each testcase, as `AFL` does before havoc.
+/// If the effector map is enabled, bytes whose flip does not change the hash of the [`MapObserver`]
+/// are skipped by the arithmetic, interesting and token overwrite passes.
+#[derive(Clone, Debug)]
+pub struct DeterministicStage<E, EM, I, O, OT, S, Z>
+where
+    I: Input + HasBytesVec,
+    O: MapObserver,
+{
+    map_observer_name: String,
+    use_effector_map: bool,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, I, O, OT, S, Z)>,
+}
+
+impl<E, EM, I, O, OT, S, Z> Stage<E, EM, S, Z> for DeterministicStage<E, EM, I, O, OT, S, Z>
+where
+    E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    I: Input + HasBytesVec,
+    O: MapObserver,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasMetadata,
+    Z: Evaluator<E, EM, I, S> + ExecutesInput<I, OT, S, Z>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        start_timer!(state);
+        let (input, mut meta) = {
+            let mut testcase = state.corpus().get(corpus_idx)?.borrow_mut();
+            let meta = testcase
+                .metadata()
+                .get::<DeterministicMetadata>()
+                .cloned()
+                .unwrap_or_default();
+            (testcase.load_input()?.clone(), meta)
+        };
+        mark_feature_time!(state, PerfFeature::GetInputFromCorpus);
+        if meta.is_done() {
+            return Ok(());
+        }
+
+        let tokens = state
+            .metadata()
+            .get::<Tokens>()
+            .map(|t| t.tokens().to_vec())
+            .unwrap_or_default();
+        let len = input.bytes().len();
+
+        let build_effector = self.use_effector_map && len >= EFF_MIN_LEN;
+        let orig_hash = if build_effector {
+            Some(self.run_hash(fuzzer, executor, state, manager, &input)?)
+        } else {
+            None
+        };
+
+        while !meta.is_done() {
+            let pass = DETERMINISTIC_PASSES[meta.pass];
+            state.add_metadata(StageOpMetadata {
+                op: pass.afl_op().to_string(),
+            });
+            let mapping = build_effector && pass == DeterministicPass::ByteFlip1;
+            if mapping && meta.step == 0 {
+                meta.effector = Some(vec![false; len]);
+            }
+            // Once per pass, the steps only update the stored progress in place
+            Self::store_progress(state, corpus_idx, &meta)?;
+
+            while meta.step < pass.steps(len, &tokens) {
+                let step = meta.step;
+                meta.step += 1;
+
+                start_timer!(state);
+                let mut candidate = input.clone();
+                let applied = pass.apply(
+                    step,
+                    candidate.bytes_mut(),
+                    &tokens,
+                    meta.effector.as_deref(),
+                );
+                mark_feature_time!(state, PerfFeature::Mutate);
+                if !applied {
+                    continue;
+                }
+
+                // Store the progress before running, so that a crash does not repeat this step
+                let next_step = meta.step;
+                Self::update_progress(state, corpus_idx, |stored| stored.step = next_step)?;
+                fuzzer.evaluate_input(state, executor, manager, candidate)?;
+
+                if mapping {
+                    let observer = executor
+                        .observers()
+                        .match_name::<O>(&self.map_observer_name)
+                        .ok_or_else(|| Error::KeyNotFound("MapObserver not found".to_string()))?;
+                    if Some(observer.hash()) != orig_hash {
+                        meta.effector.as_mut().unwrap()[step] = true;
+                        Self::update_progress(state, corpus_idx, |stored| {
+                            if let Some(effector) = stored.effector.as_mut() {
+                                effector[step] = true;
+                            }
+                        })?;
+                    }
+                }
+            }
+
+            if mapping {
+                // Almost every byte has an effect, the map is not worth it
+                let effective = meta
+                    .effector
+                    .as_ref()
+                    .map_or(0, |eff| eff.iter().filter(|&&e| e).count());
+                if effective * 100 > len * EFF_MAX_PERC {
+                    meta.effector = None;
+                }
+            }
+
+            meta.pass += 1;
+            meta.step = 0;
+            Self::store_progress(state, corpus_idx, &meta)?;
+        }
+
+        // The effector map is not needed anymore
+        meta.effector = None;
+        Self::store_progress(state, corpus_idx, &meta)?;
+        let _ = state.metadata_mut().remove::<StageOpMetadata>();
+
+        Ok(())
+    }
+}
+
+impl<E, EM, I, O, OT, S, Z> DeterministicStage<E, EM, I, O, OT, S, Z>
+where
+    E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    I: Input + HasBytesVec,
+    O: MapObserver,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasMetadata,
+    Z: Evaluator<E, EM, I, S> + ExecutesInput<I, OT, S, Z>,
+{
+    /// Creates a new [`DeterministicStage`].
+    /// If `use_effector_map` is set, the hash of the given [`MapObserver`] is used to skip bytes without effect.
+    #[must_use]
+    pub fn new(map_observer: &O, use_effector_map: bool) -> Self {
+        Self {
+            map_observer_name: map_observer.name().to_string(),
+            use_effector_map,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Run the input and get the hash of the map
+    fn run_hash(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+    ) -> Result<u64, Error> {
+        fuzzer.execute_input(state, executor, manager, input)?;
+        let observer = executor
+            .observers()
+            .match_name::<O>(&self.map_observer_name)
+            .ok_or_else(|| Error::KeyNotFound("MapObserver not found".to_string()))?;
+        Ok(observer.hash())
+    }
+
+    /// Store the progress in the metadata of the testcase
+    fn store_progress(
+        state: &mut S,
+        corpus_idx: usize,
+        meta: &DeterministicMetadata,
+    ) -> Result<(), Error> {
+        state
+            .corpus()
+            .get(corpus_idx)?
+            .borrow_mut()
+            .add_metadata(meta.clone());
+        Ok(())
+    }
+
+    /// Update the progress stored by [`Self::store_progress`] in place, without copying the effector map
+    fn update_progress<F>(state: &mut S, corpus_idx: usize, update: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut DeterministicMetadata),
+    {
+        let mut testcase = state.corpus().get(corpus_idx)?.borrow_mut();
+        let stored = testcase
+            .metadata_mut()
+            .get_mut::<DeterministicMetadata>()
+            .ok_or_else(|| Error::KeyNotFound("DeterministicMetadata not found".to_string()))?;
+        update(stored);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{DeterministicPass, DETERMINISTIC_PASSES};
+
+    #[test]
+    fn test_deterministic_passes() {
+        let input = b"\x00\x01\x02\x03".to_vec();
+        let tokens = vec![b"AB".to_vec()];
+
+        assert_eq!(DeterministicPass::BitFlip4.steps(input.len(), &tokens), 29);
+        assert_eq!(
+            DeterministicPass::Arith16.steps(input.len(), &tokens),
+            3 * 140
+        );
+
+        let mut bytes = input.clone();
+        assert!(DeterministicPass::BitFlip1.apply(9, &mut bytes, &tokens, None));
+        assert_eq!(bytes, b"\x00\x41\x02\x03");
+
+        // Big endian subtraction of 1 from the word at offset 0
+        let mut bytes = input.clone();
+        assert!(DeterministicPass::Arith16.apply(3, &mut bytes, &tokens, None));
+        assert_eq!(bytes, b"\x00\x00\x02\x03");
+
+        // Skipped, as the byte was marked as without effect
+        let mut bytes = input.clone();
+        let effector = [true, false, true, true];
+        assert!(!DeterministicPass::Arith8.apply(70, &mut bytes, &tokens, Some(&effector)));
+
+        let mut bytes = input.clone();
+        assert!(DeterministicPass::TokenInsert.apply(4, &mut bytes, &tokens, None));
+        assert_eq!(bytes, b"\x00\x01\x02\x03AB");
+
+        // Every step of every pass applies in bounds
+        for pass in &DETERMINISTIC_PASSES {
+            for step in 0..pass.steps(input.len(), &tokens) {
+                let mut bytes: Vec<u8> = input.clone();
+                pass.apply(step, &mut bytes, &tokens, None);
+            }
+        }
+    }
+}
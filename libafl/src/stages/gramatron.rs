@@ -0,0 +1,241 @@
+//! Stages for `Gramatron` grammar fuzzing: importing raw byte seeds, and minimizing inputs by removing sub-walks.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    fuzzer::Evaluator,
+    generators::Automaton,
+    inputs::GramatronInput,
+    mutators::GramatronSubwalkRemovalMutator,
+    stages::{MinimizationStage, Stage},
+    state::{HasClientPerfMonitor, HasCorpus, HasMetadata},
+    Error,
+};
+
+/// A [`MinimizationStage`] for [`GramatronInput`]s, removing automaton sub-walks.
+/// Use a [`crate::stages::MapEqualityFactory`] as feedback factory to keep the coverage.
+pub type GramatronMinimizationStage<CS, E, EM, F, FF, OT, S, Z> =
+    MinimizationStage<CS, E, EM, F, FF, GramatronInput, GramatronSubwalkRemovalMutator, OT, S, Z>;
+
+/// Metadata used by the [`GramatronImportStage`] to remember what it already imported
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct GramatronImportMetadata {
+    /// The last modification time of the imported files
+    pub last_time: Option<SystemTime>,
+    /// The number of files imported so far
+    pub imported: usize,
+    /// The number of files that could not be parsed with the automaton
+    pub failed: usize,
+}
+
+crate::impl_serdeany!(GramatronImportMetadata);
+
+/// A stage that imports raw byte seeds from directories, for example a `BytesInput` corpus,
+/// by parsing them into [`GramatronInput`]s with the [`Automaton`].
+/// Files that are not part of the grammar are skipped, new or changed files are picked up on every run.
+#[derive(Debug)]
+pub struct GramatronImportStage<E, EM, S, Z>
+where
+    S: HasClientPerfMonitor + HasCorpus<GramatronInput> + HasMetadata,
+    Z: Evaluator<E, EM, GramatronInput, S>,
+{
+    automaton: Automaton,
+    in_dirs: Vec<PathBuf>,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, S, Z)>,
+}
+
+impl<E, EM, S, Z> Stage<E, EM, S, Z> for GramatronImportStage<E, EM, S, Z>
+where
+    S: HasClientPerfMonitor + HasCorpus<GramatronInput> + HasMetadata,
+    Z: Evaluator<E, EM, GramatronInput, S>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        _corpus_idx: usize,
+    ) -> Result<(), Error> {
+        if state.metadata().get::<GramatronImportMetadata>().is_none() {
+            state.add_metadata(GramatronImportMetadata::default());
+        }
+        let last = state
+            .metadata()
+            .get::<GramatronImportMetadata>()
+            .unwrap()
+            .last_time;
+
+        let mut files = vec![];
+        for dir in &self.in_dirs {
+            collect_files(dir, last, &mut files)?;
+        }
+
+        let (mut imported, mut failed) = (0, 0);
+        let mut max_time = last;
+        for (path, time) in files {
+            max_time = Some(max_time.map_or(time, |t| t.max(time)));
+            // A file removed or unreadable since it was listed does not stop the import of the others
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    println!("Skipping {}, failed to read: {:?}", path.display(), err);
+                    continue;
+                }
+            };
+            match self.automaton.parse(&bytes) {
+                Some(input) => {
+                    fuzzer.evaluate_input(state, executor, manager, input)?;
+                    imported += 1;
+                }
+                None => failed += 1,
+            }
+        }
+
+        let meta = state
+            .metadata_mut()
+            .get_mut::<GramatronImportMetadata>()
+            .unwrap();
+        meta.last_time = max_time;
+        meta.imported += imported;
+        meta.failed += failed;
+
+        Ok(())
+    }
+}
+
+impl<E, EM, S, Z> GramatronImportStage<E, EM, S, Z>
+where
+    S: HasClientPerfMonitor + HasCorpus<GramatronInput> + HasMetadata,
+    Z: Evaluator<E, EM, GramatronInput, S>,
+{
+    /// Creates a new [`GramatronImportStage`], importing the files in `in_dirs`
+    #[must_use]
+    pub fn new(automaton: Automaton, in_dirs: &[PathBuf]) -> Self {
+        Self {
+            automaton,
+            in_dirs: in_dirs.to_vec(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Collect the non-empty files in `dir`, recursively, modified since `last`
+fn collect_files(
+    dir: &Path,
+    last: Option<SystemTime>,
+    files: &mut Vec<(PathBuf, SystemTime)>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let attr = match fs::metadata(&path) {
+            Ok(attr) => attr,
+            Err(_) => continue,
+        };
+        if attr.is_dir() {
+            collect_files(&path, last, files)?;
+        } else if attr.is_file() && attr.len() > 0 {
+            if let Ok(time) = attr.modified() {
+                if last.map_or(true, |last| time >= last) {
+                    files.push((path, time));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use std::{env, fs};
+
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::InMemoryCorpus,
+        events::SimpleEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        generators::{Automaton, Trigger},
+        inputs::GramatronInput,
+        monitors::NopMonitor,
+        schedulers::QueueScheduler,
+        stages::{GramatronImportMetadata, GramatronImportStage, Stage},
+        state::{HasMetadata, StdState},
+        StdFuzzer,
+    };
+
+    #[test]
+    fn test_gramatron_import_stage() {
+        let in_dir =
+            env::temp_dir().join(format!("libafl_gramatron_import_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&in_dir);
+        fs::create_dir_all(in_dir.join("nested")).unwrap();
+        fs::write(in_dir.join("a"), b"aab").unwrap();
+        fs::write(in_dir.join("nested").join("b"), b"b").unwrap();
+        // Not part of the grammar
+        fs::write(in_dir.join("c"), b"ba").unwrap();
+
+        // S -> "a" S | "b"
+        let automaton = Automaton {
+            init_state: 0,
+            final_state: 1,
+            pda: vec![
+                vec![
+                    Trigger {
+                        dest: 0,
+                        term: "a".into(),
+                    },
+                    Trigger {
+                        dest: 1,
+                        term: "b".into(),
+                    },
+                ],
+                vec![],
+            ],
+        };
+
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<GramatronInput>::new(),
+            InMemoryCorpus::<GramatronInput>::new(),
+            tuple_list!(),
+        );
+        let mut manager = SimpleEventManager::new(NopMonitor::new());
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+        let runs = Cell::new(0);
+        let mut harness = |_input: &GramatronInput| {
+            runs.set(runs.get() + 1);
+            ExitKind::Ok
+        };
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(),
+            &mut fuzzer,
+            &mut state,
+            &mut manager,
+        )
+        .unwrap();
+
+        let mut stage = GramatronImportStage::new(automaton, &[in_dir.clone()]);
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut manager, 0)
+            .unwrap();
+        let meta = state.metadata().get::<GramatronImportMetadata>().unwrap();
+        assert_eq!(meta.imported, 2);
+        assert_eq!(meta.failed, 1);
+        assert!(meta.last_time.is_some());
+        drop(executor);
+        assert_eq!(runs.get(), 2);
+
+        fs::remove_dir_all(&in_dir).unwrap();
+    }
+}
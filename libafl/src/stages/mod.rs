@@ -13,6 +13,15 @@ pub mod push;
 pub mod tracing;
 pub use tracing::{ShadowTracingStage, TracingStage};
 
+pub mod colorization;
+pub use colorization::{ColorizationStage, TaintMetadata};
+
+pub mod redqueen;
+pub use redqueen::{RedqueenMetadata, RedqueenStage, RedqueenTracingStage};
+
+pub mod deterministic;
+pub use deterministic::{DeterministicMetadata, DeterministicPass, DeterministicStage};
+
 pub mod calibrate;
 pub use calibrate::CalibrationStage;
 
@@ -25,6 +34,12 @@ pub use generalization::GeneralizationStage;
 pub mod owned;
 pub use owned::StagesOwnedList;
 
+pub mod tmin;
+pub use tmin::{
+    HashEqualityFactory, HashEqualityFeedback, MapEqualityFactory, MapEqualityFeedback,
+    MinimizationStage,
+};
+
 #[cfg(feature = "std")]
 pub mod concolic;
 #[cfg(feature = "std")]
@@ -37,6 +52,16 @@ pub mod sync;
 #[cfg(feature = "std")]
 pub use sync::*;
 
+#[cfg(feature = "std")]
+pub mod gramatron;
+#[cfg(feature = "std")]
+pub use gramatron::{GramatronImportMetadata, GramatronImportStage, GramatronMinimizationStage};
+
+#[cfg(feature = "std")]
+pub mod afl_stats;
+#[cfg(feature = "std")]
+pub use afl_stats::{AflStatsMetadata, AflStatsStage};
+
 use crate::{
     events::{EventFirer, EventRestarter, HasEventManagerId, ProgressReporter},
     executors::{Executor, HasObservers},
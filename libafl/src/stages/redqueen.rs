@@ -0,0 +1,365 @@
+//! The `Redqueen` input-to-state stages.
+//! After the [`crate::stages::ColorizationStage`], the [`RedqueenTracingStage`] traces the comparisons
+//! of both the original and the colorized input, and the [`RedqueenStage`] maps the comparison operands back to
+//! input offsets and deterministically replaces them, trying several encodings of each operand.
+
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Corpus,
+    executors::{Executor, HasObservers},
+    fuzzer::Evaluator,
+    inputs::{HasBytesVec, Input},
+    observers::{CmpValues, CmpValuesMetadata, ObserversTuple},
+    stages::{colorization::TaintMetadata, Stage, TracingStage},
+    state::{HasClientPerfMonitor, HasCorpus, HasExecutions, HasMetadata},
+    Error,
+};
+
+/// The comparison operands logged for the original and for the colorized input
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RedqueenMetadata {
+    /// The comparisons of the original input
+    #[serde(skip)]
+    pub orig_cmpvals: Vec<CmpValues>,
+    /// The comparisons of the colorized input
+    #[serde(skip)]
+    pub new_cmpvals: Vec<CmpValues>,
+}
+
+crate::impl_serdeany!(RedqueenMetadata);
+
+/// A stage that runs the tracer on the original and on the colorized input (see [`TaintMetadata`]),
+/// storing the logged comparisons as [`RedqueenMetadata`].
+/// The tracer needs an observer that fills [`CmpValuesMetadata`], such as a `CmpLogObserver`.
+#[derive(Clone, Debug)]
+pub struct RedqueenTracingStage<EM, I, OT, S, TE, Z>
+where
+    I: Input + HasBytesVec,
+    TE: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasExecutions + HasCorpus<I> + HasMetadata,
+{
+    tracing: TracingStage<EM, I, OT, S, TE, Z>,
+}
+
+impl<E, EM, I, OT, S, TE, Z> Stage<E, EM, S, Z> for RedqueenTracingStage<EM, I, OT, S, TE, Z>
+where
+    I: Input + HasBytesVec,
+    TE: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasExecutions + HasCorpus<I> + HasMetadata,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let input = state
+            .corpus()
+            .get(corpus_idx)?
+            .borrow_mut()
+            .load_input()?
+            .clone();
+
+        self.tracing.trace(fuzzer, state, manager, &input)?;
+        let orig_cmpvals = take_cmpvals(state);
+
+        let new_cmpvals = match state.metadata().get::<TaintMetadata>() {
+            Some(taint) if taint.input_vec.len() == input.bytes().len() => {
+                let mut colorized = input;
+                colorized.bytes_mut().copy_from_slice(&taint.input_vec);
+                self.tracing.trace(fuzzer, state, manager, &colorized)?;
+                take_cmpvals(state)
+            }
+            _ => orig_cmpvals.clone(),
+        };
+
+        state.add_metadata(RedqueenMetadata {
+            orig_cmpvals,
+            new_cmpvals,
+        });
+
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S, TE, Z> RedqueenTracingStage<EM, I, OT, S, TE, Z>
+where
+    I: Input + HasBytesVec,
+    TE: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasExecutions + HasCorpus<I> + HasMetadata,
+{
+    /// Creates a new [`RedqueenTracingStage`] using the given tracer executor
+    pub fn new(tracer_executor: TE) -> Self {
+        Self {
+            tracing: TracingStage::new(tracer_executor),
+        }
+    }
+
+    /// Gets the underlying tracer executor
+    pub fn executor(&self) -> &TE {
+        self.tracing.executor()
+    }
+}
+
+/// Take the comparisons logged in the last traced run out of the state
+fn take_cmpvals<S>(state: &mut S) -> Vec<CmpValues>
+where
+    S: HasMetadata,
+{
+    state
+        .metadata_mut()
+        .get_mut::<CmpValuesMetadata>()
+        .map(|meta| core::mem::take(&mut meta.list))
+        .unwrap_or_default()
+}
+
+/// A stage replacing input-to-state comparison operands deterministically,
+/// using the [`RedqueenMetadata`] and [`TaintMetadata`] of the earlier stages.
+#[derive(Clone, Debug)]
+pub struct RedqueenStage<E, EM, I, S, Z>
+where
+    I: Input + HasBytesVec,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasMetadata,
+    Z: Evaluator<E, EM, I, S>,
+{
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, I, S, Z)>,
+}
+
+impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for RedqueenStage<E, EM, I, S, Z>
+where
+    I: Input + HasBytesVec,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasMetadata,
+    Z: Evaluator<E, EM, I, S>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let input = state
+            .corpus()
+            .get(corpus_idx)?
+            .borrow_mut()
+            .load_input()?
+            .clone();
+
+        let candidates = {
+            let meta = match state.metadata().get::<RedqueenMetadata>() {
+                Some(meta) => meta,
+                None => return Ok(()),
+            };
+            let colorized = match state.metadata().get::<TaintMetadata>() {
+                Some(taint) if taint.input_vec.len() == input.bytes().len() => {
+                    taint.input_vec.as_slice()
+                }
+                _ => input.bytes(),
+            };
+            i2s_candidates(
+                input.bytes(),
+                colorized,
+                &meta.orig_cmpvals,
+                &meta.new_cmpvals,
+            )
+        };
+
+        for (offset, replacement) in candidates {
+            let mut mutated = input.clone();
+            mutated.bytes_mut()[offset..offset + replacement.len()].copy_from_slice(&replacement);
+            fuzzer.evaluate_input(state, executor, manager, mutated)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<E, EM, I, S, Z> RedqueenStage<E, EM, I, S, Z>
+where
+    I: Input + HasBytesVec,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasMetadata,
+    Z: Evaluator<E, EM, I, S>,
+{
+    /// Creates a new [`RedqueenStage`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E, EM, I, S, Z> Default for RedqueenStage<E, EM, I, S, Z>
+where
+    I: Input + HasBytesVec,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasMetadata,
+    Z: Evaluator<E, EM, I, S>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The bytes of the lowest `width` bytes of `value`, little endian or byte-swapped
+fn encode(value: u64, width: usize, swapped: bool) -> Vec<u8> {
+    let mut bytes = value.to_le_bytes()[..width].to_vec();
+    if swapped {
+        bytes.reverse();
+    }
+    bytes
+}
+
+/// If `value`, an operand of `width` bytes, is the zero- or sign-extension of its lowest `narrow` bytes
+fn is_extended(value: u64, width: usize, narrow: usize) -> bool {
+    let full_mask = if width == 8 {
+        u64::MAX
+    } else {
+        (1 << (8 * width)) - 1
+    };
+    let high_mask = full_mask & !((1 << (8 * narrow)) - 1);
+    let high = value & high_mask;
+    let sign = (value >> (8 * narrow - 1)) & 1;
+    high == 0 || (high == high_mask && sign == 1)
+}
+
+/// All encodings of a numeric operand, as (pattern in the original input, pattern in the colorized input, replacement).
+/// The operand is tried plain and byte-swapped, with the replacement ±1 for less/greater-than comparisons,
+/// and in narrower widths if it was zero- or sign-extended.
+fn numeric_encodings(
+    width: usize,
+    orig: u64,
+    colorized: u64,
+    replacement: u64,
+) -> Vec<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+    let mut encodings = vec![];
+    let mut narrow = width;
+    while narrow >= 1 {
+        if narrow == width
+            || [orig, colorized, replacement]
+                .iter()
+                .all(|value| is_extended(*value, width, narrow))
+        {
+            for repl in [
+                replacement,
+                replacement.wrapping_add(1),
+                replacement.wrapping_sub(1),
+            ] {
+                for swapped in [false, true] {
+                    if swapped && narrow == 1 {
+                        continue;
+                    }
+                    let pattern = encode(orig, narrow, swapped);
+                    let repl = encode(repl, narrow, swapped);
+                    if pattern != repl {
+                        encodings.push((pattern, encode(colorized, narrow, swapped), repl));
+                    }
+                }
+            }
+        }
+        narrow /= 2;
+    }
+    encodings
+}
+
+/// Map the comparison operands back to offsets of the input, and return the replacements to try.
+/// An offset is only used if the operand matches the original input there,
+/// and its colorized counterpart matches the colorized input, so the operand comes from the input.
+#[must_use]
+pub fn i2s_candidates(
+    orig_input: &[u8],
+    colorized_input: &[u8],
+    orig_cmpvals: &[CmpValues],
+    new_cmpvals: &[CmpValues],
+) -> BTreeSet<(usize, Vec<u8>)> {
+    let mut encodings = vec![];
+    for (orig, new) in orig_cmpvals.iter().zip(new_cmpvals) {
+        if let (CmpValues::Bytes((o0, o1)), CmpValues::Bytes((n0, n1))) = (orig, new) {
+            let len = o0.len().min(o1.len()).min(n0.len()).min(n1.len());
+            if len > 0 && o0[..len] != o1[..len] {
+                encodings.push((o0[..len].to_vec(), n0[..len].to_vec(), o1[..len].to_vec()));
+                encodings.push((o1[..len].to_vec(), n1[..len].to_vec(), o0[..len].to_vec()));
+            }
+        } else if let (Some(o), Some(n)) = (orig.to_u64_tuple(), new.to_u64_tuple()) {
+            let width = match orig {
+                CmpValues::U8(_) => 1,
+                CmpValues::U16(_) => 2,
+                CmpValues::U32(_) => 4,
+                _ => 8,
+            };
+            if o.0 != o.1 {
+                encodings.extend(numeric_encodings(width, o.0, n.0, o.1));
+                encodings.extend(numeric_encodings(width, o.1, n.1, o.0));
+            }
+        }
+    }
+
+    let mut candidates = BTreeSet::new();
+    for (pattern, colorized, replacement) in encodings {
+        let len = pattern.len();
+        if len > orig_input.len() {
+            continue;
+        }
+        for offset in 0..=orig_input.len() - len {
+            if orig_input[offset..offset + len] == pattern[..]
+                && colorized_input[offset..offset + len] == colorized[..]
+            {
+                candidates.insert((offset, replacement.clone()));
+            }
+        }
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::{observers::CmpValues, stages::redqueen::i2s_candidates};
+
+    #[test]
+    fn test_i2s_candidates() {
+        // The input holds a big-endian u32 at offset 2, compared against 0xdeadbeef
+        let orig = [0, 0, 0x12, 0x34, 0x56, 0x78, 0];
+        let colorized = [1, 2, 0xaa, 0xbb, 0xcc, 0xdd, 3];
+        let candidates = i2s_candidates(
+            &orig,
+            &colorized,
+            &[CmpValues::U32((0x1234_5678, 0xdead_beef))],
+            &[CmpValues::U32((0xaabb_ccdd, 0xdead_beef))],
+        );
+        assert!(candidates.contains(&(2, vec![0xde, 0xad, 0xbe, 0xef])));
+        assert!(candidates.contains(&(2, vec![0xde, 0xad, 0xbe, 0xf0])));
+
+        // A zero-extended u8 compared as u32, that did not change when colorizing: not from the input
+        let candidates = i2s_candidates(
+            &orig,
+            &colorized,
+            &[CmpValues::U32((0x12, 0x41))],
+            &[CmpValues::U32((0x12, 0x41))],
+        );
+        assert!(candidates.is_empty());
+
+        // The same, but colorized
+        let candidates = i2s_candidates(
+            &orig,
+            &colorized,
+            &[CmpValues::U32((0x12, 0x41))],
+            &[CmpValues::U32((0xaa, 0x41))],
+        );
+        let offsets: Vec<_> = candidates.iter().map(|(offset, _)| *offset).collect();
+        assert!(offsets.iter().all(|offset| *offset == 2));
+        assert!(candidates.contains(&(2, vec![0x41])));
+    }
+}
@@ -1,7 +1,9 @@
 //| The [`MutationalStage`] is the default stage used during fuzzing.
 //! For the current input, it will perform a range of random mutations, and then run them in the executor.
 
-use core::marker::PhantomData;
+use alloc::string::{String, ToString};
+use core::{marker::PhantomData, time::Duration};
+use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
@@ -10,6 +12,10 @@ use std::{
 };
 
 use crate::{
+    corpus::{
+        ondisk::{lock_afl_id, parse_afl_id},
+        Corpus, StageOpMetadata,
+    },
     fuzzer::Evaluator,
     inputs::Input,
     stages::Stage,
@@ -175,3 +181,355 @@ where
         }
     }
 }
+
+/// Metadata used by the [`AflSyncStage`] to store the sync progress
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AflSyncMetadata {
+    /// The next id to import, for each peer
+    pub cursors: HashMap<String, usize>,
+    /// The number of corpus entries that have been published, or imported from peers
+    pub published: usize,
+    /// The next id of our published entries
+    pub next_id: usize,
+    /// The last time the sync was done
+    pub last_time: Option<SystemTime>,
+}
+
+crate::impl_serdeany!(AflSyncMetadata);
+
+/// A stage that joins an `AFL++` sync directory (as used by `-M`/`-S`) as a peer named `name`.
+/// It imports new entries from the `queue/` of every other peer, using a per-peer id cursor,
+/// and publishes new entries of our corpus as `AFL++`-style `name/queue/id:...` files.
+/// Entries already written there, for example by an [`crate::corpus::OnDiskCorpus::afl_queue`], are not copied again,
+/// and the ids are locked the same way, so both can share the queue.
+/// Entries are imported under the `sync` [`StageOpMetadata`].
+#[derive(Debug)]
+pub struct AflSyncStage<CB, E, EM, I, S, Z>
+where
+    CB: FnMut(&mut Z, &mut S, &Path) -> Result<I, Error>,
+    I: Input,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasRand + HasMetadata,
+    Z: Evaluator<E, EM, I, S>,
+{
+    sync_dir: PathBuf,
+    name: String,
+    interval: Duration,
+    load_callback: CB,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, I, S, Z)>,
+}
+
+impl<CB, E, EM, I, S, Z> Stage<E, EM, S, Z> for AflSyncStage<CB, E, EM, I, S, Z>
+where
+    CB: FnMut(&mut Z, &mut S, &Path) -> Result<I, Error>,
+    I: Input,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasRand + HasMetadata,
+    Z: Evaluator<E, EM, I, S>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        _corpus_idx: usize,
+    ) -> Result<(), Error> {
+        if state.metadata().get::<AflSyncMetadata>().is_none() {
+            let next_id = self.next_own_id()?;
+            state.add_metadata(AflSyncMetadata {
+                next_id,
+                ..AflSyncMetadata::default()
+            });
+        }
+
+        let now = SystemTime::now();
+        let last_time = state.metadata().get::<AflSyncMetadata>().unwrap().last_time;
+        if let Some(last_time) = last_time {
+            if now.duration_since(last_time).unwrap_or_default() < self.interval {
+                return Ok(());
+            }
+        }
+
+        self.publish(state)?;
+
+        for peer in fs::read_dir(&self.sync_dir)? {
+            let peer = peer?;
+            let peer_name = peer.file_name().to_string_lossy().to_string();
+            let queue = peer.path().join("queue");
+            if peer_name == self.name || peer_name.starts_with('.') || !queue.is_dir() {
+                continue;
+            }
+            self.import_peer(&peer_name, &queue, fuzzer, executor, state, manager)?;
+        }
+
+        // Entries imported from peers are not published again
+        let count = state.corpus().count();
+        let meta = state.metadata_mut().get_mut::<AflSyncMetadata>().unwrap();
+        meta.published = count;
+        meta.last_time = Some(now);
+
+        #[cfg(feature = "introspection")]
+        state.introspection_monitor_mut().finish_stage();
+
+        Ok(())
+    }
+}
+
+impl<CB, E, EM, I, S, Z> AflSyncStage<CB, E, EM, I, S, Z>
+where
+    CB: FnMut(&mut Z, &mut S, &Path) -> Result<I, Error>,
+    I: Input,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasRand + HasMetadata,
+    Z: Evaluator<E, EM, I, S>,
+{
+    /// Creates a new [`AflSyncStage`] for the peer `name` in `sync_dir`, syncing at most every `interval`
+    #[must_use]
+    pub fn new(sync_dir: PathBuf, name: &str, interval: Duration, load_callback: CB) -> Self {
+        Self {
+            sync_dir,
+            name: name.to_string(),
+            interval,
+            load_callback,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The `queue/` directory our own entries are published to
+    #[must_use]
+    pub fn own_queue(&self) -> PathBuf {
+        self.sync_dir.join(&self.name).join("queue")
+    }
+
+    /// The id following the highest id in our own queue
+    fn next_own_id(&self) -> Result<usize, Error> {
+        let queue = self.own_queue();
+        fs::create_dir_all(&queue)?;
+        let mut next = 0;
+        for entry in fs::read_dir(&queue)? {
+            if let Some(id) = entry?.file_name().to_str().and_then(parse_afl_id) {
+                next = next.max(id + 1);
+            }
+        }
+        Ok(next)
+    }
+
+    /// Write all corpus entries found since the last sync to our own queue
+    fn publish(&mut self, state: &mut S) -> Result<(), Error> {
+        let queue = self.own_queue();
+        let (published, mut next_id) = {
+            let meta = state.metadata().get::<AflSyncMetadata>().unwrap();
+            (meta.published, meta.next_id)
+        };
+
+        for idx in published..state.corpus().count() {
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            let already_there = testcase
+                .filename()
+                .as_ref()
+                .map_or(false, |filename| Path::new(filename).starts_with(&queue));
+            if already_there {
+                continue;
+            }
+            let id = lock_afl_id(&queue, &mut next_id);
+            let name = format!("id:{:06},execs:{}", id, testcase.executions());
+            testcase.load_input()?.to_file(queue.join(name))?;
+        }
+
+        state
+            .metadata_mut()
+            .get_mut::<AflSyncMetadata>()
+            .unwrap()
+            .next_id = next_id;
+        Ok(())
+    }
+
+    /// Import all entries of a peer's queue with an id of at least the peer's cursor
+    fn import_peer(
+        &mut self,
+        peer_name: &str,
+        queue: &Path,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let cursor = state
+            .metadata()
+            .get::<AflSyncMetadata>()
+            .unwrap()
+            .cursors
+            .get(peer_name)
+            .copied()
+            .unwrap_or(0);
+
+        let mut entries = vec![];
+        for entry in fs::read_dir(queue)? {
+            let entry = entry?;
+            if let Some(id) = entry.file_name().to_str().and_then(parse_afl_id) {
+                if id >= cursor && entry.file_type()?.is_file() {
+                    entries.push((id, entry.path()));
+                }
+            }
+        }
+        entries.sort_unstable();
+
+        state.add_metadata(StageOpMetadata {
+            op: "sync".to_string(),
+        });
+        let mut next_cursor = cursor;
+        for (id, path) in entries {
+            // A file that can't be loaded does not stop the sync of the others
+            match (self.load_callback)(fuzzer, state, &path) {
+                Ok(input) => drop(fuzzer.evaluate_input(state, executor, manager, input)?),
+                Err(err) => println!(
+                    "Skipping {} of {}, failed to load: {:?}",
+                    path.display(),
+                    peer_name,
+                    err
+                ),
+            }
+            next_cursor = id + 1;
+        }
+        let _ = state.metadata_mut().remove::<StageOpMetadata>();
+
+        state
+            .metadata_mut()
+            .get_mut::<AflSyncMetadata>()
+            .unwrap()
+            .cursors
+            .insert(peer_name.to_string(), next_cursor);
+        Ok(())
+    }
+}
+
+impl<E, EM, I, S, Z> AflSyncStage<fn(&mut Z, &mut S, &Path) -> Result<I, Error>, E, EM, I, S, Z>
+where
+    I: Input,
+    S: HasClientPerfMonitor + HasCorpus<I> + HasRand + HasMetadata,
+    Z: Evaluator<E, EM, I, S>,
+{
+    /// Creates a new [`AflSyncStage`] invoking `Input::from_file` to load inputs
+    #[must_use]
+    pub fn with_from_file(sync_dir: PathBuf, name: &str, interval: Duration) -> Self {
+        fn load_callback<Z, S, I: Input>(_: &mut Z, _: &mut S, p: &Path) -> Result<I, Error> {
+            I::from_file(p)
+        }
+        Self::new(sync_dir, name, interval, load_callback::<_, _, I>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{cell::RefCell, time::Duration};
+    use std::{env, fs, path::PathBuf};
+
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{ondisk::parse_afl_id, Corpus, InMemoryCorpus, Testcase},
+        events::SimpleEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        inputs::{BytesInput, HasBytesVec, Input},
+        monitors::NopMonitor,
+        schedulers::QueueScheduler,
+        stages::{AflSyncMetadata, AflSyncStage, Stage},
+        state::{HasCorpus, HasMetadata, StdState},
+        Error, StdFuzzer,
+    };
+
+    #[test]
+    fn test_afl_sync_stage() {
+        let sync_dir = env::temp_dir().join(format!("libafl_afl_sync_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&sync_dir);
+        let peer_queue = sync_dir.join("peer").join("queue");
+        fs::create_dir_all(&peer_queue).unwrap();
+        fs::write(peer_queue.join("id:000000,orig:a"), b"a").unwrap();
+        fs::write(peer_queue.join("id:000001,orig:bad"), b"bad").unwrap();
+        fs::write(peer_queue.join("id:000002,src:000000"), b"c").unwrap();
+
+        let own_queue = sync_dir.join("main").join("queue");
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        corpus
+            .add(Testcase::new(BytesInput::new(b"own".to_vec())))
+            .unwrap();
+        corpus
+            .add(Testcase::new(BytesInput::new(b"own2".to_vec())))
+            .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::<BytesInput>::new(),
+            tuple_list!(),
+        );
+        let mut manager = SimpleEventManager::new(NopMonitor::new());
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+        let mut harness = |_input: &BytesInput| ExitKind::Ok;
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(),
+            &mut fuzzer,
+            &mut state,
+            &mut manager,
+        )
+        .unwrap();
+
+        let loaded: RefCell<Vec<PathBuf>> = RefCell::new(vec![]);
+        let load_callback = |_: &mut _, _: &mut _, path: &std::path::Path| {
+            loaded.borrow_mut().push(path.to_path_buf());
+            let input = BytesInput::from_file(path)?;
+            if input.bytes() == b"bad" {
+                return Err(Error::Serialize("bad entry".to_string()));
+            }
+            Ok(input)
+        };
+        let mut stage = AflSyncStage::new(sync_dir.clone(), "main", Duration::ZERO, load_callback);
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut manager, 0)
+            .unwrap();
+
+        // The bad entry is skipped, and the cursor moves past it
+        assert_eq!(loaded.borrow().len(), 3);
+        let meta = state.metadata().get::<AflSyncMetadata>().unwrap();
+        assert_eq!(meta.cursors.get("peer"), Some(&3));
+
+        // Our entries are published with consecutive ids
+        let published = |queue: &std::path::Path| {
+            let mut names: Vec<_> = fs::read_dir(queue)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+                .filter(|name| parse_afl_id(name).is_some())
+                .collect();
+            names.sort();
+            names
+        };
+        let names = published(&own_queue);
+        assert_eq!(names.len(), 2);
+        assert!(names[0].starts_with("id:000000,"));
+        assert!(names[1].starts_with("id:000001,"));
+        let meta = state.metadata().get::<AflSyncMetadata>().unwrap();
+        assert_eq!(meta.next_id, 2);
+
+        // Nothing new since the last sync
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut manager, 0)
+            .unwrap();
+        assert_eq!(loaded.borrow().len(), 3);
+        assert_eq!(published(&own_queue).len(), 2);
+
+        // A client sharing our queue, such as an `OnDiskCorpus::afl_queue`, took the next id
+        fs::write(own_queue.join(".id:000002.lafl_lock"), b"").unwrap();
+        state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"own3".to_vec())))
+            .unwrap();
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut manager, 0)
+            .unwrap();
+        let names = published(&own_queue);
+        assert_eq!(names.len(), 3);
+        assert!(names[2].starts_with("id:000003,"));
+
+        drop(stage);
+        fs::remove_dir_all(&sync_dir).unwrap();
+    }
+}
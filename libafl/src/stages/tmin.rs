@@ -0,0 +1,521 @@
+//! The [`MinimizationStage`] shrinks a testcase (`tmin`), for example a crash, while a [`Feedback`] still holds for it.
+
+use alloc::string::{String, ToString};
+use core::{fmt::Debug, marker::PhantomData};
+
+use crate::{
+    bolts::{current_time, tuples::Named, HasLen},
+    corpus::Corpus,
+    events::EventFirer,
+    executors::{Executor, ExitKind, HasObservers},
+    feedbacks::{Feedback, FeedbackFactory},
+    fuzzer::{ExecutesInput, HasScheduler},
+    inputs::Input,
+    mark_feature_time,
+    mutators::{MutationResult, Mutator},
+    observers::{MapObserver, ObserverWithHashField, ObserversTuple},
+    schedulers::Scheduler,
+    stages::Stage,
+    start_timer,
+    state::{HasClientPerfMonitor, HasCorpus},
+    Error,
+};
+
+#[cfg(feature = "introspection")]
+use crate::monitors::PerfFeature;
+
+/// The default number of mutations tried by the [`MinimizationStage`] per testcase
+pub static DEFAULT_MINIMIZATION_RUNS: usize = 1024;
+
+/// A stage that minimizes a testcase using the given [`Mutator`].
+/// Only mutations that make the input smaller are executed, so any mutator is used in a delete-biased way.
+/// A smaller input is kept only if the [`Feedback`] created by the [`FeedbackFactory`] from the run of the
+/// original input still holds, for example the same map hash, the same backtrace, or a crash.
+/// In the end, the [`crate::corpus::Testcase`] is replaced with the smallest input found.
+#[derive(Clone, Debug)]
+pub struct MinimizationStage<CS, E, EM, F, FF, I, M, OT, S, Z>
+where
+    I: Input + HasLen,
+    M: Mutator<I, S>,
+{
+    mutator: M,
+    factory: FF,
+    runs: usize,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(CS, E, EM, F, I, OT, S, Z)>,
+}
+
+impl<CS, E, EM, F, FF, I, M, OT, S, Z> Stage<E, EM, S, Z>
+    for MinimizationStage<CS, E, EM, F, FF, I, M, OT, S, Z>
+where
+    CS: Scheduler<I, S>,
+    E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    EM: EventFirer<I>,
+    F: Feedback<I, S>,
+    FF: FeedbackFactory<F, I, S, OT>,
+    I: Input + HasLen,
+    M: Mutator<I, S>,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasCorpus<I>,
+    Z: ExecutesInput<I, OT, S, Z> + HasScheduler<CS, I, S>,
+{
+    #[allow(clippy::cast_possible_wrap)]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        start_timer!(state);
+        let original = state
+            .corpus()
+            .get(corpus_idx)?
+            .borrow_mut()
+            .load_input()?
+            .clone();
+        mark_feature_time!(state, PerfFeature::GetInputFromCorpus);
+
+        let exit_kind = fuzzer.execute_input(state, executor, manager, &original)?;
+        let mut feedback = self.factory.create_feedback(executor.observers());
+        // Nothing to preserve if the feedback does not even hold for the original input
+        if !feedback_holds(
+            &mut feedback,
+            state,
+            manager,
+            executor.observers(),
+            &original,
+            exit_kind,
+        )? {
+            return Ok(());
+        }
+
+        let original_len = original.len();
+        let mut best = original;
+        let mut best_exec_time = None;
+
+        for i in 0..self.runs {
+            if best.len() == 0 {
+                break;
+            }
+
+            let mut input = best.clone();
+            start_timer!(state);
+            let mutated = self.mutator.mutate(state, &mut input, i as i32)?;
+            mark_feature_time!(state, PerfFeature::Mutate);
+
+            // Only smaller inputs are executed, the mutator sees the end of each mutation anyway
+            if mutated == MutationResult::Mutated && input.len() < best.len() {
+                let start = current_time();
+                let exit_kind = fuzzer.execute_input(state, executor, manager, &input)?;
+                let exec_time = current_time() - start;
+                if feedback_holds(
+                    &mut feedback,
+                    state,
+                    manager,
+                    executor.observers(),
+                    &input,
+                    exit_kind,
+                )? {
+                    best = input;
+                    best_exec_time = Some(exec_time);
+                }
+            }
+
+            start_timer!(state);
+            self.mutator.post_exec(state, i as i32, None)?;
+            mark_feature_time!(state, PerfFeature::MutatePostExec);
+        }
+
+        if best.len() < original_len {
+            let old = state.corpus().get(corpus_idx)?.borrow().clone();
+            let mut testcase = old.clone();
+            testcase.set_input(best);
+            if let Some(exec_time) = best_exec_time {
+                testcase.set_exec_time(exec_time);
+            }
+            if let Some(filename) = testcase.filename() {
+                testcase.input().as_ref().unwrap().to_file(filename)?;
+            }
+            state.corpus_mut().replace(corpus_idx, testcase)?;
+            fuzzer.scheduler().on_replace(state, corpus_idx, &old)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<CS, E, EM, F, FF, I, M, OT, S, Z> MinimizationStage<CS, E, EM, F, FF, I, M, OT, S, Z>
+where
+    CS: Scheduler<I, S>,
+    E: Executor<EM, I, S, Z> + HasObservers<I, OT, S>,
+    EM: EventFirer<I>,
+    F: Feedback<I, S>,
+    FF: FeedbackFactory<F, I, S, OT>,
+    I: Input + HasLen,
+    M: Mutator<I, S>,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor + HasCorpus<I>,
+    Z: ExecutesInput<I, OT, S, Z> + HasScheduler<CS, I, S>,
+{
+    /// Creates a new minimization stage, trying [`DEFAULT_MINIMIZATION_RUNS`] mutations per testcase
+    pub fn new(mutator: M, factory: FF) -> Self {
+        Self::with_runs(mutator, factory, DEFAULT_MINIMIZATION_RUNS)
+    }
+
+    /// Creates a new minimization stage, trying `runs` mutations per testcase
+    pub fn with_runs(mutator: M, factory: FF, runs: usize) -> Self {
+        Self {
+            mutator,
+            factory,
+            runs,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The mutator used to shrink the testcases
+    pub fn mutator(&self) -> &M {
+        &self.mutator
+    }
+
+    /// The mutator used to shrink the testcases (mutable)
+    pub fn mutator_mut(&mut self) -> &mut M {
+        &mut self.mutator
+    }
+}
+
+/// Check if the feedback holds for the last run. No metadata is kept.
+fn feedback_holds<EM, F, I, OT, S>(
+    feedback: &mut F,
+    state: &mut S,
+    manager: &mut EM,
+    observers: &OT,
+    input: &I,
+    exit_kind: ExitKind,
+) -> Result<bool, Error>
+where
+    EM: EventFirer<I>,
+    F: Feedback<I, S>,
+    I: Input,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor,
+{
+    start_timer!(state);
+    let holds = feedback.is_interesting(state, manager, input, observers, &exit_kind)?;
+    feedback.discard_metadata(state, input)?;
+    mark_feature_time!(state, PerfFeature::GetFeedbackInterestingAll);
+    Ok(holds)
+}
+
+/// A [`Feedback`] that holds if the hash of a [`MapObserver`] equals the hash of the original run
+#[derive(Clone, Debug)]
+pub struct MapEqualityFeedback<M> {
+    name: String,
+    obs_name: String,
+    orig_hash: u64,
+    phantom: PhantomData<M>,
+}
+
+impl<M> Named for MapEqualityFeedback<M> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<I, M, S> Feedback<I, S> for MapEqualityFeedback<M>
+where
+    I: Input,
+    M: MapObserver,
+    S: HasClientPerfMonitor,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let obs = observers
+            .match_name::<M>(&self.obs_name)
+            .expect("Should have been provided valid observer name.");
+        Ok(obs.hash() == self.orig_hash)
+    }
+}
+
+/// A [`FeedbackFactory`] for [`MapEqualityFeedback`]s, keeping the coverage of a testcase while minimizing it
+#[derive(Clone, Debug)]
+pub struct MapEqualityFactory<M> {
+    obs_name: String,
+    phantom: PhantomData<M>,
+}
+
+impl<M> MapEqualityFactory<M>
+where
+    M: MapObserver,
+{
+    /// Creates a new map equality feedback factory for the given observer
+    #[must_use]
+    pub fn new_from_observer(obs: &M) -> Self {
+        Self {
+            obs_name: obs.name().to_string(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, M, OT, S> FeedbackFactory<MapEqualityFeedback<M>, I, S, OT> for MapEqualityFactory<M>
+where
+    I: Input,
+    M: MapObserver,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor,
+{
+    fn create_feedback(&self, observers: &OT) -> MapEqualityFeedback<M> {
+        let obs = observers
+            .match_name::<M>(&self.obs_name)
+            .expect("Should have been provided valid observer name.");
+        MapEqualityFeedback {
+            name: "MapEq".to_string(),
+            obs_name: self.obs_name.clone(),
+            orig_hash: obs.hash(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// A [`Feedback`] that holds if the hash of an [`ObserverWithHashField`], such as a backtrace,
+/// equals the hash of the original run.
+/// Without a hash in the original run, it never holds, so the [`MinimizationStage`] has nothing to preserve.
+#[derive(Clone, Debug)]
+pub struct HashEqualityFeedback<O> {
+    name: String,
+    obs_name: String,
+    orig_hash: Option<u64>,
+    phantom: PhantomData<O>,
+}
+
+impl<O> Named for HashEqualityFeedback<O> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<I, O, S> Feedback<I, S> for HashEqualityFeedback<O>
+where
+    I: Input,
+    O: ObserverWithHashField + Named + Debug,
+    S: HasClientPerfMonitor,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        let obs = observers
+            .match_name::<O>(&self.obs_name)
+            .expect("Should have been provided valid observer name.");
+        Ok(self.orig_hash.is_some() && *obs.hash() == self.orig_hash)
+    }
+}
+
+/// A [`FeedbackFactory`] for [`HashEqualityFeedback`]s, keeping the same crash while minimizing a testcase
+#[derive(Clone, Debug)]
+pub struct HashEqualityFactory<O> {
+    obs_name: String,
+    phantom: PhantomData<O>,
+}
+
+impl<O> HashEqualityFactory<O>
+where
+    O: ObserverWithHashField + Named + Debug,
+{
+    /// Creates a new hash equality feedback factory for the given observer
+    #[must_use]
+    pub fn new_from_observer(obs: &O) -> Self {
+        Self {
+            obs_name: obs.name().to_string(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, O, OT, S> FeedbackFactory<HashEqualityFeedback<O>, I, S, OT> for HashEqualityFactory<O>
+where
+    I: Input,
+    O: ObserverWithHashField + Named + Debug,
+    OT: ObserversTuple<I, S>,
+    S: HasClientPerfMonitor,
+{
+    fn create_feedback(&self, observers: &OT) -> HashEqualityFeedback<O> {
+        let obs = observers
+            .match_name::<O>(&self.obs_name)
+            .expect("Should have been provided valid observer name.");
+        HashEqualityFeedback {
+            name: "HashEq".to_string(),
+            obs_name: self.obs_name.clone(),
+            orig_hash: *obs.hash(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::SimpleEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        feedbacks::{Feedback, FeedbackFactory},
+        inputs::{BytesInput, HasBytesVec},
+        monitors::NopMonitor,
+        mutators::{mutations::BytesDeleteMutator, StdScheduledMutator},
+        observers::{ASANBacktraceObserver, ObserverWithHashField, StdMapObserver},
+        schedulers::QueueScheduler,
+        stages::{HashEqualityFactory, MapEqualityFactory, MinimizationStage, Stage},
+        state::{HasCorpus, StdState},
+        StdFuzzer,
+    };
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, (), BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    static mut TMIN_MAP: [u8; 4] = [0; 4];
+
+    #[test]
+    fn test_minimization_stage() {
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        corpus
+            .add(Testcase::new(BytesInput::new(b"xxAxx".to_vec())))
+            .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::<BytesInput>::new(),
+            tuple_list!(),
+        );
+        let mut manager = SimpleEventManager::new(NopMonitor::new());
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+
+        // The coverage only depends on the presence of an `A`
+        let mut harness = |input: &BytesInput| {
+            let map = unsafe { &mut TMIN_MAP };
+            if input.bytes().contains(&b'A') {
+                map[0] = 1;
+            } else {
+                map[1] = 1;
+            }
+            ExitKind::Ok
+        };
+        let observer = StdMapObserver::new("map", unsafe { &mut TMIN_MAP });
+        let factory = MapEqualityFactory::new_from_observer(&observer);
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut manager,
+        )
+        .unwrap();
+
+        let mutator = StdScheduledMutator::new(tuple_list!(BytesDeleteMutator::new()));
+        let mut stage = MinimizationStage::with_runs(mutator, factory, 256);
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut manager, 0)
+            .unwrap();
+
+        // The mutator does not shrink inputs of 2 bytes any further
+        let testcase = state.corpus().get(0).unwrap().borrow();
+        let minimized = testcase.input().as_ref().unwrap().bytes();
+        assert_eq!(minimized.len(), 2);
+        assert!(minimized.contains(&b'A'));
+        // The time is the one of the minimized input
+        assert!(testcase.exec_time().is_some());
+    }
+
+    #[test]
+    fn test_minimization_stage_without_hash() {
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        corpus
+            .add(Testcase::new(BytesInput::new(b"xxAxx".to_vec())))
+            .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::<BytesInput>::new(),
+            tuple_list!(),
+        );
+        let mut manager = SimpleEventManager::new(NopMonitor::new());
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+
+        // The entry does not crash, so the backtrace observer has no hash
+        let mut harness = |_input: &BytesInput| ExitKind::Ok;
+        let observer = ASANBacktraceObserver::new("asan");
+        let factory = HashEqualityFactory::new_from_observer(&observer);
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut manager,
+        )
+        .unwrap();
+
+        let mutator = StdScheduledMutator::new(tuple_list!(BytesDeleteMutator::new()));
+        let mut stage = MinimizationStage::with_runs(mutator, factory, 256);
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut manager, 0)
+            .unwrap();
+
+        let testcase = state.corpus().get(0).unwrap().borrow();
+        assert_eq!(testcase.input().as_ref().unwrap().bytes(), b"xxAxx");
+    }
+
+    #[test]
+    fn test_hash_equality_feedback() {
+        let mut state: TestState = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::<BytesInput>::new(),
+            tuple_list!(),
+        );
+        let mut manager = SimpleEventManager::new(NopMonitor::new());
+        let input = BytesInput::new(b"A".to_vec());
+
+        let mut observers = tuple_list!(ASANBacktraceObserver::new("asan"));
+        let factory = HashEqualityFactory::new_from_observer(&observers.0);
+
+        // Without a hash of the original run, there is nothing to compare to
+        let mut feedback =
+            FeedbackFactory::<_, BytesInput, TestState, _>::create_feedback(&factory, &observers);
+        assert!(!feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+
+        observers.0.update_hash(5);
+        let mut feedback =
+            FeedbackFactory::<_, BytesInput, TestState, _>::create_feedback(&factory, &observers);
+        assert!(feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+
+        observers.0.update_hash(6);
+        assert!(!feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+    }
+}
@@ -4,7 +4,7 @@ use core::{fmt::Debug, marker::PhantomData};
 
 use crate::{
     corpus::Corpus,
-    executors::{Executor, HasObservers, ShadowExecutor},
+    executors::{Executor, ExitKind, HasObservers, ShadowExecutor},
     inputs::Input,
     mark_feature_time,
     observers::ObserversTuple,
@@ -56,25 +56,7 @@ where
             .clone();
         mark_feature_time!(state, PerfFeature::GetInputFromCorpus);
 
-        start_timer!(state);
-        self.tracer_executor
-            .observers_mut()
-            .pre_exec_all(state, &input)?;
-        mark_feature_time!(state, PerfFeature::PreExecObservers);
-
-        start_timer!(state);
-        let exit_kind = self
-            .tracer_executor
-            .run_target(fuzzer, state, manager, &input)?;
-        mark_feature_time!(state, PerfFeature::TargetExecution);
-
-        *state.executions_mut() += 1;
-
-        start_timer!(state);
-        self.tracer_executor
-            .observers_mut()
-            .post_exec_all(state, &input, &exit_kind)?;
-        mark_feature_time!(state, PerfFeature::PostExecObservers);
+        self.trace(fuzzer, state, manager, &input)?;
 
         Ok(())
     }
@@ -99,6 +81,37 @@ where
     pub fn executor(&self) -> &TE {
         &self.tracer_executor
     }
+
+    /// Runs the tracer executor on the given input, triggering its observers
+    pub fn trace(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        start_timer!(state);
+        self.tracer_executor
+            .observers_mut()
+            .pre_exec_all(state, input)?;
+        mark_feature_time!(state, PerfFeature::PreExecObservers);
+
+        start_timer!(state);
+        let exit_kind = self
+            .tracer_executor
+            .run_target(fuzzer, state, manager, input)?;
+        mark_feature_time!(state, PerfFeature::TargetExecution);
+
+        *state.executions_mut() += 1;
+
+        start_timer!(state);
+        self.tracer_executor
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+        mark_feature_time!(state, PerfFeature::PostExecObservers);
+
+        Ok(exit_kind)
+    }
 }
 
 /// A stage that runs the shadow executor using also the shadow observers
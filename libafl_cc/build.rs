@@ -179,6 +179,7 @@ fn main() {
         println!("cargo:rerun-if-changed=src/afl-coverage-pass.cc");
         println!("cargo:rerun-if-changed=src/autotokens-pass.cc");
         println!("cargo:rerun-if-changed=src/coverage-accounting-pass.cc");
+        println!("cargo:rerun-if-changed=src/ctx-pass.cc");
 
         assert!(Command::new(llvm_bindir.join("clang++"))
             .args(&cxxflags)
@@ -223,6 +224,17 @@ fn main() {
             .status()
             .expect("Failed to compile coverage-accounting-pass.cc")
             .success());
+
+        assert!(Command::new(llvm_bindir.join("clang++"))
+            .args(&cxxflags)
+            .args(&custom_flags)
+            .arg(src_dir.join("ctx-pass.cc"))
+            .args(&ldflags)
+            .args(&["-fPIC", "-shared", "-o"])
+            .arg(out_dir.join(format!("ctx-pass.{}", dll_extension())))
+            .status()
+            .expect("Failed to compile ctx-pass.cc")
+            .success());
     } else {
         write!(
             clang_constants_file,
@@ -0,0 +1,206 @@
+/*
+   LibAFL - Context sensitivity LLVM pass
+   --------------------------------------------------
+
+   Based on the CTX instrumentation of the AFL++ LLVM-mode coverage pass.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+   This pass only maintains the calling context in __libafl_sancov_ctx:
+   each function that calls other functions xors its own random id into the
+   context on entry, and restores the context of its caller on return.
+   The sancov pcguard runtime of libafl_targets (feature sancov_ctx) hashes
+   the context into the edge index.
+
+ */
+
+#include "common-llvm.h"
+
+#include <time.h>
+
+#include "llvm/Support/CommandLine.h"
+#include "llvm/IR/IRBuilder.h"
+#include "llvm/IR/BasicBlock.h"
+#include "llvm/IR/Instructions.h"
+#include "llvm/IR/Module.h"
+#include "llvm/Support/Debug.h"
+
+#define MAP_SIZE LIBAFL_EDGES_MAP_SIZE
+
+using namespace llvm;
+
+static cl::opt<bool> Debug("debug", cl::desc("Debug prints"), cl::init(false), cl::NotHidden);
+
+namespace {
+
+#ifdef USE_NEW_PM
+class CtxPass : public PassInfoMixin<CtxPass> {
+ public:
+  CtxPass() {
+#else
+class CtxPass : public ModulePass {
+ public:
+  static char ID;
+  CtxPass() : ModulePass(ID) {
+#endif
+  }
+
+#ifdef USE_NEW_PM
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+#else
+  bool runOnModule(Module &M) override;
+#endif
+
+ protected:
+  uint32_t map_size = MAP_SIZE;
+
+};
+
+}  // namespace
+
+#ifdef USE_NEW_PM
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {
+    LLVM_PLUGIN_API_VERSION, "CtxPass", "v0.1",
+    /* lambda to insert our pass into the pass pipeline. */
+    [](PassBuilder &PB) {
+       using OptimizationLevel = typename PassBuilder::OptimizationLevel;
+       PB.registerOptimizerLastEPCallback(
+         [](ModulePassManager &MPM, OptimizationLevel OL) {
+           MPM.addPass(CtxPass());
+         }
+       );
+    }
+  };
+}
+#else
+
+char CtxPass::ID = 0;
+#endif
+
+/* Does the function call other, non intrinsic, functions? */
+static bool hasCalls(Function &F) {
+
+  for (auto &BB : F) {
+
+    for (auto &IN : BB) {
+
+      if (auto *Call = dyn_cast<CallBase>(&IN)) {
+
+        Function *Callee = Call->getCalledFunction();
+        if (!Callee || !Callee->isIntrinsic()) return true;
+
+      }
+
+    }
+
+  }
+
+  return false;
+
+}
+
+#ifdef USE_NEW_PM
+PreservedAnalyses CtxPass::run(Module &M, ModuleAnalysisManager &MAM) {
+#else
+bool CtxPass::runOnModule(Module &M) {
+#endif
+
+  LLVMContext &C = M.getContext();
+
+  IntegerType *Int32Ty = IntegerType::getInt32Ty(C);
+
+#ifdef USE_NEW_PM
+  auto PA = PreservedAnalyses::all();
+#endif
+
+  /* Setup random() so we get Actually Random(TM) */
+  srand(time(NULL));
+
+#if defined(__ANDROID__) || defined(__HAIKU__)
+  GlobalVariable *AFLContext = new GlobalVariable(
+      M, Int32Ty, false, GlobalValue::ExternalLinkage, 0, "__libafl_sancov_ctx");
+#else
+  GlobalVariable *AFLContext = new GlobalVariable(
+      M, Int32Ty, false, GlobalValue::ExternalLinkage, 0, "__libafl_sancov_ctx",
+      0, GlobalVariable::GeneralDynamicTLSModel, 0, false);
+#endif
+
+  int inst_funcs = 0;
+
+  for (auto &F : M) {
+
+    if (F.isDeclaration() || !hasCalls(F)) { continue; }
+
+    if (Debug)
+      fprintf(stderr, "FUNCTION: %s (%zu)\n", F.getName().str().c_str(),
+              F.size());
+
+    /* Load the context of the caller and store our own */
+
+    BasicBlock::iterator IP = F.getEntryBlock().getFirstInsertionPt();
+    IRBuilder<>          IRB(&(*IP));
+
+    LoadInst *PrevCtx = IRB.CreateLoad(AFLContext);
+    PrevCtx->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+
+    Value *NewCtx =
+        IRB.CreateXor(PrevCtx, ConstantInt::get(Int32Ty, RandBelow(map_size)));
+    IRB.CreateStore(NewCtx, AFLContext)
+        ->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+
+    /* Restore the context of the caller on each exit */
+
+    for (auto &BB : F) {
+
+      Instruction *Inst = BB.getTerminator();
+      if (isa<ReturnInst>(Inst) || isa<ResumeInst>(Inst)) {
+
+        IRBuilder<> Post_IRB(Inst);
+        Post_IRB.CreateStore(PrevCtx, AFLContext)
+            ->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+
+      }
+
+    }
+
+    inst_funcs++;
+
+  }
+
+  if (Debug) {
+
+    if (!inst_funcs)
+      fprintf(stderr, "No instrumentation targets found.\n");
+    else
+      fprintf(stderr, "Instrumented %d functions.\n", inst_funcs);
+
+  }
+
+#ifdef USE_NEW_PM
+  return PA;
+#else
+  return true;
+#endif
+
+}
+
+#ifndef USE_NEW_PM
+static void registerCtxPass(const PassManagerBuilder &,
+                            legacy::PassManagerBase &PM) {
+
+  PM.add(new CtxPass());
+
+}
+
+static RegisterStandardPasses RegisterCtxPass(
+    PassManagerBuilder::EP_OptimizerLast, registerCtxPass);
+
+static RegisterStandardPasses RegisterCtxPass0(
+    PassManagerBuilder::EP_EnabledOnOptLevel0, registerCtxPass);
+#endif
@@ -0,0 +1,104 @@
+//! Deterministic hang detection for `QEMU`, counting the executed blocks against a budget.
+//! Once the budget of a run is exhausted, `SIGALRM` is raised and the `QemuExecutor` reports a timeout.
+
+use core::pin::Pin;
+use libafl::inputs::Input;
+pub use libafl_targets::budget::{budget_start, budget_stop, budget_tick, EXEC_BUDGET, EXEC_COUNT};
+
+use crate::{
+    emu::Emulator,
+    helper::{QemuHelper, QemuHelperTuple, QemuInstrumentationFilter},
+    hooks::QemuHooks,
+};
+
+/// A [`QemuHelper`] that counts the executed blocks of each run against a budget,
+/// raising `SIGALRM` once it is exhausted, for reproducible hangs.
+#[derive(Debug)]
+pub struct QemuBudgetHelper {
+    filter: QemuInstrumentationFilter,
+    budget: u64,
+}
+
+impl QemuBudgetHelper {
+    /// Create a new [`QemuBudgetHelper`], aborting runs after `budget` executed blocks
+    #[must_use]
+    pub fn new(filter: QemuInstrumentationFilter, budget: u64) -> Self {
+        Self { filter, budget }
+    }
+
+    /// Set the budget of blocks for the next runs, `0` for no budget
+    pub fn set_budget(&mut self, budget: u64) {
+        self.budget = budget;
+    }
+
+    /// The budget of blocks of a run
+    #[must_use]
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    /// If the block at `addr` passes the instrumentation filter, and counts against the budget
+    #[must_use]
+    pub fn must_instrument(&self, addr: u64) -> bool {
+        self.filter.allowed(addr)
+    }
+}
+
+impl<I, S> QemuHelper<I, S> for QemuBudgetHelper
+where
+    I: Input,
+{
+    fn init_hooks<'a, QT>(&self, hooks: Pin<&QemuHooks<'a, I, QT, S>>)
+    where
+        QT: QemuHelperTuple<I, S>,
+    {
+        hooks.block_generation(gen_budget_block_ids::<I, QT, S>);
+        hooks.block_execution(trace_block_budget::<I, QT, S>);
+    }
+
+    fn pre_exec(&mut self, _emulator: &Emulator, _input: &I) {
+        unsafe {
+            budget_start(self.budget);
+        }
+    }
+
+    fn post_exec(&mut self, _emulator: &Emulator, _input: &I) {
+        unsafe {
+            budget_stop();
+        }
+    }
+}
+
+/// The block generation hook of [`QemuBudgetHelper`], instruments the blocks that pass its filter
+pub fn gen_budget_block_ids<I, QT, S>(
+    _emulator: &Emulator,
+    helpers: &mut QT,
+    _state: Option<&mut S>,
+    pc: u64,
+) -> Option<u64>
+where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    if let Some(h) = helpers.match_first_type::<QemuBudgetHelper>() {
+        if !h.must_instrument(pc) {
+            return None;
+        }
+    }
+    Some(pc)
+}
+
+/// The block execution hook of [`QemuBudgetHelper`], counts the block with [`budget_tick`]
+pub fn trace_block_budget<I, QT, S>(
+    _emulator: &Emulator,
+    _helpers: &mut QT,
+    _state: Option<&mut S>,
+    _id: u64,
+) where
+    I: Input,
+    QT: QemuHelperTuple<I, S>,
+{
+    unsafe {
+        budget_tick();
+    }
+}
@@ -45,6 +45,8 @@ pub mod cmplog;
 pub use cmplog::QemuCmpLogHelper;
 pub mod snapshot;
 pub use snapshot::QemuSnapshotHelper;
+pub mod budget;
+pub use budget::QemuBudgetHelper;
 pub mod asan;
 pub use asan::{init_with_asan, QemuAsanHelper};
 
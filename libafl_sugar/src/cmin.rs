@@ -0,0 +1,154 @@
+//! In-Memory corpus minimization made easy.
+//! Use this sugar to reduce a `libfuzzer`-style corpus to the testcases needed to keep its coverage.
+
+use core::fmt::{self, Debug, Formatter};
+use std::{fs, path::PathBuf, time::Duration};
+use typed_builder::TypedBuilder;
+
+use libafl::{
+    bolts::{current_nanos, rands::StdRand, tuples::tuple_list, AsSlice},
+    corpus::{
+        Corpus, CorpusMinimizer, InMemoryCorpus, StdCorpusMinimizer, UnweightedCorpusMinimizer,
+    },
+    events::SimpleEventManager,
+    executors::{inprocess::InProcessExecutor, ExitKind, TimeoutExecutor},
+    feedbacks::{CrashFeedback, MapFeedbackState, MaxMapFeedback},
+    fuzzer::StdFuzzer,
+    inputs::{BytesInput, HasTargetBytes, Input},
+    monitors::SimpleMonitor,
+    observers::{HitcountsMapObserver, StdMapObserver},
+    schedulers::QueueScheduler,
+    state::{HasCorpus, StdState},
+    Error,
+};
+
+use libafl_targets::{EDGES_MAP, MAX_EDGES_NUM};
+
+use crate::DEFAULT_TIMEOUT_SECS;
+
+/// In-Memory corpus minimization made easy.
+/// Runs every input of the input directories once, and writes the minimized corpus to the output directory.
+/// The inputs should not crash the harness, as a crash aborts the minimization.
+#[derive(TypedBuilder)]
+pub struct InMemoryBytesCminSugar<'a, H>
+where
+    H: FnMut(&[u8]),
+{
+    /// Timeout of the executor
+    #[builder(default = None)]
+    timeout: Option<u64>,
+    /// Input directories
+    input_dirs: &'a [PathBuf],
+    /// Output directory
+    output_dir: PathBuf,
+    /// Flag if the testcases should be weighted by size and execution time (default is `true`)
+    #[builder(default = true)]
+    weighted: bool,
+    /// Bytes harness
+    #[builder(setter(strip_option))]
+    harness: Option<H>,
+}
+
+impl<H> Debug for InMemoryBytesCminSugar<'_, H>
+where
+    H: FnMut(&[u8]),
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InMemoryBytesCminSugar")
+            .field("timeout", &self.timeout)
+            .field("input_dirs", &self.input_dirs)
+            .field("output_dir", &self.output_dir)
+            .field("weighted", &self.weighted)
+            .field(
+                "harness",
+                if self.harness.is_some() {
+                    &"<harness_fn>"
+                } else {
+                    &"None"
+                },
+            )
+            .finish()
+    }
+}
+
+impl<'a, H> InMemoryBytesCminSugar<'a, H>
+where
+    H: FnMut(&[u8]),
+{
+    /// Run the corpus minimization, returning the number of testcases kept
+    pub fn run(&mut self) -> Result<usize, Error> {
+        let timeout = Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS));
+
+        if fs::create_dir_all(&self.output_dir).is_err() {
+            return Err(Error::IllegalArgument(format!(
+                "Out dir at {:?} is not a valid directory!",
+                &self.output_dir
+            )));
+        }
+
+        let mut harness_bytes = self.harness.take().unwrap();
+
+        // Create an observation channel using the coverage map
+        let edges = unsafe { &mut EDGES_MAP[0..MAX_EDGES_NUM] };
+        let edges_observer = HitcountsMapObserver::new(StdMapObserver::new("edges", edges));
+
+        let weighted_minimizer = StdCorpusMinimizer::new(&edges_observer);
+        let unweighted_minimizer = UnweightedCorpusMinimizer::new(&edges_observer);
+
+        // The state of the edges feedback.
+        let feedback_state = MapFeedbackState::with_observer(&edges_observer);
+        let feedback = MaxMapFeedback::new(&feedback_state, &edges_observer);
+        let objective = CrashFeedback::new();
+
+        let mut state = StdState::new(
+            StdRand::with_seed(current_nanos()),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            tuple_list!(feedback_state),
+        );
+
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+
+        let monitor = SimpleMonitor::new(|s| println!("{}", s));
+        let mut mgr = SimpleEventManager::new(monitor);
+
+        // The wrapped harness function, calling out to the LLVM-style harness
+        let mut harness = |input: &BytesInput| {
+            let target = input.target_bytes();
+            let buf = target.as_slice();
+            (harness_bytes)(buf);
+            ExitKind::Ok
+        };
+
+        let mut executor = TimeoutExecutor::new(
+            InProcessExecutor::new(
+                &mut harness,
+                tuple_list!(edges_observer),
+                &mut fuzzer,
+                &mut state,
+                &mut mgr,
+            )?,
+            timeout,
+        );
+
+        println!("Loading from {:?}", &self.input_dirs);
+        state.load_initial_inputs_forced(&mut fuzzer, &mut executor, &mut mgr, self.input_dirs)?;
+        let before = state.corpus().count();
+
+        if self.weighted {
+            weighted_minimizer.minimize(&mut fuzzer, &mut executor, &mut mgr, &mut state)?;
+        } else {
+            unweighted_minimizer.minimize(&mut fuzzer, &mut executor, &mut mgr, &mut state)?;
+        }
+
+        let after = state.corpus().count();
+        for idx in 0..after {
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            let input = testcase.load_input()?;
+            input.to_file(self.output_dir.join(input.generate_name(idx)))?;
+        }
+        println!("Minimized the corpus from {} to {} inputs.", before, after);
+
+        Ok(after)
+    }
+}
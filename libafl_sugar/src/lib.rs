@@ -60,6 +60,9 @@
 pub mod inmemory;
 pub use inmemory::InMemoryBytesCoverageSugar;
 
+pub mod cmin;
+pub use cmin::InMemoryBytesCminSugar;
+
 #[cfg(target_os = "linux")]
 pub mod qemu;
 #[cfg(target_os = "linux")]
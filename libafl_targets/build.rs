@@ -109,6 +109,12 @@ fn main() {
         .define("ACCOUNTING_MAP_SIZE", Some(&*format!("{}", acc_map_size)))
         .compile("coverage");
 
+    println!("cargo:rerun-if-changed=src/malloc_hooks.c");
+
+    cc::Build::new()
+        .file(src_dir.join("malloc_hooks.c"))
+        .compile("malloc_hooks");
+
     println!("cargo:rerun-if-changed=src/cmplog.h");
     println!("cargo:rerun-if-changed=src/cmplog.c");
 
@@ -0,0 +1,174 @@
+//! Hang detection by a budget of executed edges or blocks, instead of a wall-clock timer.
+//! The instrumentation calls [`budget_tick`] for each edge, with the `sancov_budget` feature for `sancov_pcguard`,
+//! or for each block, with the `QemuBudgetHelper` of `libafl_qemu`.
+//! Once the budget of a run is exhausted, `SIGALRM` is raised, handled by the [`libafl::executors::InProcessExecutor`]
+//! as a timeout, reporting [`ExitKind::Timeout`].
+//! The same input always hangs after the same number of edges, so, unlike a timer, hangs are reproducible
+//! and do not depend on the load of the machine.
+
+use core::fmt::{self, Debug, Formatter};
+
+use libafl::{
+    bolts::os::unix_signals::{raise, Signal},
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::Input,
+    observers::ObserversTuple,
+    Error,
+};
+
+/// The number of edges or blocks a run may execute, `0` for no budget
+pub static mut EXEC_BUDGET: u64 = 0;
+/// The number of edges or blocks executed in the current run
+pub static mut EXEC_COUNT: u64 = 0;
+
+/// Starts a new run with the given budget of edges or blocks, `0` for no budget
+///
+/// # Safety
+/// Writes the global budget state, not thread safe.
+#[inline]
+pub unsafe fn budget_start(budget: u64) {
+    EXEC_COUNT = 0;
+    EXEC_BUDGET = budget;
+}
+
+/// Stops the budget of the current run, returns the number of executed edges or blocks
+///
+/// # Safety
+/// Writes the global budget state, not thread safe.
+#[inline]
+pub unsafe fn budget_stop() -> u64 {
+    EXEC_BUDGET = 0;
+    EXEC_COUNT
+}
+
+/// Counts an executed edge or block, raises `SIGALRM` once the budget of the run is exhausted.
+///
+/// # Safety
+/// Writes the global budget state, not thread safe.
+#[inline]
+pub unsafe fn budget_tick() {
+    if EXEC_BUDGET != 0 {
+        EXEC_COUNT += 1;
+        if EXEC_COUNT > EXEC_BUDGET {
+            // Raise only once, if the signal is ignored, the run goes on
+            EXEC_BUDGET = 0;
+            raise(Signal::SigAlarm);
+        }
+    }
+}
+
+/// The budget executor is a wrapper that sets a budget of executed edges or blocks before each run,
+/// the deterministic alternative to the `TimeoutExecutor`.
+/// The wrapped executor has to handle `SIGALRM` as timeout, like the [`libafl::executors::InProcessExecutor`].
+pub struct BudgetExecutor<E> {
+    executor: E,
+    budget: u64,
+}
+
+impl<E> Debug for BudgetExecutor<E>
+where
+    E: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BudgetExecutor")
+            .field("executor", &self.executor)
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+impl<E> BudgetExecutor<E> {
+    /// Create a new [`BudgetExecutor`], wrapping the given `executor` and
+    /// aborting runs after `budget` executed edges or blocks
+    #[must_use]
+    pub fn new(executor: E, budget: u64) -> Self {
+        Self { executor, budget }
+    }
+
+    /// Set the budget of edges or blocks for the next runs, `0` for no budget
+    pub fn set_budget(&mut self, budget: u64) {
+        self.budget = budget;
+    }
+
+    /// The budget of edges or blocks of a run
+    #[must_use]
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    /// Retrieve the inner `Executor` that is wrapped by this `BudgetExecutor`.
+    pub fn inner(&mut self) -> &mut E {
+        &mut self.executor
+    }
+}
+
+impl<E, EM, I, S, Z> Executor<EM, I, S, Z> for BudgetExecutor<E>
+where
+    E: Executor<EM, I, S, Z>,
+    I: Input,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        unsafe {
+            budget_start(self.budget);
+        }
+        let ret = self.executor.run_target(fuzzer, state, mgr, input);
+        unsafe {
+            budget_stop();
+        }
+        ret
+    }
+
+    fn post_run_reset(&mut self) {
+        self.executor.post_run_reset();
+    }
+}
+
+impl<E, I, OT, S> HasObservers<I, OT, S> for BudgetExecutor<E>
+where
+    E: HasObservers<I, OT, S>,
+    OT: ObserversTuple<I, S>,
+{
+    #[inline]
+    fn observers(&self) -> &OT {
+        self.executor.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut OT {
+        self.executor.observers_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{budget_start, budget_stop, budget_tick};
+
+    #[test]
+    fn test_budget_counting() {
+        unsafe {
+            // Without a budget, nothing is counted
+            budget_start(0);
+            budget_tick();
+            assert_eq!(budget_stop(), 0);
+
+            // Up to the budget, no signal is raised
+            budget_start(10);
+            for _ in 0..10 {
+                budget_tick();
+            }
+            assert_eq!(budget_stop(), 10);
+
+            // Stopped runs are not counted, and each run starts from zero
+            budget_tick();
+            budget_start(3);
+            budget_tick();
+            assert_eq!(budget_stop(), 1);
+        }
+    }
+}
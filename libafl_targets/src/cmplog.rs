@@ -172,17 +172,153 @@ pub static mut libafl_cmplog_enabled: u8 = 0;
 
 pub use libafl_cmplog_enabled as CMPLOG_ENABLED;
 
-/// A [`CmpObserver`] observer for `CmpLog`
+/// The width of the `CmpLog` map of `AFL++`
+pub const AFL_CMPLOG_MAP_W: usize = 65536;
+/// The height of the `CmpLog` map of `AFL++`
+pub const AFL_CMPLOG_MAP_H: usize = 32;
+/// The height of the `CmpLog` routine map of `AFL++`
+pub const AFL_CMPLOG_MAP_RTN_H: usize = AFL_CMPLOG_MAP_H / 2;
+/// The maximum length of a routine argument logged by `AFL++`
+pub const AFL_CMPLOG_RTN_LEN: usize = 31;
+/// `AFL++` `CmpLog` instruction type
+pub const AFL_CMP_TYPE_INS: u64 = 1;
+/// `AFL++` `CmpLog` routine type
+pub const AFL_CMP_TYPE_RTN: u64 = 2;
+
+/// The header for `AFL++` `CmpLog` hits, a bitfield of 24 bits `hits`, 24 bits `id`,
+/// 5 bits `shape`, 2 bits `type`, 4 bits `attribute`, 1 bit `overflow`.
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct AFLppCmpLogHeader(u64);
+
+impl AFLppCmpLogHeader {
+    /// The number of times the comparison was executed
+    #[must_use]
+    pub fn hits(&self) -> usize {
+        (self.0 & 0xff_ffff) as usize
+    }
+
+    /// The size of the operands, minus one
+    #[must_use]
+    pub fn shape(&self) -> u64 {
+        (self.0 >> 48) & 0x1f
+    }
+
+    /// The type, [`AFL_CMP_TYPE_INS`] or [`AFL_CMP_TYPE_RTN`]
+    #[must_use]
+    pub fn type_(&self) -> u64 {
+        (self.0 >> 53) & 0x3
+    }
+}
+
+/// The operands logged by `AFL++` `CmpLog`, the `128` bit parts are unused for smaller comparisons.
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct AFLppCmpLogOperands {
+    v0: u64,
+    v1: u64,
+    v0_128: u64,
+    v1_128: u64,
+}
+
+/// The routine arguments logged by `AFL++` `CmpLog`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AFLppCmpLogFnOperands {
+    v0: [u8; AFL_CMPLOG_RTN_LEN],
+    v0_len: u8,
+    v1: [u8; AFL_CMPLOG_RTN_LEN],
+    v1_len: u8,
+}
+
+/// Union of `AFL++` `CmpLog` operands and routines
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union AFLppCmpLogVals {
+    operands: [[AFLppCmpLogOperands; AFL_CMPLOG_MAP_H]; AFL_CMPLOG_MAP_W],
+    fn_operands: [[AFLppCmpLogFnOperands; AFL_CMPLOG_MAP_RTN_H]; AFL_CMPLOG_MAP_W],
+}
+
+impl Debug for AFLppCmpLogVals {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AFLppCmpLogVals").finish_non_exhaustive()
+    }
+}
+
+/// The `CmpLog` map of `AFL++` 4, as written by binaries built with `afl-clang-lto -c` or `AFL_LLVM_CMPLOG`
+/// to the `__AFL_CMPLOG_SHM_ID` shared memory.
+/// It is too large for the stack, map it from a shared memory instead.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AFLppCmpLogMap {
+    headers: [AFLppCmpLogHeader; AFL_CMPLOG_MAP_W],
+    vals: AFLppCmpLogVals,
+}
+
+impl CmpMap for AFLppCmpLogMap {
+    fn len(&self) -> usize {
+        AFL_CMPLOG_MAP_W
+    }
+
+    fn executions_for(&self, idx: usize) -> usize {
+        self.headers[idx].hits()
+    }
+
+    fn usable_executions_for(&self, idx: usize) -> usize {
+        if self.headers[idx].type_() == AFL_CMP_TYPE_RTN {
+            self.executions_for(idx).min(AFL_CMPLOG_MAP_RTN_H)
+        } else {
+            self.executions_for(idx).min(AFL_CMPLOG_MAP_H)
+        }
+    }
+
+    fn values_of(&self, idx: usize, execution: usize) -> CmpValues {
+        if self.headers[idx].type_() == AFL_CMP_TYPE_RTN {
+            unsafe {
+                let operands = &self.vals.fn_operands[idx][execution];
+                let v0_len = (operands.v0_len as usize).min(AFL_CMPLOG_RTN_LEN);
+                let v1_len = (operands.v1_len as usize).min(AFL_CMPLOG_RTN_LEN);
+                CmpValues::Bytes((
+                    operands.v0[..v0_len].to_vec(),
+                    operands.v1[..v1_len].to_vec(),
+                ))
+            }
+        } else {
+            let operands = unsafe { &self.vals.operands[idx][execution] };
+            // 128 bit comparisons are reduced to their lower half
+            match self.headers[idx].shape() {
+                0 => CmpValues::U8((operands.v0 as u8, operands.v1 as u8)),
+                1 => CmpValues::U16((operands.v0 as u16, operands.v1 as u16)),
+                2 | 3 => CmpValues::U32((operands.v0 as u32, operands.v1 as u32)),
+                _ => CmpValues::U64((operands.v0, operands.v1)),
+            }
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        // Like `afl-fuzz`, we reset just the headers
+        for header in &mut self.headers {
+            *header = AFLppCmpLogHeader::default();
+        }
+        Ok(())
+    }
+}
+
+/// A [`CmpObserver`] observer for `CmpLog`, over a [`CmpLogMap`] or an [`AFLppCmpLogMap`]
 #[derive(Debug)]
-pub struct CmpLogObserver<'a> {
-    map: OwnedRefMut<'a, CmpLogMap>,
+pub struct CmpLogObserver<'a, CM = CmpLogMap>
+where
+    CM: CmpMap,
+{
+    map: OwnedRefMut<'a, CM>,
     size: Option<OwnedRefMut<'a, usize>>,
     add_meta: bool,
     name: String,
 }
 
-impl<'a, I, S> CmpObserver<CmpLogMap, I, S> for CmpLogObserver<'a>
+impl<'a, CM, I, S> CmpObserver<CM, I, S> for CmpLogObserver<'a, CM>
 where
+    CM: CmpMap,
     S: HasMetadata,
 {
     /// Get the number of usable cmps (all by default)
@@ -193,19 +329,20 @@ where
         }
     }
 
-    fn cmp_map(&self) -> &CmpLogMap {
+    fn cmp_map(&self) -> &CM {
         self.map.as_ref()
     }
 
-    fn cmp_map_mut(&mut self) -> &mut CmpLogMap {
+    fn cmp_map_mut(&mut self) -> &mut CM {
         self.map.as_mut()
     }
 }
 
-impl<'a, I, S> Observer<I, S> for CmpLogObserver<'a>
+impl<'a, CM, I, S> Observer<I, S> for CmpLogObserver<'a, CM>
 where
+    CM: CmpMap,
     S: HasMetadata,
-    Self: CmpObserver<CmpLogMap, I, S>,
+    Self: CmpObserver<CM, I, S>,
 {
     fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
         self.map.as_mut().reset()?;
@@ -226,16 +363,22 @@ where
     }
 }
 
-impl<'a> Named for CmpLogObserver<'a> {
+impl<'a, CM> Named for CmpLogObserver<'a, CM>
+where
+    CM: CmpMap,
+{
     fn name(&self) -> &str {
         &self.name
     }
 }
 
-impl<'a> CmpLogObserver<'a> {
+impl<'a, CM> CmpLogObserver<'a, CM>
+where
+    CM: CmpMap,
+{
     /// Creates a new [`CmpLogObserver`] with the given name.
     #[must_use]
-    pub fn new(name: &'static str, map: &'a mut CmpLogMap, add_meta: bool) -> Self {
+    pub fn new(name: &'static str, map: &'a mut CM, add_meta: bool) -> Self {
         Self {
             name: name.to_string(),
             size: None,
@@ -246,3 +389,64 @@ impl<'a> CmpLogObserver<'a> {
 
     // TODO with_size
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{alloc::alloc_zeroed, boxed::Box};
+    use core::alloc::Layout;
+
+    use libafl::observers::{CmpMap, CmpValues};
+
+    use crate::cmplog::{
+        AFLppCmpLogFnOperands, AFLppCmpLogHeader, AFLppCmpLogMap, AFLppCmpLogOperands,
+        AFL_CMPLOG_MAP_RTN_H, AFL_CMPLOG_RTN_LEN, AFL_CMP_TYPE_INS, AFL_CMP_TYPE_RTN,
+    };
+
+    #[test]
+    fn test_aflpp_cmplog_map() {
+        // The map is too large for the stack, the layout of the map aligns the allocation
+        #[allow(clippy::cast_ptr_alignment)]
+        let mut map = unsafe {
+            Box::from_raw(alloc_zeroed(Layout::new::<AFLppCmpLogMap>()).cast::<AFLppCmpLogMap>())
+        };
+
+        // 2 hits of a 2 byte comparison, with id 5 and the overflow bit set
+        let header = AFLppCmpLogHeader(1 << 63 | AFL_CMP_TYPE_INS << 53 | 1 << 48 | 5 << 24 | 2);
+        assert_eq!(header.hits(), 2);
+        assert_eq!(header.shape(), 1);
+        assert_eq!(header.type_(), AFL_CMP_TYPE_INS);
+        map.headers[3] = header;
+        unsafe {
+            map.vals.operands[3][1] = AFLppCmpLogOperands {
+                v0: 0x1_1234,
+                v1: 0xabcd,
+                ..AFLppCmpLogOperands::default()
+            };
+        }
+        assert_eq!(map.executions_for(3), 2);
+        assert_eq!(map.usable_executions_for(3), 2);
+        assert_eq!(map.values_of(3, 1), CmpValues::U16((0x1234, 0xabcd)));
+
+        // 40 hits of a routine, more than the routine map holds
+        map.headers[7] = AFLppCmpLogHeader(AFL_CMP_TYPE_RTN << 53 | 40);
+        let mut operands = AFLppCmpLogFnOperands {
+            v0: [0; AFL_CMPLOG_RTN_LEN],
+            v0_len: 3,
+            v1: [b'x'; AFL_CMPLOG_RTN_LEN],
+            v1_len: 200,
+        };
+        operands.v0[..3].copy_from_slice(b"abc");
+        unsafe {
+            map.vals.fn_operands[7][0] = operands;
+        }
+        assert_eq!(map.usable_executions_for(7), AFL_CMPLOG_MAP_RTN_H);
+        assert_eq!(
+            map.values_of(7, 0),
+            CmpValues::Bytes((b"abc".to_vec(), vec![b'x'; AFL_CMPLOG_RTN_LEN]))
+        );
+
+        map.reset().unwrap();
+        assert_eq!(map.executions_for(3), 0);
+        assert_eq!(map.executions_for(7), 0);
+    }
+}
@@ -29,3 +29,28 @@ MAYBE_THREAD_LOCAL prev_loc_t __afl_prev_loc[NGRAM_SIZE_MAX];
 MAYBE_THREAD_LOCAL prev_loc_t __afl_prev_caller[CTX_MAX_K];
 MAYBE_THREAD_LOCAL uint32_t   __afl_prev_ctx;
 MAYBE_THREAD_LOCAL prev_loc_t __afl_acc_prev_loc;
+
+// The calling context, maintained by the ctx pass of libafl_cc, for sancov_pcguard
+MAYBE_THREAD_LOCAL uint32_t __libafl_sancov_ctx;
+// The scrambled ids of the previous blocks, the most recent first, for the ngram coverage of sancov_pcguard
+MAYBE_THREAD_LOCAL prev_loc_t __libafl_sancov_prev_locs[NGRAM_SIZE_MAX];
+
+// Hash the previous ngram_size - 1 blocks and, if use_ctx is set, the calling context into loc,
+// then make loc the most recent previous block.
+// The state is thread local, like __afl_prev_loc, so it is kept here for sancov_pcguard.
+uint32_t __libafl_sancov_hash_edge(uint32_t loc, uint32_t ngram_size, int use_ctx) {
+  uint32_t hash = loc;
+  if (ngram_size > 1) {
+    for (uint32_t i = 0; i < ngram_size - 1; i++) {
+      hash ^= __libafl_sancov_prev_locs[i];
+    }
+    for (uint32_t i = ngram_size - 2; i > 0; i--) {
+      __libafl_sancov_prev_locs[i] = __libafl_sancov_prev_locs[i - 1];
+    }
+    __libafl_sancov_prev_locs[0] = loc >> 1;
+  }
+  if (use_ctx) {
+    hash ^= __libafl_sancov_ctx;
+  }
+  return hash;
+}
@@ -76,10 +76,17 @@ pub unsafe fn edges_map_from_ptr<'a>() -> OwnedSliceMut<'a, u8> {
 }
 
 /// Gets the current maximum number of edges tracked.
+/// With the `sancov_ngram4`, `sancov_ngram8` and `sancov_ctx` features, edges are hashed over the whole map.
 #[must_use]
 pub fn edges_max_num() -> usize {
     unsafe {
-        if MAX_EDGES_NUM > 0 {
+        if MAX_EDGES_NUM > 0
+            && !cfg!(any(
+                feature = "sancov_ngram4",
+                feature = "sancov_ngram8",
+                feature = "sancov_ctx"
+            ))
+        {
             MAX_EDGES_NUM
         } else {
             #[cfg(feature = "pointer_maps")]
@@ -0,0 +1,169 @@
+//! A forkserver executor for `AFL++` `CmpLog` binaries, built with `afl-clang-lto -c` or `AFL_LLVM_CMPLOG=1`.
+//! The target logs its comparisons to the `__AFL_CMPLOG_SHM_ID` shared memory, observed by a [`CmpLogObserver`].
+//! Use it as tracer in a `TracingStage`, next to the executor of the main target.
+
+use core::fmt::{self, Debug, Formatter};
+
+use libafl::{
+    bolts::{
+        fs::OutFile,
+        shmem::{ShMem, ShMemProvider},
+    },
+    executors::{
+        forkserver::{Forkserver, ForkserverExecutorBuilder, HasForkserver},
+        Executor, ExitKind, ForkserverExecutor, HasObservers,
+    },
+    inputs::{HasTargetBytes, Input},
+    observers::ObserversTuple,
+    Error,
+};
+
+use crate::cmplog::{AFLppCmpLogMap, CmpLogObserver};
+
+/// The observers of a [`CmpLogForkserverExecutor`], the [`CmpLogObserver`] first
+pub type CmpLogForkserverObservers<OT> = (CmpLogObserver<'static, AFLppCmpLogMap>, OT);
+
+/// A [`ForkserverExecutor`] for an `AFL++` `CmpLog` binary.
+/// It owns the `__AFL_CMPLOG_SHM_ID` shared memory, observed by a [`CmpLogObserver`] named `cmplog`
+/// that adds the logged values to the state metadata.
+pub struct CmpLogForkserverExecutor<I, OT, S, SP>
+where
+    OT: Debug,
+    SP: ShMemProvider,
+{
+    executor: ForkserverExecutor<I, CmpLogForkserverObservers<OT>, S, SP>,
+    /// Dropped after the executor, and its [`CmpLogObserver`]
+    cmplog_shmem: SP::ShMem,
+}
+
+impl<I, OT, S, SP> Debug for CmpLogForkserverExecutor<I, OT, S, SP>
+where
+    OT: Debug,
+    SP: ShMemProvider,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CmpLogForkserverExecutor")
+            .field("executor", &self.executor)
+            .field("cmplog_shmem", &self.cmplog_shmem)
+            .finish()
+    }
+}
+
+impl<I, OT, S, SP> CmpLogForkserverExecutor<I, OT, S, SP>
+where
+    I: Input + HasTargetBytes,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+    CmpLogForkserverObservers<OT>: ObserversTuple<I, S>,
+{
+    /// Creates a new [`CmpLogForkserverExecutor`] for the `CmpLog` binary of the `builder`.
+    /// The shared memory of the map comes from the [`ShMemProvider`] of the `builder`, if it has one,
+    /// and its id is only passed to the target.
+    /// The `observers` are run after the [`CmpLogObserver`].
+    pub fn new(
+        mut builder: ForkserverExecutorBuilder<'_, SP>,
+        observers: OT,
+    ) -> Result<Self, Error> {
+        let mut cmplog_shmem = match builder.shmem_provider_mut() {
+            Some(shmem_provider) => shmem_provider.new_shmem_object::<AFLppCmpLogMap>()?,
+            None => SP::new()?.new_shmem_object::<AFLppCmpLogMap>()?,
+        };
+
+        // The zeroed shared memory is a valid empty map, and lives as long as the executor
+        let cmplog_map =
+            unsafe { &mut *(cmplog_shmem.as_object_mut::<AFLppCmpLogMap>() as *mut _) };
+        let cmplog_observer = CmpLogObserver::new("cmplog", cmplog_map, true);
+
+        let executor = builder
+            .env("__AFL_CMPLOG_SHM_ID", cmplog_shmem.id().to_string())
+            .build((cmplog_observer, observers))?;
+        Ok(Self {
+            executor,
+            cmplog_shmem,
+        })
+    }
+
+    /// The wrapped [`ForkserverExecutor`]
+    #[must_use]
+    pub fn executor(&self) -> &ForkserverExecutor<I, CmpLogForkserverObservers<OT>, S, SP> {
+        &self.executor
+    }
+}
+
+impl<EM, I, OT, S, SP, Z> Executor<EM, I, S, Z> for CmpLogForkserverExecutor<I, OT, S, SP>
+where
+    I: Input + HasTargetBytes,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+    CmpLogForkserverObservers<OT>: ObserversTuple<I, S>,
+{
+    #[inline]
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        self.executor.run_target(fuzzer, state, mgr, input)
+    }
+}
+
+impl<I, OT, S, SP> HasObservers<I, CmpLogForkserverObservers<OT>, S>
+    for CmpLogForkserverExecutor<I, OT, S, SP>
+where
+    I: Input + HasTargetBytes,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+    CmpLogForkserverObservers<OT>: ObserversTuple<I, S>,
+{
+    #[inline]
+    fn observers(&self) -> &CmpLogForkserverObservers<OT> {
+        self.executor.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut CmpLogForkserverObservers<OT> {
+        self.executor.observers_mut()
+    }
+}
+
+impl<I, OT, S, SP> HasForkserver for CmpLogForkserverExecutor<I, OT, S, SP>
+where
+    I: Input + HasTargetBytes,
+    OT: ObserversTuple<I, S>,
+    SP: ShMemProvider,
+    CmpLogForkserverObservers<OT>: ObserversTuple<I, S>,
+{
+    type SP = SP;
+
+    #[inline]
+    fn forkserver(&self) -> &Forkserver {
+        self.executor.forkserver()
+    }
+
+    #[inline]
+    fn forkserver_mut(&mut self) -> &mut Forkserver {
+        self.executor.forkserver_mut()
+    }
+
+    #[inline]
+    fn out_file(&self) -> &OutFile {
+        HasForkserver::out_file(&self.executor)
+    }
+
+    #[inline]
+    fn out_file_mut(&mut self) -> &mut OutFile {
+        self.executor.out_file_mut()
+    }
+
+    #[inline]
+    fn shmem(&self) -> &Option<SP::ShMem> {
+        self.executor.shmem()
+    }
+
+    #[inline]
+    fn shmem_mut(&mut self) -> &mut Option<SP::ShMem> {
+        self.executor.shmem_mut()
+    }
+}
@@ -93,5 +93,25 @@ pub use value_profile::*;
 pub mod cmplog;
 pub use cmplog::*;
 
+#[cfg(unix)]
+pub mod budget;
+#[cfg(unix)]
+pub use budget::*;
+
+#[cfg(unix)]
+pub mod malloc_hooks;
+#[cfg(unix)]
+pub use malloc_hooks::*;
+
+#[cfg(all(unix, feature = "std"))]
+pub mod lsan;
+#[cfg(all(unix, feature = "std"))]
+pub use lsan::*;
+
+#[cfg(all(feature = "forkserver", unix))]
+pub mod forkserver;
+#[cfg(all(feature = "forkserver", unix))]
+pub use forkserver::*;
+
 #[cfg(feature = "std")]
 pub mod drcov;
@@ -0,0 +1,339 @@
+//! Leak detection for in-process harnesses built with `LSan`, standalone or as part of `ASan`,
+//! like the `-detect_leaks` of `libFuzzer`.
+//! Run the harness in [`run_leak_checked`]: if it made more allocations than frees, it runs
+//! `__lsan_do_recoverable_leak_check` and aborts on leaks. The crash handler of the in-process executor
+//! then keeps the input as objective, with the report of the [`LeakObserver`] as [`LeakMetadata`]
+//! if the objective has a [`LeakFeedback`], and restarts the fuzzer.
+//! The leaks stay allocated and later checks would report them again, so, as `libFuzzer`, we do not go on after a leak.
+//!
+//! Leak detection has to be enabled in the sanitizer options, the defaults of `libafl_targets` disable it:
+//! `ASAN_OPTIONS=detect_leaks=1:leak_check_at_exit=0`.
+
+use alloc::string::{String, ToString};
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{Read, Seek, SeekFrom},
+    os::unix::io::AsRawFd,
+    process,
+};
+
+use libafl::{
+    bolts::tuples::Named,
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::Feedback,
+    inputs::Input,
+    observers::{Observer, ObserversTuple},
+    state::{HasClientPerfMonitor, HasMetadata},
+    Error,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::malloc_hooks::{install_malloc_hooks, start_malloc_tracing, stop_malloc_tracing};
+
+extern "C" {
+    fn libafl_lsan_leak_check(report_fd: i32) -> i32;
+}
+
+/// Run the `LSan` leak check, returns the report if leaks were found.
+/// Returns an error if the target was not built with `LSan`.
+pub fn lsan_leak_check() -> Result<Option<String>, Error> {
+    // The report goes to an unlinked temporary file, a pipe could fill up
+    let path = env::temp_dir().join(format!("libafl_lsan_{}", process::id()));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)?;
+    fs::remove_file(&path)?;
+
+    match unsafe { libafl_lsan_leak_check(file.as_raw_fd()) } {
+        -1 => Err(Error::Unknown(
+            "The target was not built with LSan".to_string(),
+        )),
+        0 => Ok(None),
+        _ => {
+            let mut report = String::new();
+            file.seek(SeekFrom::Start(0))?;
+            file.read_to_string(&mut report)?;
+            Ok(Some(report))
+        }
+    }
+}
+
+/// The report of the leak found by [`run_leak_checked`], for the [`LeakObserver`]
+static mut LEAK_REPORT: Option<String> = None;
+
+/// If a failing leak check was already reported, it fails the same way for every run
+static LEAK_CHECK_FAILURE_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Run the `harness`, counting its allocations and frees like the `MallocFreeTracer` of `libFuzzer`.
+/// If there were more allocations than frees and `LSan` finds leaks, the process aborts,
+/// to be handled as crash by the in-process executor, i.e.
+/// `run_leak_checked(|| libfuzzer_test_one_input(buf))`.
+/// Without a [`LeakObserver`], the allocations are not hooked and nothing is checked.
+/// If the leak check fails, for example without `LSan`, leaks are not detected and the error is printed once.
+pub fn run_leak_checked<F, R>(harness: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    start_malloc_tracing();
+    let ret = harness();
+    if stop_malloc_tracing() {
+        match lsan_leak_check() {
+            Ok(Some(report)) => {
+                println!("Memory leak detected, aborting.");
+                unsafe {
+                    LEAK_REPORT = Some(report);
+                }
+                process::abort();
+            }
+            Ok(None) => (),
+            Err(err) => {
+                if !LEAK_CHECK_FAILURE_REPORTED.swap(true, Ordering::Relaxed) {
+                    println!("Leak check failed, leaks will not be detected: {:?}", err);
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// The report of the leaks found by `LSan`, added to the testcases by the [`LeakFeedback`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LeakMetadata {
+    /// The report printed by `LSan`
+    pub report: String,
+}
+
+libafl::impl_serdeany!(LeakMetadata);
+
+/// An [`Observer`] installing the sanitizer malloc hooks, for [`run_leak_checked`],
+/// and keeping the `LSan` report if the run leaked.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LeakObserver {
+    name: String,
+    report: Option<String>,
+}
+
+impl LeakObserver {
+    /// Creates a new [`LeakObserver`] with the given name.
+    /// Returns an error if the target was not built with a sanitizer.
+    pub fn new(name: &'static str) -> Result<Self, Error> {
+        if !install_malloc_hooks() {
+            return Err(Error::Unknown(
+                "The target was not built with a sanitizer, cannot hook malloc".to_string(),
+            ));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            report: None,
+        })
+    }
+
+    /// The `LSan` report of the last run, if it leaked
+    #[must_use]
+    pub fn report(&self) -> &Option<String> {
+        &self.report
+    }
+}
+
+impl<I, S> Observer<I, S> for LeakObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.report = None;
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.report = unsafe { LEAK_REPORT.take() };
+        Ok(())
+    }
+}
+
+impl Named for LeakObserver {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A [`LeakFeedback`] reports as interesting if the [`LeakObserver`] found leaks,
+/// and adds the report to the testcase as [`LeakMetadata`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LeakFeedback {
+    name: String,
+    report: Option<String>,
+}
+
+impl<I, S> Feedback<I, S> for LeakFeedback
+where
+    I: Input,
+    S: HasClientPerfMonitor,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<I>,
+        OT: ObserversTuple<I, S>,
+    {
+        // TODO Replace with match_name_type when stable
+        let observer = observers.match_name::<LeakObserver>(self.name()).unwrap();
+        self.report = observer.report().clone();
+        Ok(self.report.is_some())
+    }
+
+    /// Append the `LSan` report to the testcase
+    #[inline]
+    fn append_metadata(&mut self, _state: &mut S, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(report) = self.report.take() {
+            testcase.add_metadata(LeakMetadata { report });
+        }
+        Ok(())
+    }
+
+    /// Discard the stored report in case that the testcase is not added
+    #[inline]
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.report = None;
+        Ok(())
+    }
+}
+
+impl Named for LeakFeedback {
+    #[inline]
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl LeakFeedback {
+    /// Creates a new [`LeakFeedback`] for the given [`LeakObserver`].
+    #[must_use]
+    pub fn new_with_observer(observer: &LeakObserver) -> Self {
+        Self {
+            name: observer.name().to_string(),
+            report: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use libafl::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{InMemoryCorpus, Testcase},
+        events::SimpleEventManager,
+        executors::ExitKind,
+        feedbacks::Feedback,
+        inputs::BytesInput,
+        monitors::NopMonitor,
+        observers::Observer,
+        state::{HasMetadata, StdState},
+    };
+
+    use super::{LeakFeedback, LeakMetadata, LeakObserver, LEAK_REPORT};
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, (), BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    fn test_state() -> TestState {
+        StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            (),
+        )
+    }
+
+    /// A [`LeakObserver`] without the malloc hooks, that need a sanitizer
+    fn test_observer() -> LeakObserver {
+        LeakObserver {
+            name: "leak".to_string(),
+            report: None,
+        }
+    }
+
+    #[test]
+    fn test_leak_observer() {
+        let mut state = test_state();
+        let input = BytesInput::new(vec![]);
+        let mut observer = test_observer();
+
+        unsafe {
+            LEAK_REPORT = Some("leaked 8 bytes".to_string());
+        }
+        Observer::<BytesInput, TestState>::pre_exec(&mut observer, &mut state, &input).unwrap();
+        observer
+            .post_exec(&mut state, &input, &ExitKind::Crash)
+            .unwrap();
+        assert_eq!(observer.report().as_deref(), Some("leaked 8 bytes"));
+        // The report is taken, the next run does not see it
+        assert!(unsafe { LEAK_REPORT.is_none() });
+
+        Observer::<BytesInput, TestState>::pre_exec(&mut observer, &mut state, &input).unwrap();
+        assert!(observer.report().is_none());
+    }
+
+    #[test]
+    fn test_leak_feedback() {
+        let mut state = test_state();
+        let mut manager = SimpleEventManager::new(NopMonitor::new());
+        let input = BytesInput::new(vec![]);
+        let mut observers = tuple_list!(test_observer());
+        let mut feedback = LeakFeedback::new_with_observer(&observers.0);
+
+        // No leak
+        assert!(!feedback
+            .is_interesting(&mut state, &mut manager, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+
+        observers.0.report = Some("leaked 8 bytes".to_string());
+        assert!(feedback
+            .is_interesting(
+                &mut state,
+                &mut manager,
+                &input,
+                &observers,
+                &ExitKind::Crash
+            )
+            .unwrap());
+        let mut testcase = Testcase::<BytesInput>::new(input.clone());
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        assert_eq!(
+            testcase.metadata().get::<LeakMetadata>().unwrap().report,
+            "leaked 8 bytes"
+        );
+
+        // A discarded report is not added to the next testcase
+        assert!(feedback
+            .is_interesting(
+                &mut state,
+                &mut manager,
+                &input,
+                &observers,
+                &ExitKind::Crash
+            )
+            .unwrap());
+        feedback.discard_metadata(&mut state, &input).unwrap();
+        let mut testcase = Testcase::<BytesInput>::new(input);
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        assert!(testcase.metadata().get::<LeakMetadata>().is_none());
+    }
+}
@@ -0,0 +1,56 @@
+#include "common.h"
+
+#include <stddef.h>
+#include <stdio.h>
+
+#ifndef _WIN32
+
+  #include <unistd.h>
+
+typedef void (*malloc_hook_t)(const volatile void *ptr, size_t size);
+typedef void (*free_hook_t)(const volatile void *ptr);
+
+// Only defined by the sanitizers runtimes
+EXT_FUNC(__sanitizer_install_malloc_and_free_hooks, int,
+         (malloc_hook_t malloc_hook, free_hook_t free_hook), false);
+
+int libafl_install_malloc_hooks(malloc_hook_t malloc_hook,
+                                free_hook_t   free_hook) {
+
+  if (!CHECK_WEAK_FN(__sanitizer_install_malloc_and_free_hooks)) return 0;
+  return __sanitizer_install_malloc_and_free_hooks(malloc_hook, free_hook);
+
+}
+
+// Only defined by the LSan runtime, also part of ASan
+EXT_FUNC(__lsan_do_recoverable_leak_check, int, (void), false);
+
+// Runs the LSan leak check, writing the report to `report_fd` instead of
+// stderr if not negative. Returns -1 if the target has no LSan runtime.
+int libafl_lsan_leak_check(int report_fd) {
+
+  if (!CHECK_WEAK_FN(__lsan_do_recoverable_leak_check)) return -1;
+
+  int stderr_fd = -1;
+  if (report_fd >= 0) {
+
+    fflush(stderr);
+    stderr_fd = dup(2);
+    dup2(report_fd, 2);
+
+  }
+
+  int leaks = __lsan_do_recoverable_leak_check();
+
+  if (stderr_fd >= 0) {
+
+    dup2(stderr_fd, 2);
+    close(stderr_fd);
+
+  }
+
+  return leaks;
+
+}
+
+#endif
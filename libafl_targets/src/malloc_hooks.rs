@@ -0,0 +1,115 @@
+//! Hooks on the allocations of targets built with a sanitizer, with `__sanitizer_install_malloc_and_free_hooks`.
+//! An allocation larger than the malloc limit raises `SIGUSR1`, reported as [`libafl::executors::ExitKind::Oom`]
+//! by the in-process executors, like the `-malloc_limit_mb` of `libFuzzer`.
+//! Between [`start_malloc_tracing`] and [`stop_malloc_tracing`], the hooks also count the allocations and frees,
+//! like the `MallocFreeTracer` of `libFuzzer`, for the leak detection of [`crate::lsan::run_leak_checked`].
+
+use core::{
+    ffi::c_void,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use libafl::bolts::os::unix_signals::{raise, Signal};
+
+extern "C" {
+    fn libafl_install_malloc_hooks(
+        malloc_hook: extern "C" fn(*const c_void, usize),
+        free_hook: extern "C" fn(*const c_void),
+    ) -> i32;
+}
+
+/// The size limit of a single allocation, in bytes, `0` for no limit
+pub static mut MALLOC_LIMIT: usize = 0;
+
+/// The number of allocations since the last [`start_malloc_tracing`]
+pub static MALLOC_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// The number of frees since the last [`start_malloc_tracing`]
+pub static FREE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// If the allocations and frees are counted, only while the harness runs
+static MALLOC_TRACING: AtomicBool = AtomicBool::new(false);
+
+static mut MALLOC_HOOKS_INSTALLED: bool = false;
+
+extern "C" fn malloc_hook(_ptr: *const c_void, size: usize) {
+    if MALLOC_TRACING.load(Ordering::Relaxed) {
+        MALLOC_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+    unsafe {
+        let limit = MALLOC_LIMIT;
+        if limit != 0 && size > limit {
+            // The signal handler may allocate too
+            MALLOC_LIMIT = 0;
+            raise(Signal::SigUser1);
+            MALLOC_LIMIT = limit;
+        }
+    }
+}
+
+extern "C" fn free_hook(_ptr: *const c_void) {
+    if MALLOC_TRACING.load(Ordering::Relaxed) {
+        FREE_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Start counting the allocations and frees, call right before the harness
+pub fn start_malloc_tracing() {
+    MALLOC_COUNT.store(0, Ordering::Relaxed);
+    FREE_COUNT.store(0, Ordering::Relaxed);
+    MALLOC_TRACING.store(true, Ordering::SeqCst);
+}
+
+/// Stop counting the allocations and frees, call right after the harness.
+/// Returns `true` if there were more allocations than frees since [`start_malloc_tracing`].
+pub fn stop_malloc_tracing() -> bool {
+    MALLOC_TRACING.store(false, Ordering::SeqCst);
+    MALLOC_COUNT.load(Ordering::Relaxed) > FREE_COUNT.load(Ordering::Relaxed)
+}
+
+/// Install the malloc hooks, once.
+/// Returns `false` if the target was not built with a sanitizer.
+pub fn install_malloc_hooks() -> bool {
+    unsafe {
+        if !MALLOC_HOOKS_INSTALLED {
+            MALLOC_HOOKS_INSTALLED = libafl_install_malloc_hooks(malloc_hook, free_hook) != 0;
+        }
+        MALLOC_HOOKS_INSTALLED
+    }
+}
+
+/// Limit the size of single allocations to `malloc_limit_mb` MB, `0` for no limit.
+/// Returns `false` if the target was not built with a sanitizer, then the limit is not enforced.
+pub fn set_malloc_limit_mb(malloc_limit_mb: usize) -> bool {
+    unsafe {
+        MALLOC_LIMIT = malloc_limit_mb << 20;
+    }
+    install_malloc_hooks()
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr;
+
+    use super::{free_hook, malloc_hook, start_malloc_tracing, stop_malloc_tracing};
+
+    #[test]
+    fn test_malloc_tracing() {
+        // More allocations than frees
+        start_malloc_tracing();
+        malloc_hook(ptr::null(), 16);
+        malloc_hook(ptr::null(), 16);
+        free_hook(ptr::null());
+        assert!(stop_malloc_tracing());
+
+        // Balanced, and the counts of the previous run are reset
+        start_malloc_tracing();
+        malloc_hook(ptr::null(), 16);
+        free_hook(ptr::null());
+        assert!(!stop_malloc_tracing());
+
+        // Allocations outside of the harness are not counted
+        malloc_hook(ptr::null(), 16);
+        start_malloc_tracing();
+        assert!(!stop_malloc_tracing());
+    }
+}
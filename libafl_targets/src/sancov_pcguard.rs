@@ -10,7 +10,62 @@ compile_error!(
     "the libafl_targets `sancov_pcguard_edges` and `sancov_pcguard_hitcounts` features are mutually exclusive."
 );
 
+#[cfg(all(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
+#[cfg(not(any(doc, feature = "clippy")))]
+compile_error!(
+    "the libafl_targets `sancov_ngram4` and `sancov_ngram8` features are mutually exclusive."
+);
+
+/// The number of blocks hashed into an edge, including the current one
+#[cfg(feature = "sancov_ngram4")]
+pub const NGRAM_SIZE: usize = 4;
+/// The number of blocks hashed into an edge, including the current one
+#[cfg(all(feature = "sancov_ngram8", not(feature = "sancov_ngram4")))]
+pub const NGRAM_SIZE: usize = 8;
+
+/// The number of blocks hashed into an edge, only the current one without `N-gram` coverage
+#[cfg(all(
+    feature = "sancov_ctx",
+    not(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))
+))]
+const NGRAM_SIZE: usize = 1;
+
+#[cfg(any(
+    feature = "sancov_ngram4",
+    feature = "sancov_ngram8",
+    feature = "sancov_ctx"
+))]
+extern "C" {
+    /// Hashes the previous blocks and the calling context (of the `Ctx` pass of `libafl_cc`) into `loc`.
+    /// The state is thread local, like the `__afl_prev_loc` of `AFL++`, so it lives in `coverage.c`.
+    fn __libafl_sancov_hash_edge(loc: u32, ngram_size: u32, use_ctx: i32) -> u32;
+}
+
+/// Hash the previous blocks and the calling context into the position of the edge in the map.
+///
+/// # Safety
+/// Accesses the thread local `N-gram` and context state of `coverage.c`.
+#[cfg(any(
+    feature = "sancov_ngram4",
+    feature = "sancov_ngram8",
+    feature = "sancov_ctx"
+))]
+#[inline]
+unsafe fn hash_edge(pos: usize, map_len: usize) -> usize {
+    // Spread the sequential guard ids over the whole map
+    let loc = (pos as u32).wrapping_mul(0x9e37_79b1);
+    let hash = __libafl_sancov_hash_edge(
+        loc,
+        NGRAM_SIZE as u32,
+        i32::from(cfg!(feature = "sancov_ctx")),
+    );
+    hash as usize % map_len
+}
+
 /// Callback for sancov `pc_guard` - usually called by `llvm` on each block or edge.
+/// With the `sancov_ngram4`, `sancov_ngram8` or `sancov_ctx` features, the previous blocks
+/// or the calling context (instrumented by the `Ctx` pass of `libafl_cc`) are hashed into the position.
+/// With the `sancov_budget` feature, the edge is counted for the [`crate::budget::BudgetExecutor`].
 ///
 /// # Safety
 /// Dereferences `guard`, reads the position from there, then dereferences the [`EDGES_MAP`] at that position.
@@ -18,6 +73,26 @@ compile_error!(
 #[no_mangle]
 pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard(guard: *mut u32) {
     let pos = *guard as usize;
+    #[cfg(all(feature = "sancov_budget", unix))]
+    crate::budget::budget_tick();
+    #[cfg(all(
+        any(
+            feature = "sancov_ngram4",
+            feature = "sancov_ngram8",
+            feature = "sancov_ctx"
+        ),
+        feature = "pointer_maps"
+    ))]
+    let pos = hash_edge(pos, EDGES_MAP_PTR_SIZE);
+    #[cfg(all(
+        any(
+            feature = "sancov_ngram4",
+            feature = "sancov_ngram8",
+            feature = "sancov_ctx"
+        ),
+        not(feature = "pointer_maps")
+    ))]
+    let pos = hash_edge(pos, EDGES_MAP.len());
     #[cfg(feature = "pointer_maps")]
     {
         #[cfg(feature = "sancov_pcguard_edges")]
@@ -1,4 +1,7 @@
 //! Value profile support for `LibAFL`
+//!
+//! To keep inputs whose comparisons get closer to being satisfied, based on the operands
+//! logged by the [`crate::CmpLogObserver`], use the [`libafl::feedbacks::CmpFeedback`].
 
 use crate::CMP_MAP_SIZE;
 